@@ -0,0 +1,34 @@
+use std::hint::black_box;
+use std::path::Path;
+
+use criterion::{Criterion, criterion_group, criterion_main};
+use rogue_reborn::{rsb, scan};
+
+/// Decode speed and pixel memory over every RSB in `data/texture`
+fn decode(c: &mut Criterion) {
+    let paths = scan::find(Path::new("data/texture")).unwrap().paths;
+    let rsbs = paths.iter()
+        .map(|path| rsb::read(path).unwrap())
+        .collect::<Vec<_>>();
+
+    let texels = rsbs.iter().map(|rsb| rsb.size()).sum::<usize>();
+    let bytes = rsbs.iter()
+        .map(|rsb| rsb.pixels.byte_len())
+        .sum::<usize>();
+    println!("{} RSBs: {texels} texels in {bytes} bytes of pixels",
+        rsbs.len());
+
+    c.bench_function("rsb::read", |b| b.iter(|| {
+        for path in &paths {
+            black_box(rsb::read(path).unwrap());
+        }
+    }));
+    c.bench_function("Rsb::to_rgba8", |b| b.iter(|| {
+        for rsb in &rsbs {
+            black_box(rsb.to_rgba8());
+        }
+    }));
+}
+
+criterion_group!(benches, decode);
+criterion_main!(benches);
@@ -0,0 +1,22 @@
+use std::path::PathBuf;
+
+use anyhow::Context;
+use rogue_reborn::map;
+
+fn main() -> anyhow::Result<()> {
+    let args = std::env::args_os().skip(1).map(PathBuf::from).collect::<Vec<_>>();
+    let [old, new] = args.as_slice() else {
+        anyhow::bail!("usage: map-diff <old.map> <new.map>");
+    };
+
+    let old_map = map::read(old)
+        .with_context(|| format!("{}", old.display()))?;
+    let new_map = map::read(new)
+        .with_context(|| format!("{}", new.display()))?;
+
+    println!("--- {}", old.display());
+    println!("+++ {}", new.display());
+    print!("{}", map::diff(&old_map, &new_map));
+
+    Ok(())
+}
@@ -0,0 +1,8 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rogue_reborn::map;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = map::read_bytes(data);
+});
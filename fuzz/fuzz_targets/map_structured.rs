@@ -0,0 +1,12 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rogue_reborn::map;
+use rogue_reborn_fuzz::MapBytes;
+
+fuzz_target!(|map: MapBytes| {
+    // Every generated MAP is well formed, so it must parse
+    if let Err(e) = map::read_bytes(&map.0) {
+        panic!("generated MAP did not parse: {e:?}");
+    }
+});
@@ -0,0 +1,20 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rogue_reborn::rsb::{self, RowDecoder};
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(rsb) = rsb::read_bytes(data) else {
+        return;
+    };
+
+    // The streaming decoder must agree with decoding the whole RSB
+    let rgba = rsb.to_rgba8();
+    let mut decoder = RowDecoder::new(data).expect("header already parsed");
+    let mut row = vec![0; decoder.row_len()];
+    let mut streamed = Vec::with_capacity(rgba.len());
+    while decoder.read_row(&mut row).expect("pixels already parsed").is_some() {
+        streamed.extend_from_slice(&row);
+    }
+    assert_eq!(streamed, rgba);
+});
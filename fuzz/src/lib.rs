@@ -0,0 +1,319 @@
+//! Structure-aware input generation for the fuzz targets. Run a target with
+//! `cargo +nightly fuzz run <target>` from this directory, seeding the corpus
+//! from `data/` for the raw byte targets.
+//!
+//! Random bytes rarely get past the MAP magic and the first few section
+//! headers, so `MapBytes` builds a well-formed MAP from the fuzzer's input
+//! instead, following the same layout `map::read` expects. Each list is kept
+//! short so that one input covers every section.
+
+use arbitrary::{Arbitrary, Result, Unstructured};
+
+/// The most items generated for any one list
+const MAX_COUNT: u32 = 4;
+
+/// Dynamic object section ids the reader knows, see `map::Id`
+const DYNAMIC_IDS: [u32; 7] = [14, 15, 16, 20, 25, 31, 36];
+
+/// The bytes of a well-formed MAP file
+#[derive(Debug)]
+pub struct MapBytes(pub Vec<u8>);
+
+impl<'a> Arbitrary<'a> for MapBytes {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let mut w = Writer { u, out: Vec::new() };
+        w.map()?;
+        Ok(Self(w.out))
+    }
+}
+
+struct Writer<'a, 'b> {
+    u: &'b mut Unstructured<'a>,
+    out: Vec<u8>,
+}
+
+impl Writer<'_, '_> {
+    fn map(&mut self) -> Result<()> {
+        self.bytes_cstring(b"BeginMapv2.1");
+        self.any_u32()?;
+        self.list(true, Self::material)?;
+        self.list(true, Self::object)?;
+        self.list(true, Self::portal)?;
+        // Lights are only a count
+        self.section_header()?;
+        self.any_u32()?;
+        self.list(true, Self::dynamic_object)?;
+        self.list(true, Self::room)?;
+        self.list(true, Self::transition)?;
+        self.list(true, Self::planning_level)?;
+        self.bytes_cstring(b"EndMap");
+        Ok(())
+    }
+
+    fn material(&mut self) -> Result<()> {
+        self.section_header()?;
+        self.cstring()?;
+        self.f32s(1)?;
+        self.any_u32()?;
+        let address_mode = *self.u.choose(&[0, 1, 3])?;
+        self.u32(address_mode);
+        // Ambient, diffuse and specular colours then specular level
+        self.f32s(4 * 3 + 1)?;
+        self.any_u8()
+    }
+
+    fn object(&mut self) -> Result<()> {
+        self.section_header()?;
+        self.section_header()?;
+        self.list(false, |w| w.f32s(3))?;
+        self.list(false, Self::object_data)?;
+        // Collision vertices and faces
+        self.list(false, |w| w.f32s(3))?;
+        self.list(false, |w| w.f32s(4))?;
+        // Tags
+        self.list(false, |w| w.u16s(8))?;
+        self.list(false, |w| {
+            w.cstring()?;
+            w.any_u32()?;
+            w.list(false, |w| w.u16s(1))
+        })
+    }
+
+    fn object_data(&mut self) -> Result<()> {
+        self.any_u32()?;
+        let faces = self.count()?;
+        self.u32(faces);
+        self.f32s(faces * 4)?;
+        self.u16s(faces * 3 * 2)?;
+        let vertices = self.count()?;
+        self.u32(vertices);
+        // Normal, UV and colour of each vertex
+        self.f32s(vertices * (3 + 2 + 4))
+    }
+
+    fn portal(&mut self) -> Result<()> {
+        self.section_header()?;
+        self.list(false, |w| w.f32s(3))?;
+        self.any_u32()?;
+        self.any_u32()
+    }
+
+    fn dynamic_object(&mut self) -> Result<()> {
+        let id = *self.u.choose(&DYNAMIC_IDS)?;
+        self.any_u32()?;
+        self.u32(id);
+        self.section_name()?;
+        self.cstring()?;
+        self.transformation_matrix()?;
+        match id {
+            14 => {
+                self.dynamic_common()?;
+                // A count of zero switches to the flat layout
+                let structs = self.count()?;
+                self.u32(structs);
+                if structs > 0 {
+                    for _ in 0..structs {
+                        self.cstring()?;
+                        self.f32s(9)?;
+                        self.u32s(2)?;
+                    }
+                } else {
+                    self.list(false, Self::cstring)?;
+                    self.f32s(4)?;
+                }
+            }
+            15 => {
+                self.dynamic_common()?;
+                self.any_u32()?;
+                self.list(false, Self::cstring)?;
+                self.f32s(3)?;
+                self.any_u32()?;
+                self.cstrings(3)?;
+                self.f32s(5)?;
+            }
+            16 => {
+                self.dynamic_common()?;
+                self.any_u32()?;
+                self.list(false, Self::cstring)?;
+                self.f32s(3)?;
+                self.list(false, Self::cstring)?;
+                self.cstrings(3)?;
+                self.f32s(5)?;
+            }
+            20 => self.cstring()?,
+            25 => {
+                self.cstrings(2)?;
+                self.f32s(6)?;
+                self.list(false, Self::cstring)?;
+            }
+            31 => self.list(false, |w| {
+                w.cstring()?;
+                w.f32s(8)
+            })?,
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// `DynamicObjectKindCommon`
+    fn dynamic_common(&mut self) -> Result<()> {
+        self.transformation_matrix()?;
+        self.cstring()?;
+        self.any_u32()?;
+        // Four sounds then seven collision and destruction strings
+        self.cstrings(4 + 7)
+    }
+
+    fn room(&mut self) -> Result<()> {
+        self.any_u32()?;
+        self.section_name()?;
+        let flags = [self.flag()?, self.flag()?, self.flag()?];
+        self.out.extend(flags);
+        let unknown4 = if flags[0] == 0 {
+            let flag = self.flag()?;
+            self.out.push(flag);
+            Some(flag)
+        } else {
+            None
+        };
+        if flags[2] == 1 {
+            self.f32s(6)?;
+        }
+        if unknown4 == Some(1) {
+            self.f32s(6)?;
+        }
+        self.list(false, |w| {
+            w.cstring()?;
+            w.list(false, |w| {
+                w.transformation_matrix()?;
+                w.f32s(6)
+            })?;
+            w.list(false, |w| w.f32s(1))?;
+            w.any_u8()
+        })?;
+        let heights = self.count()?;
+        self.u32(heights);
+        self.f32s(1)?;
+        self.f32s(heights * 2)
+    }
+
+    fn transition(&mut self) -> Result<()> {
+        self.cstring()?;
+        self.f32s(6)
+    }
+
+    fn planning_level(&mut self) -> Result<()> {
+        self.f32s(2)?;
+        self.list(false, Self::cstring)
+    }
+
+    /// A count then that many items. Top-level lists have a section header.
+    fn list(
+        &mut self,
+        section: bool,
+        mut item: impl FnMut(&mut Self) -> Result<()>,
+    ) -> Result<()> {
+        if section {
+            self.section_header()?;
+        }
+        let n = self.count()?;
+        self.u32(n);
+        for _ in 0..n {
+            item(self)?;
+        }
+        Ok(())
+    }
+
+    fn count(&mut self) -> Result<u32> {
+        self.u.int_in_range(0..=MAX_COUNT)
+    }
+
+    /// A 0 or 1 flag, which is what the reader branches on
+    fn flag(&mut self) -> Result<u8> {
+        self.u.int_in_range(0..=1)
+    }
+
+    fn section_header(&mut self) -> Result<()> {
+        // Section size, unused by the reader
+        self.any_u32()?;
+        self.any_u32()?;
+        self.section_name()
+    }
+
+    /// A name, sometimes behind the "Version" convention
+    fn section_name(&mut self) -> Result<()> {
+        if self.u.arbitrary()? {
+            self.bytes_cstring(b"Version");
+            self.any_u32()?;
+        }
+        self.cstring()
+    }
+
+    fn transformation_matrix(&mut self) -> Result<()> {
+        self.f32s(12)
+    }
+
+    /// A short printable ASCII string. The reader accepts Latin-1 in most
+    /// places, but a few fields are still read as UTF-8.
+    fn cstring(&mut self) -> Result<()> {
+        let len = self.u.int_in_range(0..=12)?;
+        let mut bytes = Vec::with_capacity(len);
+        for _ in 0..len {
+            bytes.push(self.u.int_in_range(b' '..=b'~')?);
+        }
+        if bytes == b"Version" {
+            bytes.push(b'_');
+        }
+        self.bytes_cstring(&bytes);
+        Ok(())
+    }
+
+    fn cstrings(&mut self, n: u32) -> Result<()> {
+        for _ in 0..n {
+            self.cstring()?;
+        }
+        Ok(())
+    }
+
+    fn bytes_cstring(&mut self, bytes: &[u8]) {
+        self.u32(bytes.len() as u32 + 1);
+        self.out.extend_from_slice(bytes);
+        self.out.push(0);
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.out.extend(value.to_le_bytes());
+    }
+
+    fn any_u32(&mut self) -> Result<()> {
+        self.u32s(1)
+    }
+
+    fn u32s(&mut self, n: u32) -> Result<()> {
+        for _ in 0..n {
+            let value = self.u.arbitrary::<u32>()?;
+            self.u32(value);
+        }
+        Ok(())
+    }
+
+    fn u16s(&mut self, n: u32) -> Result<()> {
+        for _ in 0..n {
+            self.out.extend(self.u.arbitrary::<u16>()?.to_le_bytes());
+        }
+        Ok(())
+    }
+
+    fn any_u8(&mut self) -> Result<()> {
+        let value = self.u.arbitrary::<u8>()?;
+        self.out.push(value);
+        Ok(())
+    }
+
+    fn f32s(&mut self, n: u32) -> Result<()> {
+        for _ in 0..n {
+            self.out.extend(self.u.arbitrary::<f32>()?.to_le_bytes());
+        }
+        Ok(())
+    }
+}
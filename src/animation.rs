@@ -0,0 +1,265 @@
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::Context;
+use serde::Serialize;
+
+use crate::rsb;
+
+/// How long a face holds its eyes open between blinks
+pub const OPEN_DURATION: Duration = Duration::from_millis(2500);
+
+/// How long a blink frame is shown
+pub const BLINK_DURATION: Duration = Duration::from_millis(150);
+
+/// How long each frame of a numbered sequence is shown
+pub const FRAME_DURATION: Duration = Duration::from_millis(100);
+
+/// Textures that are frames of one animation, found by naming convention.
+/// See `sequences`.
+#[derive(Clone, Debug, Serialize)]
+pub struct Sequence {
+    /// The shared file stem, e.g. `Chavez_hrt_face`
+    pub name: String,
+    /// Frames in playback order. The sequence loops.
+    pub frames: Vec<Frame>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Frame {
+    pub path: PathBuf,
+    pub duration: Duration,
+}
+
+/// The role a file plays in its sequence, from its stem. Sorts in playback
+/// order.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Role {
+    /// The stem with no suffix, e.g. the open-eyed face
+    Base,
+    /// A trailing frame number, e.g. `fire01` or `fire_2`
+    Numbered(u32),
+    /// A `_blink` suffix
+    Blink,
+}
+
+/// Split a file stem into its sequence name and role
+fn role(stem: &str) -> (&str, Role) {
+    let lower = stem.to_ascii_lowercase();
+    if lower.ends_with("_blink") {
+        return (&stem[..stem.len() - "_blink".len()], Role::Blink);
+    }
+
+    let digits = stem.len() - stem.trim_end_matches(|c: char| {
+        c.is_ascii_digit()
+    }).len();
+    let base = stem[..stem.len() - digits].trim_end_matches('_');
+    if digits > 0 && !base.is_empty() {
+        if let Ok(number) = stem[stem.len() - digits..].parse() {
+            return (base, Role::Numbered(number));
+        }
+    }
+    (stem, Role::Base)
+}
+
+/// Group RSB `paths` into animation sequences by naming convention: a base
+/// texture and its `_blink` variant, or numbered frames like `fire01`,
+/// `fire02`. Files are grouped per directory and names compare
+/// case-insensitively since the game data mixes case. Only groups with more
+/// than one frame are returned, sorted by name.
+///
+/// Frame timings aren't stored in the game data, so they are the defaults
+/// above: a held base frame and a short blink for faces, and a fixed frame
+/// rate for numbered sequences.
+pub fn sequences(paths: &[PathBuf]) -> Vec<Sequence> {
+    let mut groups = BTreeMap::<_, Vec<(Role, &Path)>>::new();
+    let mut names = BTreeMap::new();
+    for path in paths {
+        let Some(stem) = path.file_stem().and_then(|x| x.to_str()) else {
+            continue;
+        };
+        let (name, role) = role(stem);
+        let key = (path.parent().map(Path::to_path_buf),
+            name.to_ascii_lowercase());
+        names.entry(key.clone()).or_insert_with(|| name.to_string());
+        groups.entry(key).or_default().push((role, path));
+    }
+
+    groups.into_iter()
+        .filter(|(_, frames)| frames.len() > 1)
+        .map(|(key, mut frames)| {
+            frames.sort();
+            let blinks = frames.iter().any(|(role, _)| *role == Role::Blink);
+            let frames = frames.into_iter()
+                .map(|(role, path)| Frame {
+                    path: path.to_path_buf(),
+                    duration: match role {
+                        Role::Base if blinks => OPEN_DURATION,
+                        Role::Blink => BLINK_DURATION,
+                        _ => FRAME_DURATION,
+                    },
+                })
+                .collect();
+            Sequence { name: names.remove(&key).unwrap_or_default(), frames }
+        })
+        .collect()
+}
+
+impl Sequence {
+    /// Decode every frame. All frames must be the same size.
+    pub fn load(&self) -> anyhow::Result<Animation> {
+        let mut animation = Animation::default();
+        for (i, frame) in self.frames.iter().enumerate() {
+            let rsb = rsb::read(&frame.path)
+                .with_context(|| format!("frame {}", frame.path.display()))?;
+            if i == 0 {
+                animation.width = rsb.width;
+                animation.height = rsb.height;
+            }
+            anyhow::ensure!(
+                (rsb.width, rsb.height) == (animation.width, animation.height),
+                "{}: frame is {}x{} but the sequence is {}x{}",
+                frame.path.display(), rsb.width, rsb.height, animation.width,
+                animation.height);
+            animation.frames.push((rsb.to_rgba8(), frame.duration));
+        }
+        Ok(animation)
+    }
+}
+
+/// A decoded `Sequence`: same-sized 8-bit RGBA frames with their durations
+#[derive(Clone, Debug, Default)]
+pub struct Animation {
+    pub width: u32,
+    pub height: u32,
+    pub frames: Vec<(Vec<u8>, Duration)>,
+}
+
+impl Animation {
+    /// Encode as a looping animated PNG
+    pub fn write_apng(&self, w: impl Write) -> anyhow::Result<()> {
+        anyhow::ensure!(!self.frames.is_empty(), "animation has no frames");
+        let mut encoder = png::Encoder::new(w, self.width, self.height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.set_animated(self.frames.len() as u32, 0)?;
+        let mut writer = encoder.write_header()?;
+        for (rgba, duration) in &self.frames {
+            let millis = duration.as_millis().min(u16::MAX as u128) as u16;
+            writer.set_frame_delay(millis, 1000)?;
+            writer.write_image_data(rgba)?;
+        }
+        writer.finish()?;
+        Ok(())
+    }
+
+    /// Lay the frames out left to right in one image, for UIs that animate
+    /// by offsetting into a sheet
+    pub fn sprite_sheet(&self) -> SpriteSheet {
+        let count = self.frames.len();
+        let (width, height) = (self.width as usize, self.height as usize);
+        let mut rgba = vec![0; width * count * height * 4];
+        // Zero-width frames have no rows to copy
+        if width > 0 {
+            for (i, (frame, _)) in self.frames.iter().enumerate() {
+                for (y, row) in frame.chunks_exact(width * 4).enumerate() {
+                    let start = (y * width * count + i * width) * 4;
+                    rgba[start..start + width * 4].copy_from_slice(row);
+                }
+            }
+        }
+
+        SpriteSheet {
+            width: self.width * count as u32,
+            height: self.height,
+            frame_width: self.width,
+            frame_height: self.height,
+            frames: self.frames.iter()
+                .enumerate()
+                .map(|(i, (_, duration))| SheetFrame {
+                    x: self.width * i as u32,
+                    y: 0,
+                    duration_ms: duration.as_millis() as u64,
+                })
+                .collect(),
+            rgba,
+        }
+    }
+}
+
+/// Every frame of an `Animation` in one image. Serializes to the layout
+/// without the pixels.
+#[derive(Clone, Debug, Serialize)]
+pub struct SpriteSheet {
+    pub width: u32,
+    pub height: u32,
+    pub frame_width: u32,
+    pub frame_height: u32,
+    pub frames: Vec<SheetFrame>,
+    /// 8-bit RGBA, row by row
+    #[serde(skip)]
+    pub rgba: Vec<u8>,
+}
+
+/// Where a frame is in a `SpriteSheet` and how long it's shown
+#[derive(Clone, Debug, Serialize)]
+pub struct SheetFrame {
+    pub x: u32,
+    pub y: u32,
+    pub duration_ms: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stems_split_into_name_and_role() {
+        assert_eq!(role("Chavez_hrt_face"), ("Chavez_hrt_face", Role::Base));
+        assert_eq!(role("Chavez_hrt_face_BLINK"),
+            ("Chavez_hrt_face", Role::Blink));
+        assert_eq!(role("fire_02"), ("fire", Role::Numbered(2)));
+        assert_eq!(role("fire10"), ("fire", Role::Numbered(10)));
+        assert_eq!(role("1234"), ("1234", Role::Base));
+    }
+
+    #[test]
+    fn face_and_blink_form_one_sequence() {
+        let paths = crate::scan::find(Path::new("data/texture")).unwrap().paths;
+        let sequences = sequences(&paths);
+        assert_eq!(sequences.len(), 1);
+        let sequence = &sequences[0];
+        assert_eq!(sequence.name, "Chavez_hrt_face");
+        let durations = sequence.frames.iter()
+            .map(|frame| frame.duration)
+            .collect::<Vec<_>>();
+        assert_eq!(durations, [OPEN_DURATION, BLINK_DURATION]);
+
+        let animation = sequence.load().unwrap();
+        let mut apng = Vec::new();
+        animation.write_apng(&mut apng).unwrap();
+        let reader = png::Decoder::new(apng.as_slice()).read_info().unwrap();
+        let control = reader.info().animation_control.unwrap();
+        assert_eq!(control.num_frames, 2);
+
+        let sheet = animation.sprite_sheet();
+        assert_eq!((sheet.width, sheet.height), (128, 64));
+        assert_eq!(sheet.rgba.len(), 128 * 64 * 4);
+        assert_eq!(&sheet.rgba[64 * 4..65 * 4], &animation.frames[1].0[..4]);
+    }
+
+    #[test]
+    fn empty_frames_make_an_empty_sheet() {
+        let animation = Animation {
+            width: 0,
+            height: 4,
+            frames: vec![(Vec::new(), FRAME_DURATION); 2],
+        };
+        let sheet = animation.sprite_sheet();
+        assert_eq!((sheet.width, sheet.height), (0, 4));
+        assert!(sheet.rgba.is_empty());
+        assert_eq!(sheet.frames.len(), 2);
+    }
+}
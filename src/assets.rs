@@ -0,0 +1,130 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+
+/// Case-insensitive index of every file under a game data directory, keyed
+/// by file name. MAP files reference assets by bare file name (and often with
+/// the wrong case or extension, e.g. a material's `concrete.bmp` ships as
+/// `texture/CONCRETE.RSB`) so lookups ignore both directory and case.
+///
+/// When two files share a name, the first in sorted path order wins.
+#[derive(Clone, Debug, Default)]
+pub struct AssetIndex {
+    root: PathBuf,
+    files: HashMap<String, PathBuf>,
+}
+
+impl AssetIndex {
+    /// Index every file under `root`
+    pub fn new(root: &Path) -> anyhow::Result<Self> {
+        // Escaped so directories like "Rogue Spear [GOG]" aren't read as
+        // patterns
+        let escaped = root.to_str()
+            .with_context(|| format!("invalid path {}", root.display()))?;
+        let pattern = Path::new(&glob::Pattern::escape(escaped))
+            .join("**").join("*");
+        let pattern = pattern.to_str()
+            .with_context(|| format!("invalid path {}", root.display()))?;
+
+        let mut paths = Vec::new();
+        for entry in glob::glob(pattern)? {
+            let entry = entry?;
+            if entry.is_file() {
+                paths.push(entry);
+            }
+        }
+        paths.sort();
+
+        let mut files = HashMap::new();
+        for path in paths {
+            if let Some(name) = path.file_name().and_then(|x| x.to_str()) {
+                files.entry(name.to_lowercase()).or_insert(path);
+            }
+        }
+
+        Ok(Self { root: root.to_path_buf(), files })
+    }
+
+    /// Find the data directory that `path` (e.g. a MAP file) belongs to:
+    /// the closest ancestor with a `texture` directory.
+    pub fn data_root(path: &Path) -> Option<PathBuf> {
+        path.ancestors()
+            .skip(1)
+            .find(|dir| dir.join("texture").is_dir())
+            .map(Path::to_path_buf)
+    }
+
+    /// The directory this index was built from
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Number of indexed files
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// Look up a file by name, ignoring case and directories
+    pub fn find(&self, name: &str) -> Option<&Path> {
+        let name = Path::new(name).file_name()?.to_str()?;
+        self.files.get(&name.to_lowercase()).map(PathBuf::as_path)
+    }
+
+    /// Look up a file by name with any of `extensions`, ignoring the
+    /// extension `name` has, case and directories. Extensions are tried in
+    /// order.
+    pub fn find_with_extension(
+        &self,
+        name: &str,
+        extensions: &[&str],
+    ) -> Option<&Path> {
+        let stem = Path::new(name).file_stem()?.to_str()?;
+        extensions.iter()
+            .find_map(|ext| self.find(&format!("{stem}.{ext}")))
+    }
+
+    /// Look up the RSB for a material texture filename. Materials name the
+    /// artists' source BMP/TGA but the game loads the RSB with the same stem.
+    pub fn find_texture(&self, filename: &str) -> Option<&Path> {
+        self.find_with_extension(filename, &["rsb"])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lookups_ignore_case_extension_and_directory() {
+        let assets = AssetIndex::new(Path::new("data")).unwrap();
+        let expected = Path::new("data/texture/faces/Chavez_hrt_face.RSB");
+        assert_eq!(assets.find("chavez_HRT_face.rsb"), Some(expected));
+        assert_eq!(assets.find_texture("Chavez_hrt_face.bmp"), Some(expected));
+        assert_eq!(assets.find_texture("textures/chavez_hrt_face.tga"),
+            Some(expected));
+        assert_eq!(assets.find_texture("missing.bmp"), None);
+    }
+
+    #[test]
+    fn roots_with_glob_characters_are_indexed() {
+        let root = std::env::temp_dir()
+            .join(format!("rogue-assets [GOG] {}", std::process::id()));
+        std::fs::create_dir_all(root.join("sound")).unwrap();
+        let sound = root.join("sound/E_DMETOP.wav");
+        std::fs::write(&sound, b"").unwrap();
+        let assets = AssetIndex::new(&root);
+        std::fs::remove_dir_all(&root).unwrap();
+        assert_eq!(assets.unwrap().find("e_dmetop.WAV"), Some(sound.as_path()));
+    }
+
+    #[test]
+    fn data_root_is_the_directory_with_textures() {
+        let map = Path::new("data/map/rm19/rm19.map");
+        assert_eq!(AssetIndex::data_root(map), Some(PathBuf::from("data")));
+    }
+}
@@ -0,0 +1,61 @@
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+
+use anyhow::Context;
+use rogue_reborn::animation::{self, Sequence};
+
+use crate::{Args, convert};
+use crate::paths::{self, Kind};
+
+/// Find animation sequences among the given RSBs and write each as an
+/// animated PNG, or with `--to sheet` a sprite sheet PNG plus JSON layout.
+pub fn run(args: &Args) -> anyhow::Result<bool> {
+    let to = args.to.as_deref().unwrap_or("apng");
+    anyhow::ensure!(matches!(to, "apng" | "sheet"),
+        "animate writes --to apng or sheet, not {to}");
+    if let Some(dir) = &args.out_dir {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("could not create {}", dir.display()))?;
+    }
+
+    let paths = paths::expand(&args.paths)?.into_iter()
+        .filter(|path| Kind::of(path) == Some(Kind::Rsb))
+        .collect::<Vec<_>>();
+    let sequences = animation::sequences(&paths);
+    anyhow::ensure!(!sequences.is_empty(), "no animation sequences found");
+
+    let mut ok = true;
+    for sequence in &sequences {
+        let dir = match &args.out_dir {
+            Some(dir) => dir.as_path(),
+            None => sequence.frames[0].path.parent().unwrap_or(Path::new(".")),
+        };
+        match write(sequence, dir, to) {
+            Ok(output) => println!("{} ({} frames) -> {}", sequence.name,
+                sequence.frames.len(), output),
+            Err(e) => {
+                ok = false;
+                eprintln!("{}: {e:?}", sequence.name);
+            }
+        }
+    }
+    Ok(ok)
+}
+
+fn write(sequence: &Sequence, dir: &Path, to: &str) -> anyhow::Result<String> {
+    let animation = sequence.load()?;
+    if to == "apng" {
+        let output = dir.join(format!("{}.png", sequence.name));
+        animation.write_apng(BufWriter::new(File::create(&output)?))?;
+        return Ok(output.display().to_string());
+    }
+
+    let sheet = animation.sprite_sheet();
+    let png = dir.join(format!("{}_sheet.png", sequence.name));
+    let json = png.with_extension("json");
+    convert::write_png(&png, sheet.width, sheet.height, &sheet.rgba)?;
+    let file = BufWriter::new(File::create(&json)?);
+    serde_json::to_writer_pretty(file, &sheet)?;
+    Ok(format!("{} + {}", png.display(), json.display()))
+}
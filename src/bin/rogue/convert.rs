@@ -0,0 +1,171 @@
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use rogue_reborn::gpu::{Format, Texture};
+use rogue_reborn::{map, rsb};
+
+use crate::Args;
+use crate::paths::{self, Kind};
+
+pub fn run(args: &Args) -> anyhow::Result<bool> {
+    let to = args.to.as_deref()
+        .context("convert requires --to <png|rsb|dds|ktx2|obj|gltf|json>")?;
+    if let Some(dir) = &args.out_dir {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("could not create {}", dir.display()))?;
+    }
+
+    let mut ok = true;
+    for input in paths::expand(&args.paths)? {
+        let output = output_path(&input, args.out_dir.as_deref(), to);
+        match convert(&input, &output, to, args) {
+            Ok(()) => println!("{} -> {}", input.display(), output.display()),
+            Err(e) => {
+                ok = false;
+                eprintln!("{}: {e:?}", input.display());
+            }
+        }
+    }
+    Ok(ok)
+}
+
+fn output_path(input: &Path, out_dir: Option<&Path>, ext: &str) -> PathBuf {
+    let output = input.with_extension(ext);
+    match (out_dir, output.file_name()) {
+        (Some(dir), Some(name)) => dir.join(name),
+        _ => output,
+    }
+}
+
+fn convert(input: &Path, output: &Path, to: &str, args: &Args)
+    -> anyhow::Result<()>
+{
+    match (Kind::of(input), to) {
+        (Some(Kind::Rsb), "png") => {
+            // Stream rows straight through so large textures stay cheap
+            let file = BufReader::new(File::open(input)?);
+            let mut decoder = rsb::RowDecoder::new(file)?;
+            let header = decoder.header();
+            let file = BufWriter::new(File::create(output)?);
+            let mut encoder = png::Encoder::new(file, header.width,
+                header.height);
+            encoder.set_color(png::ColorType::Rgba);
+            encoder.set_depth(png::BitDepth::Eight);
+            let mut writer = encoder.write_header()?.into_stream_writer()?;
+            let mut row = vec![0; decoder.row_len()];
+            while decoder.read_row(&mut row)?.is_some() {
+                writer.write_all(&row)?;
+            }
+            Ok(writer.finish()?)
+        }
+        (Some(Kind::Rsb), "dds" | "ktx2") => {
+            let rsb = rsb::read(input)?;
+            let format = match args.format.as_deref() {
+                Some(format) => parse_format(format)?,
+                None => Format::native(&rsb.bitmask).unwrap_or(Format::Rgba8),
+            };
+            let texture = Texture::from_rsb(&rsb, format, args.mipmaps)?;
+            let file = BufWriter::new(File::create(output)?);
+            if to == "dds" {
+                texture.write_dds(file)
+            } else {
+                texture.write_ktx2(file)
+            }
+        }
+        (Some(Kind::Png), "rsb") => {
+            let (width, height, rgba) = read_png(input)?;
+            // Keep alpha when the image has any, otherwise spend the bits on
+            // colour. These are the two layouts Rogue Spear ships.
+            let translucent = rgba.chunks_exact(4).any(|p| p[3] != 255);
+            let bitmask = if translucent {
+                rsb::BitMask { r: 4, g: 4, b: 4, a: 4 }
+            } else {
+                rsb::BitMask { r: 5, g: 6, b: 5, a: 0 }
+            };
+            let rsb = rsb::Rsb::from_rgba8(width, height, &rgba, bitmask)?;
+            rsb::write(output, &rsb)
+        }
+        (Some(Kind::Map), "obj") => {
+            let map = read_valid_map(input)?;
+            let mtl = output.with_extension("mtl");
+            let mtl_name = mtl.file_name()
+                .and_then(|x| x.to_str())
+                .context("invalid MTL filename")?;
+            std::fs::write(output, map::to_obj(&map, mtl_name))?;
+            std::fs::write(&mtl, map::to_mtl(&map))?;
+            Ok(())
+        }
+        (Some(Kind::Map), "gltf") => {
+            let map = read_valid_map(input)?;
+            let file = BufWriter::new(File::create(output)?);
+            Ok(serde_json::to_writer(file, &map::to_gltf(&map))?)
+        }
+        (Some(Kind::Map), "json") => {
+            let map = map::read(input)?;
+            let file = BufWriter::new(File::create(output)?);
+            Ok(serde_json::to_writer_pretty(file, &map)?)
+        }
+        (_, to) => anyhow::bail!("don't know how to convert this to {to}"),
+    }
+}
+
+fn parse_format(format: &str) -> anyhow::Result<Format> {
+    Ok(match format {
+        "565" => Format::B5G6R5,
+        "4444" => Format::B4G4R4A4,
+        "8888" => Format::Rgba8,
+        "bc1" => Format::Bc1,
+        "bc3" => Format::Bc3,
+        x => anyhow::bail!("unknown texel format {x}"),
+    })
+}
+
+/// Mesh exports index vertices directly so broken references must be caught
+/// before exporting.
+fn read_valid_map(path: &Path) -> anyhow::Result<map::Map> {
+    let map = map::read(path)?;
+    let report = map.validate();
+    anyhow::ensure!(report.is_ok(), "MAP is not valid:\n{report}");
+    Ok(map)
+}
+
+fn read_png(path: &Path) -> anyhow::Result<(u32, u32, Vec<u8>)> {
+    let file = File::open(path).context("could not open PNG file")?;
+    let mut decoder = png::Decoder::new(file);
+    decoder.set_transformations(
+        png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let mut reader = decoder.read_info()?;
+    let mut buf = vec![0; reader.output_buffer_size()];
+    let frame = reader.next_frame(&mut buf)?;
+    buf.truncate(frame.buffer_size());
+
+    let rgba = match frame.color_type {
+        png::ColorType::Rgba => buf,
+        png::ColorType::Rgb => buf.chunks_exact(3)
+            .flat_map(|p| [p[0], p[1], p[2], 255])
+            .collect(),
+        png::ColorType::GrayscaleAlpha => buf.chunks_exact(2)
+            .flat_map(|p| [p[0], p[0], p[0], p[1]])
+            .collect(),
+        png::ColorType::Grayscale => buf.iter()
+            .flat_map(|&p| [p, p, p, 255])
+            .collect(),
+        x => anyhow::bail!("unsupported PNG colour type {x:?}"),
+    };
+    Ok((frame.width, frame.height, rgba))
+}
+
+pub fn write_png(path: &Path, width: u32, height: u32, rgba: &[u8])
+    -> anyhow::Result<()>
+{
+    let file = BufWriter::new(File::create(path)?);
+    let mut encoder = png::Encoder::new(file, width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(rgba)?;
+    writer.finish()?;
+    Ok(())
+}
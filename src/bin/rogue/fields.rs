@@ -0,0 +1,50 @@
+use rogue_reborn::map::{self, FieldSamples};
+use serde_json::json;
+
+use crate::Args;
+use crate::paths::{self, Kind};
+
+/// Correlations weaker than this either way aren't printed
+const MIN_CORRELATION: f32 = 0.9;
+
+/// Print how each field still named `unknown` is distributed across the
+/// MAPs and which fields move together, to help work out what they mean
+pub fn run(args: &Args) -> anyhow::Result<bool> {
+    let mut ok = true;
+    let mut samples = FieldSamples::default();
+    for path in paths::expand(&args.paths)? {
+        if Kind::of(&path) != Some(Kind::Map) {
+            continue;
+        }
+        match map::read(&path) {
+            Ok(map) => {
+                let source = path.file_name().unwrap_or_default()
+                    .to_string_lossy();
+                samples.add_unknowns(&source, &map);
+            }
+            Err(e) => {
+                ok = false;
+                eprintln!("{}: {e:#}", path.display());
+            }
+        }
+    }
+    let distributions = samples.distributions();
+    let correlations = samples.correlations(MIN_CORRELATION);
+    if args.json {
+        let report = json!({
+            "distributions": distributions,
+            "correlations": correlations,
+        });
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        for distribution in &distributions {
+            print!("{distribution}");
+        }
+        println!();
+        println!("correlations of at least {MIN_CORRELATION}:");
+        for correlation in &correlations {
+            println!("    {correlation}");
+        }
+    }
+    Ok(ok)
+}
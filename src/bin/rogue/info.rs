@@ -0,0 +1,90 @@
+use std::path::Path;
+
+use rogue_reborn::{map, rsb};
+use serde_json::json;
+
+use crate::Args;
+use crate::paths::{self, Kind};
+
+pub fn run(args: &Args) -> anyhow::Result<bool> {
+    let mut ok = true;
+    let mut infos = Vec::new();
+    for path in paths::expand(&args.paths)? {
+        match info(&path) {
+            Ok(info) => {
+                if !args.json {
+                    println!("{}", info.text);
+                }
+                infos.push(info.json);
+            }
+            Err(e) => {
+                ok = false;
+                eprintln!("{}: {e:?}", path.display());
+                infos.push(json!({
+                    "path": path,
+                    "error": format!("{e:#}"),
+                }));
+            }
+        }
+    }
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&infos)?);
+    }
+    Ok(ok)
+}
+
+/// Both the text and JSON forms of a file summary
+struct Info {
+    text: String,
+    json: serde_json::Value,
+}
+
+fn info(path: &Path) -> anyhow::Result<Info> {
+    Ok(match Kind::of(path) {
+        Some(Kind::Rsb) => {
+            let rsb = rsb::read(path)?;
+            let bitmask = &rsb.bitmask;
+            Info {
+                text: rsb.to_string(),
+                json: json!({
+                    "path": path,
+                    "kind": "rsb",
+                    "version": rsb.version,
+                    "width": rsb.width,
+                    "height": rsb.height,
+                    "palette": rsb.palette,
+                    "bitmask": [bitmask.r, bitmask.g, bitmask.b, bitmask.a],
+                    "masked": rsb.masked_pixels.is_some(),
+                }),
+            }
+        }
+        Some(Kind::Map) => {
+            let map = map::read(path)?;
+            let counts = json!({
+                "materials": map.materials.materials.len(),
+                "objects": map.geometries.objects.len(),
+                "portals": map.portals.portals.len(),
+                "dynamic_objects": map.dynamic_objects.dynamic_objects.len(),
+                "rooms": map.rooms.rooms.len(),
+                "transitions": map.transitions.transitions.len(),
+                "planning_levels": map.planning_levels.levels.len(),
+            });
+            let mut text = format!("{} {{\n", path.display());
+            text += &format!("  timestamp: {}\n", map.header.timestamp);
+            for (name, count) in counts.as_object().unwrap() {
+                text += &format!("  {}: {count}\n", name.replace('_', " "));
+            }
+            text += "}";
+            Info {
+                text,
+                json: json!({
+                    "path": path,
+                    "kind": "map",
+                    "timestamp": map.header.timestamp,
+                    "counts": counts,
+                }),
+            }
+        }
+        _ => anyhow::bail!("not an RSB or MAP file"),
+    })
+}
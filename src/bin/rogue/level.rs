@@ -0,0 +1,355 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::Instant;
+
+use anyhow::Context;
+use minifb::{Key, KeyRepeat, MouseButton, MouseMode, Window, WindowOptions};
+use rogue_reborn::assets::AssetIndex;
+use rogue_reborn::map::{self, Collisions, Map};
+use rogue_reborn::rsb;
+use rogue_reborn::vec3;
+
+use crate::raster::{Camera, Corner, Fill, Framebuffer, Texture, Vec3};
+
+const WIDTH: usize = 800;
+const HEIGHT: usize = 600;
+
+const HELP: &str = "\
+WASD move, Q/E down/up, Shift faster, drag right mouse or arrows to look
+1 wireframe, 2 collision overlay, 3 portal overlay, T textures, Esc quit";
+
+/// Fly through a MAP. Textures are resolved against the game data directory
+/// the MAP is in, see `AssetIndex::data_root`. Invalid MAPs are still shown,
+/// without the faces that index out of range, after the validation report.
+pub fn run(path: &Path) -> anyhow::Result<bool> {
+    let map = map::read(path)?;
+    let report = map.validate();
+    if !report.is_ok() {
+        eprintln!("warning: MAP is not valid:\n{report}");
+    }
+
+    let assets = match AssetIndex::data_root(path) {
+        Some(root) => AssetIndex::new(&root)?,
+        None => AssetIndex::default(),
+    };
+    let scene = Scene::new(&map, &assets);
+    println!("{}: {} triangles, {} of {} textures found", path.display(),
+        scene.triangles.len(), scene.textures.len(),
+        map.materials.materials.len());
+    println!("{HELP}");
+
+    let mut window = Window::new(
+        &format!("rogue view: {}", path.display()),
+        WIDTH, HEIGHT,
+        WindowOptions::default(),
+    )
+    .context("window creation failed")?;
+    window.limit_update_rate(Some(std::time::Duration::from_millis(16)));
+
+    let mut camera = scene.start_camera();
+    let mut framebuffer = Framebuffer::new(WIDTH, HEIGHT);
+    let mut toggles = Toggles { textures: true, ..Default::default() };
+    let mut last_mouse: Option<(f32, f32)> = None;
+    let mut last_frame = Instant::now();
+
+    while window.is_open() && !window.is_key_down(Key::Escape) {
+        let dt = last_frame.elapsed().as_secs_f32();
+        last_frame = Instant::now();
+
+        toggles.update(&window);
+        fly(&window, &mut camera, dt, scene.speed);
+
+        let mouse = window.get_mouse_pos(MouseMode::Pass);
+        if window.get_mouse_down(MouseButton::Right) {
+            if let (Some((x0, y0)), Some((x1, y1))) = (last_mouse, mouse) {
+                camera.yaw += (x1 - x0) * 0.005;
+                camera.pitch = (camera.pitch - (y1 - y0) * 0.005)
+                    .clamp(-1.5, 1.5);
+            }
+            last_mouse = mouse;
+        } else {
+            last_mouse = None;
+        }
+
+        scene.render(&mut framebuffer, &camera, &toggles);
+        window.update_with_buffer(&framebuffer.color, WIDTH, HEIGHT)?;
+    }
+
+    Ok(true)
+}
+
+#[derive(Default)]
+struct Toggles {
+    wireframe: bool,
+    collision: bool,
+    portals: bool,
+    textures: bool,
+}
+
+impl Toggles {
+    fn update(&mut self, window: &Window) {
+        let pressed = |key| window.is_key_pressed(key, KeyRepeat::No);
+        if pressed(Key::Key1) {
+            self.wireframe = !self.wireframe;
+        }
+        if pressed(Key::Key2) {
+            self.collision = !self.collision;
+        }
+        if pressed(Key::Key3) {
+            self.portals = !self.portals;
+        }
+        if pressed(Key::T) {
+            self.textures = !self.textures;
+        }
+    }
+}
+
+fn fly(window: &Window, camera: &mut Camera, dt: f32, speed: f32) {
+    let down = |key| window.is_key_down(key);
+    let speed = if down(Key::LeftShift) { speed * 4.0 } else { speed } * dt;
+
+    let mut step = [0.0; 3];
+    let mut go = |direction: Vec3, amount: f32| {
+        step = vec3::add(step, vec3::scale(direction, amount));
+    };
+    if down(Key::W) { go(camera.forward(), speed) }
+    if down(Key::S) { go(camera.forward(), -speed) }
+    if down(Key::D) { go(camera.right(), speed) }
+    if down(Key::A) { go(camera.right(), -speed) }
+    if down(Key::E) { go([0.0, 1.0, 0.0], speed) }
+    if down(Key::Q) { go([0.0, -1.0, 0.0], speed) }
+    camera.position = vec3::add(camera.position, step);
+
+    let turn = 1.5 * dt;
+    if down(Key::Left) { camera.yaw -= turn }
+    if down(Key::Right) { camera.yaw += turn }
+    if down(Key::Up) { camera.pitch = (camera.pitch + turn).min(1.5) }
+    if down(Key::Down) { camera.pitch = (camera.pitch - turn).max(-1.5) }
+}
+
+struct Triangle {
+    corners: [Corner; 3],
+    texture: Option<usize>,
+    /// Untextured colour from the material diffuse, `0x00RRGGBB`
+    color: u32,
+    /// Fixed directional light from the face normal
+    shade: f32,
+}
+
+/// Everything needed to draw a MAP, flattened out of the MAP structure
+struct Scene {
+    triangles: Vec<Triangle>,
+    textures: Vec<Texture>,
+    collision_lines: Vec<(Vec3, Vec3)>,
+    portal_lines: Vec<(Vec3, Vec3)>,
+    min: Vec3,
+    max: Vec3,
+    /// Camera speed in MAP units per second, scaled to the level size
+    speed: f32,
+}
+
+impl Scene {
+    fn new(map: &Map, assets: &AssetIndex) -> Self {
+        let (textures, texture_slots) = load_textures(map, assets);
+        let light = normalize([0.3, 0.8, 0.5]);
+
+        let mut triangles = Vec::new();
+        let mut collision_lines = Vec::new();
+        for object in &map.geometries.objects {
+            let position = |i: u16| {
+                let v = object.vertices.get(i as usize)?;
+                Some([v.x, v.y, v.z])
+            };
+
+            for data in &object.object_datas {
+                let material = map.materials.materials.get(data.mn as usize);
+                let color = material.map_or(0x808080, |m| {
+                    let c = |x: f32| (x.clamp(0.0, 1.0) * 255.0) as u32;
+                    c(m.diffuse.r) << 16 | c(m.diffuse.g) << 8 | c(m.diffuse.b)
+                });
+                let texture = texture_slots.get(&(data.mn as usize)).copied();
+
+                let faces = &data.faces;
+                let uvs = &data.texture_vertices.uv_coords;
+                for ((&(v1, v2, v3), &(t1, t2, t3)), normal) in faces
+                    .face_indices.iter()
+                    .zip(&faces.texture_indices)
+                    .zip(&faces.normals)
+                {
+                    let corner = |v: u16, t: u16| {
+                        let uv = uvs.get(t as usize)?;
+                        // MAP V grows downwards from zero
+                        Some(Corner { position: position(v)?,
+                            uv: [uv.u, -uv.v] })
+                    };
+                    let (Some(c1), Some(c2), Some(c3)) =
+                        (corner(v1, t1), corner(v2, t2), corner(v3, t3))
+                    else {
+                        continue;
+                    };
+                    let n = [normal.x, normal.y, normal.z];
+                    triangles.push(Triangle {
+                        corners: [c1, c2, c3],
+                        texture,
+                        color,
+                        shade: 0.55 + 0.45 * vec3::dot(n, light).abs(),
+                    });
+                }
+            }
+
+            collision_lines.extend(collision_outlines(&object.collisions));
+        }
+
+        let mut min = [f32::MAX; 3];
+        let mut max = [f32::MIN; 3];
+        // Frame the camera on the portals rather than all geometry, since
+        // some maps have stray objects around the origin far from the level.
+        let framed = map.portals.portals.iter()
+            .flat_map(|portal| &portal.coordinates)
+            .chain(map.geometries.objects.iter()
+                .filter(|_| map.portals.portals.is_empty())
+                .flat_map(|object| &object.vertices));
+        for v in framed {
+            for (axis, x) in [v.x, v.y, v.z].into_iter().enumerate() {
+                min[axis] = min[axis].min(x);
+                max[axis] = max[axis].max(x);
+            }
+        }
+
+        let mut portal_lines = Vec::new();
+        for portal in &map.portals.portals {
+            let points = portal.coordinates.iter()
+                .map(|v| [v.x, v.y, v.z])
+                .collect::<Vec<_>>();
+            for i in 0..points.len() {
+                portal_lines.push((points[i], points[(i + 1) % points.len()]));
+            }
+        }
+
+        let extent = vec3::sub(max, min);
+        let speed = extent[0].max(extent[2]).max(1.0) / 10.0;
+        Self {
+            triangles,
+            textures,
+            collision_lines,
+            portal_lines,
+            min,
+            max,
+            speed,
+        }
+    }
+
+    /// Above and behind the level looking at its centre
+    fn start_camera(&self) -> Camera {
+        let centre = vec3::scale(vec3::add(self.min, self.max), 0.5);
+        let extent = vec3::sub(self.max, self.min);
+        let size = extent[0].max(extent[2]);
+        let position = vec3::add(centre, [0.0, size * 0.4, -size * 0.6]);
+        let d = vec3::sub(centre, position);
+        Camera {
+            position,
+            yaw: d[0].atan2(d[2]),
+            pitch: d[1].atan2(d[0].hypot(d[2])),
+            fov: 70f32.to_radians(),
+        }
+    }
+
+    fn render(&self, fb: &mut Framebuffer, camera: &Camera, toggles: &Toggles) {
+        fb.clear(0x202830);
+
+        for triangle in &self.triangles {
+            if toggles.wireframe {
+                let [a, b, c] = triangle.corners.map(|c| c.position);
+                for (p, q) in [(a, b), (b, c), (c, a)] {
+                    fb.line(camera, p, q, 0xc0c0c0);
+                }
+                continue;
+            }
+            let fill = match triangle.texture {
+                Some(texture) if toggles.textures => {
+                    Fill::Textured(&self.textures[texture])
+                }
+                _ => Fill::Solid(triangle.color),
+            };
+            fb.triangle(camera, triangle.corners, fill, triangle.shade);
+        }
+
+        if toggles.collision {
+            for &(a, b) in &self.collision_lines {
+                fb.line(camera, a, b, 0xff4040);
+            }
+        }
+        if toggles.portals {
+            for &(a, b) in &self.portal_lines {
+                fb.line(camera, a, b, 0xffe040);
+            }
+        }
+    }
+}
+
+/// Decode the RSB of every material whose texture can be found, along with
+/// each material index's position in the texture list
+fn load_textures(
+    map: &Map,
+    assets: &AssetIndex,
+) -> (Vec<Texture>, HashMap<usize, usize>) {
+    let mut textures = Vec::new();
+    let mut slots = HashMap::new();
+    for (index, material) in map.materials.materials.iter().enumerate() {
+        let Some(path) = assets.find_texture(&material.filename) else {
+            continue;
+        };
+        let texture = rsb::read(path).and_then(|rsb| Texture::from_rgba8(
+            rsb.width as usize, rsb.height as usize, &rsb.to_rgba8_straight()));
+        match texture {
+            Ok(texture) => {
+                slots.insert(index, textures.len());
+                textures.push(texture);
+            }
+            Err(e) => eprintln!("{}: {e:#}", path.display()),
+        }
+    }
+    (textures, slots)
+}
+
+/// Outline every collision face. Faces are stored only as planes, so the
+/// corners of one are the collision vertices lying on its plane, taken in
+/// order around their centre. Every plane in the shipped maps passes through
+/// at least four collision vertices.
+fn collision_outlines(collisions: &Collisions) -> Vec<(Vec3, Vec3)> {
+    let points = collisions.vertices.iter()
+        .map(|v| [v.x, v.y, v.z])
+        .collect::<Vec<_>>();
+    let mut lines = Vec::new();
+    for face in &collisions.faces {
+        let normal = [face.x, face.y, face.z];
+        let distance = face.distance_origin_to_face;
+        let tolerance = 1e-3 * distance.abs().max(1.0);
+        let mut corners = points.iter().copied()
+            .filter(|&p| (vec3::dot(normal, p) + distance).abs() < tolerance)
+            .collect::<Vec<_>>();
+        if corners.len() < 3 {
+            continue;
+        }
+
+        let centre = vec3::scale(
+            corners.iter().fold([0.0; 3], |sum, &p| vec3::add(sum, p)),
+            1.0 / corners.len() as f32);
+        let axis = vec3::sub(corners[0], centre);
+        let angle = |p: Vec3| {
+            let offset = vec3::sub(p, centre);
+            let sin = vec3::dot(vec3::cross(axis, offset), normal);
+            sin.atan2(vec3::dot(axis, offset))
+        };
+        corners.sort_by(|&a, &b| angle(a).total_cmp(&angle(b)));
+        for (i, &a) in corners.iter().enumerate() {
+            lines.push((a, corners[(i + 1) % corners.len()]));
+        }
+    }
+    lines
+}
+
+fn normalize(v: Vec3) -> Vec3 {
+    vec3::scale(v, 1.0 / vec3::length(v))
+}
+
@@ -0,0 +1,141 @@
+use std::ffi::OsString;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+mod animate;
+mod convert;
+mod fields;
+mod info;
+mod level;
+mod params;
+mod paths;
+mod raster;
+mod refs;
+mod sounds;
+mod stats;
+mod validate;
+mod view;
+
+const USAGE: &str = "\
+rogue: Rogue Spear asset tool
+
+USAGE:
+    rogue <COMMAND> [OPTIONS] <PATHS>...
+
+COMMANDS:
+    animate     Export RSB animation sequences, e.g. a face and its blink
+    info        Print a summary of each RSB and MAP file
+    convert     Convert RSB <-> PNG, RSB -> DDS or KTX2 and MAP -> OBJ,
+                glTF or JSON
+    validate    Check MAP files for broken references
+    refs        List what the names in MAP dynamic objects refer to and
+                report names that refer to nothing
+    sounds      List the sounds each MAP plays, where from, and the
+                files they resolve to in the game data
+    params      Print how the parameters of dynamic props are distributed
+                across MAP files
+    fields      Print how the unknown fields of MAP files are distributed,
+                with examples and the fields they correlate with
+    stats       Summarise versions and layouts across many files
+    view        Browse RSB files in a window by channel, or fly
+                through a single MAP
+
+PATHS may be files, directories (searched recursively) or glob patterns.
+
+OPTIONS:
+    --json              Machine-readable output for info, validate, refs,
+                        sounds, params, fields and stats
+    --to <FORMAT>       convert: png, rsb, dds, ktx2, obj, gltf or json
+                        animate: apng (default) or sheet
+    --out-dir <DIR>     convert, animate: where to write (default: next to the input)
+    --format <FORMAT>   convert to dds or ktx2: 565, 4444, 8888, bc1 or bc3
+                        (default: the RSB's own 16-bit layout)
+    --mipmaps           convert to dds or ktx2: include a full mip chain
+    -h, --help          Print this message
+";
+
+/// Options shared by every command. Not every command uses every option.
+#[derive(Debug, Default)]
+pub struct Args {
+    pub json: bool,
+    pub to: Option<String>,
+    pub out_dir: Option<PathBuf>,
+    pub format: Option<String>,
+    pub mipmaps: bool,
+    pub paths: Vec<String>,
+}
+
+impl Args {
+    fn parse(args: impl Iterator<Item = OsString>) -> anyhow::Result<Self> {
+        let mut parsed = Self::default();
+        let mut args = args.map(|arg| {
+            arg.into_string()
+                .map_err(|arg| anyhow::anyhow!("invalid argument: {arg:?}"))
+        });
+        while let Some(arg) = args.next() {
+            let arg = arg?;
+            match arg.as_str() {
+                "--json" => parsed.json = true,
+                "--to" => {
+                    let to = args.next().transpose()?;
+                    parsed.to = Some(to.ok_or_else(|| {
+                        anyhow::anyhow!("--to requires a format")
+                    })?);
+                }
+                "--format" => {
+                    let format = args.next().transpose()?;
+                    parsed.format = Some(format.ok_or_else(|| {
+                        anyhow::anyhow!("--format requires a texel format")
+                    })?);
+                }
+                "--mipmaps" => parsed.mipmaps = true,
+                "--out-dir" => {
+                    let dir = args.next().transpose()?;
+                    parsed.out_dir = Some(dir.ok_or_else(|| {
+                        anyhow::anyhow!("--out-dir requires a directory")
+                    })?.into());
+                }
+                x if x.starts_with("--") => anyhow::bail!("unknown option {x}"),
+                _ => parsed.paths.push(arg),
+            }
+        }
+        Ok(parsed)
+    }
+}
+
+fn main() -> ExitCode {
+    let mut args = std::env::args_os().skip(1);
+    let command = args.next().and_then(|x| x.into_string().ok());
+    let command = match command.as_deref() {
+        None | Some("-h" | "--help" | "help") => {
+            print!("{USAGE}");
+            return ExitCode::SUCCESS;
+        }
+        Some(command) => command.to_string(),
+    };
+
+    let result = Args::parse(args).and_then(|args| {
+        match command.as_str() {
+            "animate" => animate::run(&args),
+            "info" => info::run(&args),
+            "convert" => convert::run(&args),
+            "validate" => validate::run(&args),
+            "refs" => refs::run(&args),
+            "sounds" => sounds::run(&args),
+            "params" => params::run(&args),
+            "fields" => fields::run(&args),
+            "stats" => stats::run(&args),
+            "view" => view::run(&args),
+            x => anyhow::bail!("unknown command '{x}'\n\n{USAGE}"),
+        }
+    });
+
+    match result {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(e) => {
+            eprintln!("error: {e:?}");
+            ExitCode::from(2)
+        }
+    }
+}
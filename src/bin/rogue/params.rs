@@ -0,0 +1,36 @@
+use rogue_reborn::map::{self, FieldSamples};
+
+use crate::Args;
+use crate::paths::{self, Kind};
+
+/// Print how each `KindDynamicParams` field is distributed across the MAPs,
+/// to help work out what the fields mean
+pub fn run(args: &Args) -> anyhow::Result<bool> {
+    let mut ok = true;
+    let mut samples = FieldSamples::default();
+    for path in paths::expand(&args.paths)? {
+        if Kind::of(&path) != Some(Kind::Map) {
+            continue;
+        }
+        match map::read(&path) {
+            Ok(map) => {
+                let source = path.file_name().unwrap_or_default()
+                    .to_string_lossy();
+                samples.add_params(&source, &map);
+            }
+            Err(e) => {
+                ok = false;
+                eprintln!("{}: {e:#}", path.display());
+            }
+        }
+    }
+    let distributions = samples.distributions();
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&distributions)?);
+    } else {
+        for distribution in &distributions {
+            print!("{distribution}");
+        }
+    }
+    Ok(ok)
+}
@@ -0,0 +1,86 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+
+/// The file formats the tool knows, by extension
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Kind {
+    Rsb,
+    Map,
+    Png,
+}
+
+impl Kind {
+    pub fn of(path: &Path) -> Option<Self> {
+        let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+        match ext.as_str() {
+            "rsb" => Some(Self::Rsb),
+            "map" => Some(Self::Map),
+            "png" => Some(Self::Png),
+            _ => None,
+        }
+    }
+}
+
+/// Expand each argument into files. Directories are searched recursively for
+/// known file kinds, existing files are taken as given, and anything else
+/// containing glob characters is matched case-insensitively since the game
+/// data mixes `.RSB` and `.rsb`.
+pub fn expand(args: &[String]) -> anyhow::Result<Vec<PathBuf>> {
+    anyhow::ensure!(!args.is_empty(), "no paths given");
+
+    let opts = glob::MatchOptions {
+        case_sensitive: false,
+        ..Default::default()
+    };
+
+    let mut paths = Vec::new();
+    for arg in args {
+        let path = Path::new(arg);
+        if path.is_dir() {
+            // Escaped so directories like "Rogue Spear [GOG]" match
+            // themselves rather than being read as patterns
+            let root = path.to_str()
+                .with_context(|| format!("invalid path {arg}"))?;
+            let pattern = Path::new(&glob::Pattern::escape(root))
+                .join("**").join("*");
+            let pattern = pattern.to_str()
+                .with_context(|| format!("invalid path {arg}"))?;
+            for entry in glob::glob_with(pattern, opts)? {
+                let entry = entry?;
+                if entry.is_file() && Kind::of(&entry).is_some() {
+                    paths.push(entry);
+                }
+            }
+        } else if path.exists() {
+            paths.push(path.to_path_buf());
+        } else if arg.contains(['*', '?', '[']) {
+            let before = paths.len();
+            for entry in glob::glob_with(arg, opts)? {
+                paths.push(entry?);
+            }
+            anyhow::ensure!(paths.len() > before, "{arg}: no matches");
+        } else {
+            anyhow::bail!("{arg}: no such file");
+        }
+    }
+    Ok(paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn existing_files_are_not_patterns() {
+        let root = std::env::temp_dir()
+            .join(format!("rogue-paths [GOG] {}", std::process::id()));
+        std::fs::create_dir_all(&root).unwrap();
+        let file = root.join("x [1].rsb");
+        std::fs::write(&file, b"").unwrap();
+        let arg = file.to_str().unwrap().to_string();
+        let paths = expand(&[arg]);
+        std::fs::remove_dir_all(&root).unwrap();
+        assert_eq!(paths.unwrap(), [file]);
+    }
+}
@@ -0,0 +1,307 @@
+use rogue_reborn::vec3::{add, dot, scale, sub};
+
+pub type Vec3 = [f32; 3];
+
+/// Anything closer to the camera than this is clipped, in MAP units
+const NEAR: f32 = 4.0;
+
+/// A fly camera. Yaw turns around the world Y (up) axis and pitch tilts up
+/// and down; at zero yaw and pitch the camera looks down +Z.
+#[derive(Clone, Debug)]
+pub struct Camera {
+    pub position: Vec3,
+    pub yaw: f32,
+    pub pitch: f32,
+    /// Vertical field of view in radians
+    pub fov: f32,
+}
+
+impl Camera {
+    pub fn forward(&self) -> Vec3 {
+        let (sy, cy) = self.yaw.sin_cos();
+        let (sp, cp) = self.pitch.sin_cos();
+        [sy * cp, sp, cy * cp]
+    }
+
+    pub fn right(&self) -> Vec3 {
+        let (sy, cy) = self.yaw.sin_cos();
+        [cy, 0.0, -sy]
+    }
+
+    pub fn up(&self) -> Vec3 {
+        let (sy, cy) = self.yaw.sin_cos();
+        let (sp, cp) = self.pitch.sin_cos();
+        [-sy * sp, cp, -cy * sp]
+    }
+
+    /// World to view space, where +Z is into the screen
+    fn to_view(&self, p: Vec3) -> Vec3 {
+        let d = sub(p, self.position);
+        [dot(d, self.right()), dot(d, self.up()), dot(d, self.forward())]
+    }
+}
+
+/// An RGBA texture stored as `0xAARRGGBB` texels
+pub struct Texture {
+    pub width: usize,
+    pub height: usize,
+    pub texels: Vec<u32>,
+}
+
+impl Texture {
+    /// Sampling wraps around the edges, so an empty texture is refused
+    pub fn from_rgba8(
+        width: usize,
+        height: usize,
+        rgba: &[u8],
+    ) -> anyhow::Result<Self> {
+        anyhow::ensure!(width > 0 && height > 0,
+            "{width}x{height} texture is empty");
+        anyhow::ensure!(rgba.len() == width * height * 4,
+            "{} bytes of RGBA for a {width}x{height} texture", rgba.len());
+        let texels = rgba.chunks_exact(4)
+            .map(|p| u32::from_be_bytes([p[3], p[0], p[1], p[2]]))
+            .collect();
+        Ok(Self { width, height, texels })
+    }
+
+    /// Nearest-neighbour lookup with wrapping
+    fn sample(&self, u: f32, v: f32) -> u32 {
+        let x = (u * self.width as f32).floor() as i64;
+        let y = (v * self.height as f32).floor() as i64;
+        let x = x.rem_euclid(self.width as i64) as usize;
+        let y = y.rem_euclid(self.height as i64) as usize;
+        self.texels[y * self.width + x]
+    }
+}
+
+/// How a triangle is filled
+#[derive(Clone, Copy)]
+pub enum Fill<'a> {
+    Solid(u32),
+    Textured(&'a Texture),
+}
+
+/// A triangle corner before projection
+#[derive(Clone, Copy)]
+pub struct Corner {
+    pub position: Vec3,
+    pub uv: [f32; 2],
+}
+
+/// A corner in view space, the form clipping works on
+#[derive(Clone, Copy)]
+struct ViewCorner {
+    p: Vec3,
+    uv: [f32; 2],
+}
+
+impl ViewCorner {
+    fn lerp(a: Self, b: Self, t: f32) -> Self {
+        Self {
+            p: add(a.p, scale(sub(b.p, a.p), t)),
+            uv: [
+                a.uv[0] + (b.uv[0] - a.uv[0]) * t,
+                a.uv[1] + (b.uv[1] - a.uv[1]) * t,
+            ],
+        }
+    }
+}
+
+/// A projected corner. Attributes are pre-divided by view depth so that they
+/// interpolate linearly in screen space.
+#[derive(Clone, Copy)]
+struct ScreenCorner {
+    x: f32,
+    y: f32,
+    inv_z: f32,
+    u_z: f32,
+    v_z: f32,
+}
+
+/// A small CPU rasterizer target: perspective-correct textured triangles
+/// with a depth buffer, near-plane clipping and overlay lines.
+pub struct Framebuffer {
+    pub width: usize,
+    pub height: usize,
+    /// `0x00RRGGBB` pixels, the layout `minifb` expects
+    pub color: Vec<u32>,
+    /// `1 / z` per pixel so that zero is infinitely far away
+    depth: Vec<f32>,
+}
+
+impl Framebuffer {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            color: vec![0; width * height],
+            depth: vec![0.0; width * height],
+        }
+    }
+
+    pub fn clear(&mut self, color: u32) {
+        self.color.fill(color);
+        self.depth.fill(0.0);
+    }
+
+    fn focal_length(&self, camera: &Camera) -> f32 {
+        self.height as f32 / 2.0 / (camera.fov / 2.0).tan()
+    }
+
+    fn project(&self, camera: &Camera, c: ViewCorner) -> ScreenCorner {
+        let f = self.focal_length(camera);
+        let inv_z = 1.0 / c.p[2];
+        ScreenCorner {
+            x: self.width as f32 / 2.0 + c.p[0] * f * inv_z,
+            y: self.height as f32 / 2.0 - c.p[1] * f * inv_z,
+            inv_z,
+            u_z: c.uv[0] * inv_z,
+            v_z: c.uv[1] * inv_z,
+        }
+    }
+
+    /// Depth-tested triangle. `shade` scales the fill colour, 1.0 is
+    /// unchanged. Texels with less than half alpha are cut out.
+    pub fn triangle(
+        &mut self,
+        camera: &Camera,
+        corners: [Corner; 3],
+        fill: Fill,
+        shade: f32,
+    ) {
+        let view = corners.map(|c| ViewCorner {
+            p: camera.to_view(c.position),
+            uv: c.uv,
+        });
+        if view.iter().all(|c| c.p[2] < NEAR) {
+            return;
+        }
+
+        // Clip against the near plane, leaving a triangle or quad
+        let mut clipped = Vec::with_capacity(4);
+        for i in 0..3 {
+            let a = view[i];
+            let b = view[(i + 1) % 3];
+            if a.p[2] >= NEAR {
+                clipped.push(a);
+            }
+            if (a.p[2] >= NEAR) != (b.p[2] >= NEAR) {
+                let t = (NEAR - a.p[2]) / (b.p[2] - a.p[2]);
+                clipped.push(ViewCorner::lerp(a, b, t));
+            }
+        }
+
+        let screen = clipped.iter()
+            .map(|&c| self.project(camera, c))
+            .collect::<Vec<_>>();
+        for i in 1..screen.len() - 1 {
+            self.fill([screen[0], screen[i], screen[i + 1]], fill, shade);
+        }
+    }
+
+    fn fill(&mut self, c: [ScreenCorner; 3], fill: Fill, shade: f32) {
+        let area = edge(&c[0], &c[1], c[2].x, c[2].y);
+        if area.abs() < f32::EPSILON {
+            return;
+        }
+
+        let min_x = c.iter().map(|c| c.x).fold(f32::MAX, f32::min)
+            .floor().max(0.0) as usize;
+        let max_x = c.iter().map(|c| c.x).fold(f32::MIN, f32::max)
+            .ceil().min(self.width as f32 - 1.0);
+        let min_y = c.iter().map(|c| c.y).fold(f32::MAX, f32::min)
+            .floor().max(0.0) as usize;
+        let max_y = c.iter().map(|c| c.y).fold(f32::MIN, f32::max)
+            .ceil().min(self.height as f32 - 1.0);
+        if max_x < 0.0 || max_y < 0.0 {
+            return;
+        }
+        let (max_x, max_y) = (max_x as usize, max_y as usize);
+
+        for y in min_y..=max_y {
+            let py = y as f32 + 0.5;
+            for x in min_x..=max_x {
+                let px = x as f32 + 0.5;
+                let w0 = edge(&c[1], &c[2], px, py) / area;
+                let w1 = edge(&c[2], &c[0], px, py) / area;
+                let w2 = 1.0 - w0 - w1;
+                if w0 < 0.0 || w1 < 0.0 || w2 < 0.0 {
+                    continue;
+                }
+
+                let inv_z = w0 * c[0].inv_z + w1 * c[1].inv_z
+                    + w2 * c[2].inv_z;
+                let i = y * self.width + x;
+                if inv_z <= self.depth[i] {
+                    continue;
+                }
+
+                let color = match fill {
+                    Fill::Solid(color) => color,
+                    Fill::Textured(texture) => {
+                        let u = (w0 * c[0].u_z + w1 * c[1].u_z
+                            + w2 * c[2].u_z) / inv_z;
+                        let v = (w0 * c[0].v_z + w1 * c[1].v_z
+                            + w2 * c[2].v_z) / inv_z;
+                        let texel = texture.sample(u, v);
+                        if texel >> 24 < 0x80 {
+                            continue;
+                        }
+                        texel
+                    }
+                };
+
+                self.depth[i] = inv_z;
+                self.color[i] = shaded(color, shade);
+            }
+        }
+    }
+
+    /// A line drawn over everything, for overlays
+    pub fn line(&mut self, camera: &Camera, a: Vec3, b: Vec3, color: u32) {
+        let mut a = ViewCorner { p: camera.to_view(a), uv: [0.0; 2] };
+        let mut b = ViewCorner { p: camera.to_view(b), uv: [0.0; 2] };
+        if a.p[2] < NEAR && b.p[2] < NEAR {
+            return;
+        }
+        if a.p[2] < NEAR {
+            a = ViewCorner::lerp(a, b, (NEAR - a.p[2]) / (b.p[2] - a.p[2]));
+        } else if b.p[2] < NEAR {
+            b = ViewCorner::lerp(b, a, (NEAR - b.p[2]) / (a.p[2] - b.p[2]));
+        }
+
+        let a = self.project(camera, a);
+        let b = self.project(camera, b);
+        let steps = (b.x - a.x).abs().max((b.y - a.y).abs()).ceil();
+        // Lines far off screen aren't worth walking
+        if steps > 4.0 * (self.width + self.height) as f32 {
+            return;
+        }
+        let steps = steps.max(1.0) as usize;
+        for step in 0..=steps {
+            let t = step as f32 / steps as f32;
+            let x = a.x + (b.x - a.x) * t;
+            let y = a.y + (b.y - a.y) * t;
+            if x >= 0.0 && y >= 0.0 {
+                let (x, y) = (x as usize, y as usize);
+                if x < self.width && y < self.height {
+                    self.color[y * self.width + x] = color;
+                }
+            }
+        }
+    }
+}
+
+/// Twice the signed area of the triangle (a, b, p)
+fn edge(a: &ScreenCorner, b: &ScreenCorner, px: f32, py: f32) -> f32 {
+    (b.x - a.x) * (py - a.y) - (b.y - a.y) * (px - a.x)
+}
+
+fn shaded(color: u32, shade: f32) -> u32 {
+    let channel = |shift: u32| {
+        let c = ((color >> shift) & 0xff) as f32 * shade;
+        (c.min(255.0) as u32) << shift
+    };
+    channel(16) | channel(8) | channel(0)
+}
@@ -0,0 +1,49 @@
+use rogue_reborn::map;
+use serde_json::json;
+
+use crate::Args;
+use crate::paths::{self, Kind};
+
+/// Dangling names are reported but don't fail the command, since some are
+/// expected: the spark effects of dynamic props name nothing in the MAP.
+/// Only names inside the MAP are checked; sound and model files are counted
+/// as external and unchecked.
+pub fn run(args: &Args) -> anyhow::Result<bool> {
+    let mut ok = true;
+    let mut results = Vec::new();
+    for path in paths::expand(&args.paths)? {
+        if Kind::of(&path) != Some(Kind::Map) {
+            continue;
+        }
+        let result = match map::read(&path) {
+            Ok(map) => {
+                let graph = map.references();
+                if !args.json {
+                    let objects = &map.dynamic_objects.dynamic_objects;
+                    let dangling: Vec<_> = graph.dangling().collect();
+                    println!("{}: {} references, {} dangling, {} external \
+                        and unchecked", path.display(), graph.references.len(),
+                        dangling.len(), graph.external().count());
+                    for reference in dangling {
+                        println!("    {} {:?} \"{}\"",
+                            objects[reference.object].section_name,
+                            reference.field, reference.name);
+                    }
+                }
+                json!({ "path": path, "references": graph.references })
+            }
+            Err(e) => {
+                ok = false;
+                if !args.json {
+                    println!("{}: {e:#}", path.display());
+                }
+                json!({ "path": path, "error": format!("{e:#}") })
+            }
+        };
+        results.push(result);
+    }
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&results)?);
+    }
+    Ok(ok)
+}
@@ -0,0 +1,55 @@
+use rogue_reborn::assets::AssetIndex;
+use rogue_reborn::map;
+use serde_json::json;
+
+use crate::Args;
+use crate::paths::{self, Kind};
+
+/// Sounds are looked up in the data directory each MAP is in, see
+/// `AssetIndex::data_root`. Missing sounds are listed but don't fail the
+/// command.
+pub fn run(args: &Args) -> anyhow::Result<bool> {
+    let mut ok = true;
+    let mut results = Vec::new();
+    for path in paths::expand(&args.paths)? {
+        if Kind::of(&path) != Some(Kind::Map) {
+            continue;
+        }
+        let result = match map::read(&path) {
+            Ok(map) => {
+                let assets = match AssetIndex::data_root(&path) {
+                    Some(root) => AssetIndex::new(&root)?,
+                    None => AssetIndex::default(),
+                };
+                let manifest = map.audio_manifest(&assets);
+                if !args.json {
+                    println!("{}: {} sounds, {} files, {} missing",
+                        path.display(), manifest.sounds.len(),
+                        manifest.files().len(), manifest.missing().count());
+                    for s in &manifest.sounds {
+                        let [x, y, z] = s.position;
+                        let found = s.path.as_ref()
+                            .map_or("missing".to_string(), |p| {
+                                p.display().to_string()
+                            });
+                        println!("    {} {} {:?} at ({x}, {y}, {z}): {found}",
+                            s.sound, s.object_name, s.field);
+                    }
+                }
+                json!({ "path": path, "sounds": manifest.sounds })
+            }
+            Err(e) => {
+                ok = false;
+                if !args.json {
+                    println!("{}: {e:#}", path.display());
+                }
+                json!({ "path": path, "error": format!("{e:#}") })
+            }
+        };
+        results.push(result);
+    }
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&results)?);
+    }
+    Ok(ok)
+}
@@ -0,0 +1,14 @@
+use rogue_reborn::scan;
+
+use crate::Args;
+use crate::paths;
+
+pub fn run(args: &Args) -> anyhow::Result<bool> {
+    let report = scan::scan(&paths::expand(&args.paths)?);
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        println!("{report}");
+    }
+    Ok(report.failed() == 0)
+}
@@ -0,0 +1,39 @@
+use rogue_reborn::map;
+use serde_json::json;
+
+use crate::Args;
+use crate::paths::{self, Kind};
+
+/// Succeeds only when every MAP parses and has no issues, so CI can gate on
+/// the exit code.
+pub fn run(args: &Args) -> anyhow::Result<bool> {
+    let mut ok = true;
+    let mut results = Vec::new();
+    for path in paths::expand(&args.paths)? {
+        if Kind::of(&path) != Some(Kind::Map) {
+            continue;
+        }
+        let result = match map::read(&path) {
+            Ok(map) => {
+                let report = map.validate();
+                ok &= report.is_ok();
+                if !args.json {
+                    println!("{}: {report}", path.display());
+                }
+                json!({ "path": path, "issues": report.issues })
+            }
+            Err(e) => {
+                ok = false;
+                if !args.json {
+                    println!("{}: {e:#}", path.display());
+                }
+                json!({ "path": path, "error": format!("{e:#}") })
+            }
+        };
+        results.push(result);
+    }
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&results)?);
+    }
+    Ok(ok)
+}
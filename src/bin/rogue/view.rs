@@ -0,0 +1,285 @@
+use std::path::PathBuf;
+
+use anyhow::Context;
+use minifb::{Key, KeyRepeat, MouseButton, MouseMode, Window, WindowOptions};
+use rogue_reborn::rsb::{self, Channel, Rsb};
+
+use crate::{Args, level};
+use crate::paths::{self, Kind};
+
+const WIDTH: usize = 800;
+const HEIGHT: usize = 600;
+
+const HELP: &str = "\
+Left/Right page, +/- or mouse wheel zoom, drag left mouse to pan, 0 fit
+C colour, R/G/B channels, A alpha, P palette indices, M masked pixels,
+Esc quit";
+
+pub fn run(args: &Args) -> anyhow::Result<bool> {
+    let paths = paths::expand(&args.paths)?;
+    let maps = paths.iter()
+        .filter(|path| Kind::of(path) == Some(Kind::Map))
+        .count();
+    if maps > 0 {
+        anyhow::ensure!(paths.len() == 1,
+            "view opens one MAP or any number of RSBs, but {maps} of the {} \
+            files are MAPs", paths.len());
+        return level::run(&paths[0]);
+    }
+
+    let (paths, skipped): (Vec<_>, Vec<_>) = paths.into_iter()
+        .partition(|path| Kind::of(path) == Some(Kind::Rsb));
+    for path in &skipped {
+        eprintln!("warning: skipping {}, which isn't an RSB", path.display());
+    }
+    anyhow::ensure!(!paths.is_empty(), "no RSB files to view");
+    println!("{HELP}");
+
+    let mut window = Window::new(
+        "rogue view",
+        WIDTH, HEIGHT,
+        WindowOptions { resize: true, ..WindowOptions::default() },
+    )
+    .context("window creation failed")?;
+    window.limit_update_rate(Some(std::time::Duration::from_millis(16)));
+
+    let mut browser = Browser::new(paths);
+    let mut buffer = Vec::new();
+    let mut last_mouse: Option<(f32, f32)> = None;
+    while window.is_open() && !window.is_key_down(Key::Escape) {
+        browser.update(&window);
+
+        let mouse = window.get_mouse_pos(MouseMode::Pass);
+        if window.get_mouse_down(MouseButton::Left) {
+            if let (Some((x0, y0)), Some((x1, y1))) = (last_mouse, mouse) {
+                browser.pan[0] += x1 - x0;
+                browser.pan[1] += y1 - y0;
+            }
+            last_mouse = mouse;
+        } else {
+            last_mouse = None;
+        }
+        if let Some((_, dy)) = window.get_scroll_wheel() {
+            browser.zoom(if dy > 0.0 { 2.0 } else { 0.5 });
+        }
+
+        if browser.dirty {
+            window.set_title(&browser.status());
+            browser.dirty = false;
+        }
+
+        let (width, height) = window.get_size();
+        let (width, height) = (width.max(1), height.max(1));
+        buffer.resize(width * height, 0);
+        browser.draw(&mut buffer, width, height);
+        window.update_with_buffer(&buffer, width, height)?;
+    }
+
+    Ok(true)
+}
+
+/// Paging, zoom and pan state plus the decoded current texture
+struct Browser {
+    paths: Vec<PathBuf>,
+    index: usize,
+    channel: Channel,
+    /// Screen pixels per texel
+    scale: f32,
+    /// Offset of the texture centre from the window centre, in screen pixels
+    pan: [f32; 2],
+    current: Option<Result<Image, String>>,
+    /// The window title needs updating
+    dirty: bool,
+    /// Zoom and pan should be reset to fit the current texture
+    refit: bool,
+}
+
+/// The current texture decoded for display
+struct Image {
+    rsb: Rsb,
+    width: usize,
+    height: usize,
+    /// `0xAARRGGBB` pixels of the current channel, or `None` if the RSB
+    /// doesn't have it
+    pixels: Option<Vec<u32>>,
+}
+
+impl Browser {
+    fn new(paths: Vec<PathBuf>) -> Self {
+        let mut browser = Self {
+            paths,
+            index: 0,
+            channel: Channel::Color,
+            scale: 1.0,
+            pan: [0.0; 2],
+            current: None,
+            dirty: true,
+            refit: true,
+        };
+        browser.load();
+        browser
+    }
+
+    fn update(&mut self, window: &Window) {
+        let pressed = |key| window.is_key_pressed(key, KeyRepeat::Yes);
+        if pressed(Key::Right) {
+            self.index = (self.index + 1) % self.paths.len();
+            self.load();
+        }
+        if pressed(Key::Left) {
+            self.index = (self.index + self.paths.len() - 1) % self.paths.len();
+            self.load();
+        }
+
+        if pressed(Key::Equal) || pressed(Key::NumPadPlus) {
+            self.zoom(2.0);
+        }
+        if pressed(Key::Minus) || pressed(Key::NumPadMinus) {
+            self.zoom(0.5);
+        }
+        if pressed(Key::Key0) {
+            self.fit(window.get_size());
+        }
+
+        for (key, channel) in [
+            (Key::C, Channel::Color),
+            (Key::R, Channel::Red),
+            (Key::G, Channel::Green),
+            (Key::B, Channel::Blue),
+            (Key::A, Channel::Alpha),
+            (Key::P, Channel::PaletteIndex),
+            (Key::M, Channel::Masked),
+        ] {
+            let pressed = window.is_key_pressed(key, KeyRepeat::No);
+            if pressed && channel != self.channel {
+                self.channel = channel;
+                self.decode();
+            }
+        }
+
+        // Fit on the next frame after a load, once the window has a size
+        if self.refit {
+            self.fit(window.get_size());
+            self.refit = false;
+        }
+    }
+
+    fn load(&mut self) {
+        let path = &self.paths[self.index];
+        self.current = Some(match rsb::read(path) {
+            Ok(rsb) => {
+                println!("{rsb}");
+                Ok(Image {
+                    width: rsb.width as usize,
+                    height: rsb.height as usize,
+                    rsb,
+                    pixels: None,
+                })
+            }
+            Err(e) => {
+                eprintln!("{}: {e:#}", path.display());
+                Err(format!("{e:#}"))
+            }
+        });
+        self.refit = true;
+        self.decode();
+    }
+
+    fn decode(&mut self) {
+        if let Some(Ok(image)) = &mut self.current {
+            image.pixels = image.rsb.channel_rgba8(self.channel).map(|rgba| {
+                rgba.chunks_exact(4)
+                    .map(|p| u32::from_be_bytes([p[3], p[0], p[1], p[2]]))
+                    .collect()
+            });
+        }
+        self.dirty = true;
+    }
+
+    /// Largest power of two zoom that fits the texture in the window
+    fn fit(&mut self, (width, height): (usize, usize)) {
+        self.pan = [0.0; 2];
+        self.scale = 1.0;
+        if let Some(Ok(image)) = &self.current {
+            let fit = (width as f32 / image.width.max(1) as f32)
+                .min(height as f32 / image.height.max(1) as f32);
+            self.scale = 2f32.powi(fit.log2().floor() as i32)
+                .clamp(1.0 / 16.0, 64.0);
+        }
+        self.dirty = true;
+    }
+
+    fn zoom(&mut self, factor: f32) {
+        let scale = (self.scale * factor).clamp(1.0 / 16.0, 64.0);
+        let factor = scale / self.scale;
+        self.pan = self.pan.map(|x| x * factor);
+        self.scale = scale;
+        self.dirty = true;
+    }
+
+    /// The window title: where we are, the view settings and the RSB
+    /// `Display` info on one line
+    fn status(&self) -> String {
+        let path = self.paths[self.index].display();
+        let page = format!("[{}/{}]", self.index + 1, self.paths.len());
+        match &self.current {
+            Some(Ok(image)) => {
+                let info = image.rsb.to_string()
+                    .lines()
+                    .skip(1)
+                    .filter(|line| *line != "}")
+                    .map(str::trim)
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                let missing = match image.pixels {
+                    Some(_) => "",
+                    None => " (none)",
+                };
+                format!("{page} {path} {:?}{missing} {}x: {info}",
+                    self.channel, self.scale)
+            }
+            Some(Err(e)) => format!("{page} {path}: {e}"),
+            None => format!("{page} {path}"),
+        }
+    }
+
+    fn draw(&self, buffer: &mut [u32], width: usize, height: usize) {
+        buffer.fill(0x202830);
+        let Some(Ok(image)) = &self.current else {
+            return;
+        };
+        let Some(pixels) = &image.pixels else {
+            return;
+        };
+
+        let left = width as f32 / 2.0 + self.pan[0]
+            - image.width as f32 * self.scale / 2.0;
+        let top = height as f32 / 2.0 + self.pan[1]
+            - image.height as f32 * self.scale / 2.0;
+        for y in 0..height {
+            let ty = ((y as f32 + 0.5 - top) / self.scale).floor();
+            if ty < 0.0 || ty >= image.height as f32 {
+                continue;
+            }
+            for x in 0..width {
+                let tx = ((x as f32 + 0.5 - left) / self.scale).floor();
+                if tx < 0.0 || tx >= image.width as f32 {
+                    continue;
+                }
+                let texel = pixels[ty as usize * image.width + tx as usize];
+                buffer[y * width + x] = over_checkerboard(texel, x, y);
+            }
+        }
+    }
+}
+
+/// Blend a `0xAARRGGBB` texel over a grey checkerboard so alpha is visible
+fn over_checkerboard(texel: u32, x: usize, y: usize) -> u32 {
+    let background = if (x / 8 + y / 8).is_multiple_of(2) { 0x99 } else { 0x66 };
+    let alpha = texel >> 24;
+    let channel = |shift: u32| {
+        let c = (texel >> shift) & 0xff;
+        ((c * alpha + background * (255 - alpha)) / 255) << shift
+    };
+    channel(16) | channel(8) | channel(0)
+}
@@ -0,0 +1,669 @@
+use std::io::Write;
+
+use byteorder::{LE, WriteBytesExt};
+
+use crate::image::{Filter, Image};
+use crate::rsb::{BitMask, Rsb, Transfer};
+
+/// Texel formats GPU containers are written in
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// 16-bit 5/6/5 with red in the high bits, the RSB 565 layout
+    B5G6R5,
+    /// 16-bit 4/4/4/4 with alpha in the high bits, the RSB 4444 layout
+    B4G4R4A4,
+    /// 8 bits per channel, red first in memory
+    Rgba8,
+    /// 4x4 blocks of two 565 endpoints with 1-bit alpha, 8 bytes each.
+    /// Also known as DXT1.
+    Bc1,
+    /// 4x4 blocks of interpolated 8-bit alpha then BC1 colour, 16 bytes
+    /// each. Also known as DXT5.
+    Bc3,
+}
+
+impl Format {
+    /// The format an RSB's pixels already are, so they can be written as
+    /// they are without quantizing again. Only the 16-bit layouts Rogue
+    /// Spear ships have one.
+    pub fn native(bitmask: &BitMask) -> Option<Self> {
+        match (bitmask.r, bitmask.g, bitmask.b, bitmask.a) {
+            (5, 6, 5, 0) => Some(Self::B5G6R5),
+            (4, 4, 4, 4) => Some(Self::B4G4R4A4),
+            _ => None,
+        }
+    }
+
+    fn bitmask(self) -> Option<BitMask> {
+        match self {
+            Self::B5G6R5 => Some(BitMask { r: 5, g: 6, b: 5, a: 0 }),
+            Self::B4G4R4A4 => Some(BitMask { r: 4, g: 4, b: 4, a: 4 }),
+            _ => None,
+        }
+    }
+
+    /// Bytes per 4x4 block for block-compressed formats
+    fn block_size(self) -> Option<usize> {
+        match self {
+            Self::Bc1 => Some(8),
+            Self::Bc3 => Some(16),
+            _ => None,
+        }
+    }
+
+    /// Bytes per texel, or per block for block-compressed formats
+    fn texel_size(self) -> usize {
+        match self {
+            Self::B5G6R5 | Self::B4G4R4A4 => 2,
+            Self::Rgba8 => 4,
+            Self::Bc1 => 8,
+            Self::Bc3 => 16,
+        }
+    }
+
+    /// Encoded size of a `width` by `height` level
+    fn level_size(self, width: u32, height: u32) -> usize {
+        let (width, height) = (width as usize, height as usize);
+        match self.block_size() {
+            Some(block) => width.div_ceil(4) * height.div_ceil(4) * block,
+            None => width * height * self.texel_size(),
+        }
+    }
+}
+
+/// A texture encoded in a GPU `Format`, ready to be written to a DDS or KTX2
+/// container
+#[derive(Clone, Debug)]
+pub struct Texture {
+    pub width: u32,
+    pub height: u32,
+    pub format: Format,
+    /// How the colour channels are encoded. `encode` leaves this `Linear`
+    /// and `from_rsb` takes it from the RSB's `ColorModel`.
+    pub transfer: Transfer,
+    /// Encoded mip levels, largest first
+    pub levels: Vec<Vec<u8>>,
+}
+
+impl Texture {
+    /// Encode `rsb` as `format`, with a full mip chain when `mipmaps`. When
+    /// `format` is the RSB's `Format::native` the top level is copied as is.
+    pub fn from_rsb(rsb: &Rsb, format: Format, mipmaps: bool)
+        -> anyhow::Result<Self>
+    {
+        let image = Image::from_rsb(rsb)?;
+        let levels = if mipmaps {
+            image.mipmaps(Filter::Box)
+        } else {
+            vec![image]
+        };
+        let mut texture = Self::encode(&levels, format)?;
+        texture.transfer = rsb.color_model.transfer;
+
+        let native = rsb.pixels.as_bgra16()
+            .filter(|_| Format::native(&rsb.bitmask) == Some(format));
+        if let Some(values) = native {
+            texture.levels[0] = values.iter()
+                .flat_map(|x| x.to_le_bytes())
+                .collect();
+        }
+        Ok(texture)
+    }
+
+    /// Encode decoded mip `levels`, largest first, as `format`
+    pub fn encode(levels: &[Image], format: Format) -> anyhow::Result<Self> {
+        anyhow::ensure!(!levels.is_empty(), "no levels to encode");
+        let encoded = levels.iter()
+            .map(|level| encode_level(level, format))
+            .collect::<anyhow::Result<_>>()?;
+        Ok(Self {
+            width: levels[0].width,
+            height: levels[0].height,
+            format,
+            transfer: Transfer::Linear,
+            levels: encoded,
+        })
+    }
+
+    /// Write as a DirectDraw Surface using the legacy header, which every DDS
+    /// reader understands for these formats
+    pub fn write_dds(&self, mut w: impl Write) -> anyhow::Result<()> {
+        const CAPS: u32 = 0x1;
+        const HEIGHT: u32 = 0x2;
+        const WIDTH: u32 = 0x4;
+        const PITCH: u32 = 0x8;
+        const PIXEL_FORMAT: u32 = 0x1000;
+        const MIPMAP_COUNT: u32 = 0x20000;
+        const LINEAR_SIZE: u32 = 0x80000;
+        const ALPHA_PIXELS: u32 = 0x1;
+        const FOURCC: u32 = 0x4;
+        const RGB: u32 = 0x40;
+        const COMPLEX: u32 = 0x8;
+        const TEXTURE: u32 = 0x1000;
+        const MIPMAP: u32 = 0x400000;
+
+        let mipmapped = self.levels.len() > 1;
+        let compressed = self.format.block_size().is_some();
+        let mut flags = CAPS | HEIGHT | WIDTH | PIXEL_FORMAT;
+        if mipmapped {
+            flags |= MIPMAP_COUNT;
+        }
+        let pitch = if compressed {
+            flags |= LINEAR_SIZE;
+            self.levels[0].len()
+        } else {
+            flags |= PITCH;
+            self.width as usize * self.format.texel_size()
+        };
+
+        w.write_all(b"DDS ")?;
+        w.write_u32::<LE>(124)?;
+        w.write_u32::<LE>(flags)?;
+        w.write_u32::<LE>(self.height)?;
+        w.write_u32::<LE>(self.width)?;
+        w.write_u32::<LE>(pitch as u32)?;
+        w.write_u32::<LE>(0)?; // depth
+        w.write_u32::<LE>(self.levels.len() as u32)?;
+        w.write_all(&[0; 11 * 4])?;
+
+        // Pixel format: flags, FourCC, bit count, then RGBA masks
+        let (flags, fourcc, bits, masks) = match self.format {
+            Format::B5G6R5 => (RGB, [0; 4], 16, [0xf800, 0x07e0, 0x001f, 0]),
+            Format::B4G4R4A4 => (RGB | ALPHA_PIXELS, [0; 4], 16,
+                [0x0f00, 0x00f0, 0x000f, 0xf000]),
+            Format::Rgba8 => (RGB | ALPHA_PIXELS, [0; 4], 32,
+                [0xff, 0xff00, 0xff0000, 0xff000000]),
+            Format::Bc1 => (FOURCC, *b"DXT1", 0, [0; 4]),
+            Format::Bc3 => (FOURCC, *b"DXT5", 0, [0; 4]),
+        };
+        w.write_u32::<LE>(32)?;
+        w.write_u32::<LE>(flags)?;
+        w.write_all(&fourcc)?;
+        w.write_u32::<LE>(bits)?;
+        for mask in masks {
+            w.write_u32::<LE>(mask)?;
+        }
+
+        let caps = if mipmapped { TEXTURE | COMPLEX | MIPMAP } else { TEXTURE };
+        w.write_u32::<LE>(caps)?;
+        w.write_all(&[0; 4 * 4])?; // caps 2 to 4 and reserved
+
+        for level in &self.levels {
+            w.write_all(level)?;
+        }
+        Ok(())
+    }
+
+    /// Write as a Khronos KTX2 texture with no supercompression; see `Format`
+    /// for the channel layouts. An sRGB `transfer` picks the `*_SRGB` format
+    /// where Vulkan has one. The 16-bit formats have none, so they are always
+    /// written as UNORM and linear, and an sRGB texture in them has to be
+    /// decoded by whoever loads it.
+    pub fn write_ktx2(&self, mut w: impl Write) -> anyhow::Result<()> {
+        const IDENTIFIER: [u8; 12] = [
+            0xab, 0x4b, 0x54, 0x58, 0x20, 0x32, 0x30, 0xbb, 0x0d, 0x0a, 0x1a,
+            0x0a,
+        ];
+        let srgb = self.transfer == Transfer::Srgb;
+        let (vk_format, type_size, srgb) = match (self.format, srgb) {
+            (Format::B5G6R5, _) => (4, 2, false), // R5G6B5_UNORM_PACK16
+            (Format::B4G4R4A4, _) => (1000340000, 2, false), // A4R4G4B4_UNORM
+            (Format::Rgba8, false) => (37, 1, false), // R8G8B8A8_UNORM
+            (Format::Rgba8, true) => (43, 1, true), // R8G8B8A8_SRGB
+            (Format::Bc1, false) => (133, 1, false), // BC1_RGBA_UNORM_BLOCK
+            (Format::Bc1, true) => (134, 1, true), // BC1_RGBA_SRGB_BLOCK
+            (Format::Bc3, false) => (137, 1, false), // BC3_UNORM_BLOCK
+            (Format::Bc3, true) => (138, 1, true), // BC3_SRGB_BLOCK
+        };
+
+        let dfd = data_format_descriptor(self.format, srgb);
+        let level_count = self.levels.len();
+        let dfd_offset = 12 + 9 * 4 + 4 * 4 + 2 * 8 + level_count * 3 * 8;
+        let alignment = lcm(self.format.texel_size(), 4);
+
+        // Level data is stored smallest first, each aligned
+        let mut offset = dfd_offset + dfd.len();
+        let mut offsets = vec![0; level_count];
+        for (i, level) in self.levels.iter().enumerate().rev() {
+            offset = offset.next_multiple_of(alignment);
+            offsets[i] = offset;
+            offset += level.len();
+        }
+
+        w.write_all(&IDENTIFIER)?;
+        w.write_u32::<LE>(vk_format)?;
+        w.write_u32::<LE>(type_size)?;
+        w.write_u32::<LE>(self.width)?;
+        w.write_u32::<LE>(self.height)?;
+        w.write_u32::<LE>(0)?; // depth
+        w.write_u32::<LE>(0)?; // layers
+        w.write_u32::<LE>(1)?; // faces
+        w.write_u32::<LE>(level_count as u32)?;
+        w.write_u32::<LE>(0)?; // supercompression
+
+        w.write_u32::<LE>(dfd_offset as u32)?;
+        w.write_u32::<LE>(dfd.len() as u32)?;
+        w.write_u32::<LE>(0)?; // key/value data offset and length
+        w.write_u32::<LE>(0)?;
+        w.write_u64::<LE>(0)?; // supercompression global data
+        w.write_u64::<LE>(0)?;
+        for (level, offset) in self.levels.iter().zip(&offsets) {
+            w.write_u64::<LE>(*offset as u64)?;
+            w.write_u64::<LE>(level.len() as u64)?;
+            w.write_u64::<LE>(level.len() as u64)?;
+        }
+        w.write_all(&dfd)?;
+
+        let mut written = dfd_offset + dfd.len();
+        for (level, offset) in self.levels.iter().zip(&offsets).rev() {
+            w.write_all(&vec![0; offset - written])?;
+            w.write_all(level)?;
+            written = offset + level.len();
+        }
+        Ok(())
+    }
+}
+
+fn lcm(a: usize, b: usize) -> usize {
+    let gcd = |mut a: usize, mut b: usize| {
+        while b != 0 {
+            (a, b) = (b, a % b);
+        }
+        a
+    };
+    a / gcd(a, b) * b
+}
+
+/// A KTX2 basic data format descriptor: which bits of a texel or block hold
+/// which channel, and whether colour is `srgb` encoded
+fn data_format_descriptor(format: Format, srgb: bool) -> Vec<u8> {
+    const RGBSDA: u8 = 1;
+    const BC1A: u8 = 128;
+    const BC3: u8 = 130;
+    const RED: u8 = 0;
+    const GREEN: u8 = 1;
+    const BLUE: u8 = 2;
+    const ALPHA: u8 = 15;
+    const BC1A_ALPHA_PRESENT: u8 = 1;
+    const BC_COLOR: u8 = 0;
+    // Sample qualifier for alpha in an sRGB texture, which stays linear
+    const LINEAR: u8 = 1 << 4;
+    const TRANSFER_LINEAR: u32 = 1;
+    const TRANSFER_SRGB: u32 = 2;
+
+    // (bit offset, bit length, channel)
+    let (model, block, samples): (u8, u8, &[(u16, u8, u8)]) = match format {
+        Format::B5G6R5 => (RGBSDA, 0,
+            &[(0, 5, BLUE), (5, 6, GREEN), (11, 5, RED)]),
+        Format::B4G4R4A4 => (RGBSDA, 0,
+            &[(0, 4, BLUE), (4, 4, GREEN), (8, 4, RED), (12, 4, ALPHA)]),
+        Format::Rgba8 => (RGBSDA, 0,
+            &[(0, 8, RED), (8, 8, GREEN), (16, 8, BLUE), (24, 8, ALPHA)]),
+        Format::Bc1 => (BC1A, 3, &[(0, 64, BC1A_ALPHA_PRESENT)]),
+        Format::Bc3 => (BC3, 3, &[(0, 64, ALPHA), (64, 64, BC_COLOR)]),
+    };
+
+    let block_size = 24 + 16 * samples.len();
+    let mut dfd = Vec::with_capacity(4 + block_size);
+    let mut word = |x: u32| dfd.extend(x.to_le_bytes());
+    word(4 + block_size as u32);
+    word(0); // Khronos vendor, basic descriptor type
+    word(2 | (block_size as u32) << 16); // version 2
+    // Model, BT.709 primaries, transfer, straight alpha
+    let transfer = if srgb { TRANSFER_SRGB } else { TRANSFER_LINEAR };
+    word(model as u32 | 1 << 8 | transfer << 16);
+    // Texel block dimensions minus one
+    word(u32::from_le_bytes([block, block, 0, 0]));
+    word(format.texel_size() as u32); // bytes in plane 0
+    word(0);
+    for &(offset, length, channel) in samples {
+        let channel = match channel {
+            ALPHA if srgb => ALPHA | LINEAR,
+            _ => channel,
+        };
+        word(offset as u32 | ((length - 1) as u32) << 16
+            | (channel as u32) << 24);
+        word(0); // sample position
+        word(0); // lower
+        word(if length >= 32 { u32::MAX } else { (1 << length) - 1 });
+    }
+    dfd
+}
+
+fn encode_level(image: &Image, format: Format) -> anyhow::Result<Vec<u8>> {
+    // `Image` fields are public, so check what `Image::new` would have.
+    // `block` needs at least one texel to repeat.
+    let (width, height) = (image.width, image.height);
+    anyhow::ensure!(width > 0 && height > 0, "{width}x{height} level is empty");
+    anyhow::ensure!(image.rgba.len() == width as usize * height as usize * 4,
+        "{width}x{height} level has {} bytes of RGBA", image.rgba.len());
+    let mut out = Vec::with_capacity(format.level_size(image.width,
+        image.height));
+    match format {
+        Format::B5G6R5 | Format::B4G4R4A4 => {
+            let rsb = image.to_rsb(format.bitmask().unwrap())?;
+            for x in rsb.pixels.as_bgra16().unwrap_or_default() {
+                out.extend(x.to_le_bytes());
+            }
+        }
+        Format::Rgba8 => out.extend_from_slice(&image.rgba),
+        Format::Bc1 | Format::Bc3 => {
+            for by in (0..image.height).step_by(4) {
+                for bx in (0..image.width).step_by(4) {
+                    let block = block(image, bx, by);
+                    if format == Format::Bc3 {
+                        out.extend(bc3_alpha(&block));
+                        out.extend(bc1_color(&block, false));
+                    } else {
+                        out.extend(bc1_color(&block, true));
+                    }
+                }
+            }
+        }
+    }
+    Ok(out)
+}
+
+/// The 4x4 texels at (`x`, `y`), repeating the edge past the image
+fn block(image: &Image, x: u32, y: u32) -> [[u8; 4]; 16] {
+    let mut block = [[0; 4]; 16];
+    for (i, texel) in block.iter_mut().enumerate() {
+        let tx = (x + i as u32 % 4).min(image.width - 1) as usize;
+        let ty = (y + i as u32 / 4).min(image.height - 1) as usize;
+        let p = (ty * image.width as usize + tx) * 4;
+        texel.copy_from_slice(&image.rgba[p..p + 4]);
+    }
+    block
+}
+
+fn to_565([r, g, b, _]: [u8; 4]) -> u16 {
+    let q = |x: u8, bits: u32| (x as u32 * ((1 << bits) - 1) + 127) / 255;
+    (q(r, 5) << 11 | q(g, 6) << 5 | q(b, 5)) as u16
+}
+
+fn from_565(c: u16) -> [i32; 3] {
+    let e = |x: u16, bits: u32| {
+        let max = (1u32 << bits) - 1;
+        ((x as u32 * 255 + max / 2) / max) as i32
+    };
+    [e(c >> 11, 5), e(c >> 5 & 0x3f, 6), e(c & 0x1f, 5)]
+}
+
+/// Encode the colour half of a block. Endpoints are the extremes along the
+/// block's principal axis. With `punch_through`, texels under half alpha use
+/// BC1's transparent index; otherwise the block is always four-colour as BC3
+/// requires.
+fn bc1_color(block: &[[u8; 4]; 16], punch_through: bool) -> [u8; 8] {
+    let transparent = |p: &[u8; 4]| punch_through && p[3] < 128;
+    let opaque = block.iter()
+        .filter(|p| !transparent(p))
+        .map(|p| [p[0] as f32, p[1] as f32, p[2] as f32])
+        .collect::<Vec<_>>();
+    let has_transparent = opaque.len() < 16;
+    if opaque.is_empty() {
+        // Three-colour mode with every index transparent
+        return [0, 0, 0, 0, 0xff, 0xff, 0xff, 0xff];
+    }
+
+    let n = opaque.len() as f32;
+    let mean = (0..3)
+        .map(|c| opaque.iter().map(|p| p[c]).sum::<f32>() / n)
+        .collect::<Vec<_>>();
+    let mut covariance = [[0.0f32; 3]; 3];
+    for p in &opaque {
+        for i in 0..3 {
+            for j in 0..3 {
+                covariance[i][j] += (p[i] - mean[i]) * (p[j] - mean[j]);
+            }
+        }
+    }
+    let mut axis = [1.0f32, 1.0, 1.0];
+    for _ in 0..8 {
+        let next = [0, 1, 2].map(|i| {
+            (0..3).map(|j| covariance[i][j] * axis[j]).sum::<f32>()
+        });
+        let length = next.iter().map(|x| x * x).sum::<f32>().sqrt();
+        if length < 1e-6 {
+            break;
+        }
+        axis = next.map(|x| x / length);
+    }
+    let project = |p: &[f32; 3]| {
+        (0..3).map(|c| (p[c] - mean[c]) * axis[c]).sum::<f32>()
+    };
+    let (mut low, mut high) = (opaque[0], opaque[0]);
+    for p in &opaque {
+        if project(p) < project(&low) {
+            low = *p;
+        }
+        if project(p) > project(&high) {
+            high = *p;
+        }
+    }
+    let endpoint = |[r, g, b]: [f32; 3]| to_565([r as u8, g as u8, b as u8, 0]);
+    let (mut c0, mut c1) = (endpoint(high), endpoint(low));
+
+    // c0 > c1 selects four colours, c0 <= c1 three plus transparent
+    let three_colour = has_transparent;
+    if three_colour == (c0 > c1) {
+        (c0, c1) = (c1, c0);
+    }
+    let (e0, e1) = (from_565(c0), from_565(c1));
+    let mix = |a: i32, b: i32, wa: i32, wb: i32| (a * wa + b * wb) / (wa + wb);
+    let palette: Vec<[i32; 3]> = if c0 > c1 {
+        vec![e0, e1,
+            [0, 1, 2].map(|c| mix(e0[c], e1[c], 2, 1)),
+            [0, 1, 2].map(|c| mix(e0[c], e1[c], 1, 2))]
+    } else {
+        vec![e0, e1, [0, 1, 2].map(|c| mix(e0[c], e1[c], 1, 1))]
+    };
+
+    let mut indices = 0u32;
+    for (i, p) in block.iter().enumerate() {
+        let index = if transparent(p) {
+            3
+        } else {
+            palette.iter()
+                .enumerate()
+                .min_by_key(|(_, q)| {
+                    (0..3).map(|c| (p[c] as i32 - q[c]).pow(2)).sum::<i32>()
+                })
+                .map_or(0, |(index, _)| index as u32)
+        };
+        indices |= index << (2 * i);
+    }
+
+    let mut out = [0; 8];
+    out[0..2].copy_from_slice(&c0.to_le_bytes());
+    out[2..4].copy_from_slice(&c1.to_le_bytes());
+    out[4..8].copy_from_slice(&indices.to_le_bytes());
+    out
+}
+
+/// Encode the alpha half of a BC3 block between its lowest and highest alpha
+fn bc3_alpha(block: &[[u8; 4]; 16]) -> [u8; 8] {
+    let a0 = block.iter().map(|p| p[3]).max().unwrap_or(255);
+    let a1 = block.iter().map(|p| p[3]).min().unwrap_or(255);
+    let (w0, w1) = (a0 as u32, a1 as u32);
+    // a0 > a1 selects eight interpolated alphas
+    let palette = (0..8u32)
+        .map(|i| match i {
+            0 => w0,
+            1 => w1,
+            i => (w0 * (8 - i) + w1 * (i - 1)) / 7,
+        })
+        .collect::<Vec<_>>();
+
+    let mut indices = 0u64;
+    if a0 > a1 {
+        for (i, p) in block.iter().enumerate() {
+            let index = palette.iter()
+                .enumerate()
+                .min_by_key(|(_, &a)| (a as i32 - p[3] as i32).abs())
+                .map_or(0, |(index, _)| index as u64);
+            indices |= index << (3 * i);
+        }
+    }
+
+    let mut out = [0; 8];
+    out[0] = a0;
+    out[1] = a1;
+    out[2..8].copy_from_slice(&indices.to_le_bytes()[..6]);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Decode a BC1 block to RGBA, for checking the encoder
+    fn decode_bc1(block: &[u8], punch_through: bool) -> [[i32; 4]; 16] {
+        let c0 = u16::from_le_bytes([block[0], block[1]]);
+        let c1 = u16::from_le_bytes([block[2], block[3]]);
+        let (e0, e1) = (from_565(c0), from_565(c1));
+        let four = c0 > c1 || !punch_through;
+        let mix = |wa: i32, wb: i32| {
+            [0, 1, 2].map(|c| (e0[c] * wa + e1[c] * wb) / (wa + wb))
+        };
+        let palette = if four {
+            [e0, e1, mix(2, 1), mix(1, 2)].map(|[r, g, b]| [r, g, b, 255])
+        } else {
+            let [r, g, b] = mix(1, 1);
+            [[e0[0], e0[1], e0[2], 255], [e1[0], e1[1], e1[2], 255],
+                [r, g, b, 255], [0, 0, 0, 0]]
+        };
+        let indices = u32::from_le_bytes([block[4], block[5], block[6],
+            block[7]]);
+        std::array::from_fn(|i| palette[(indices >> (2 * i) & 3) as usize])
+    }
+
+    fn gradient(width: u32, height: u32) -> Image {
+        let rgba = (0..width * height)
+            .flat_map(|i| {
+                // Colours on a line, which BC1 represents well
+                let (x, y) = (i % width, i / width);
+                let t = (x + y * width) * 255 / (width * height);
+                [t as u8, (t / 2) as u8, 64,
+                    if x < width / 2 { 255 } else { 0 }]
+            })
+            .collect();
+        Image::new(width, height, rgba).unwrap()
+    }
+
+    #[test]
+    fn bc1_blocks_stay_close_to_the_source() {
+        let image = gradient(8, 8);
+        let levels = std::slice::from_ref(&image);
+        let texture = Texture::encode(levels, Format::Bc1).unwrap();
+        assert_eq!(texture.levels[0].len(), 4 * 8);
+
+        for (b, encoded) in texture.levels[0].chunks_exact(8).enumerate() {
+            let decoded = decode_bc1(encoded, true);
+            let source = block(&image, b as u32 % 2 * 4, b as u32 / 2 * 4);
+            for (d, s) in decoded.iter().zip(&source) {
+                if s[3] < 128 {
+                    assert_eq!(d[3], 0);
+                    continue;
+                }
+                for c in 0..3 {
+                    assert!((d[c] - s[c] as i32).abs() <= 24,
+                        "{decoded:?} vs {source:?}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn bc3_alpha_is_interpolated() {
+        let image = gradient(4, 4);
+        let texture = Texture::encode(&[image], Format::Bc3).unwrap();
+        let block = &texture.levels[0];
+        assert_eq!(block.len(), 16);
+        assert_eq!((block[0], block[1]), (255, 0));
+    }
+
+    #[test]
+    fn empty_levels_are_errors() {
+        for format in [Format::Rgba8, Format::Bc1, Format::Bc3] {
+            let empty = Image { width: 4, height: 0, rgba: Vec::new() };
+            let error = Texture::encode(&[empty], format).unwrap_err();
+            assert_eq!(error.to_string(), "4x0 level is empty");
+        }
+        let short = Image { width: 4, height: 4, rgba: vec![0; 4] };
+        assert!(Texture::encode(&[short], Format::Bc1).is_err());
+    }
+
+    #[test]
+    fn native_16_bit_pixels_pass_through() {
+        let rsb = crate::rsb::read(std::path::Path::new(
+            "data/texture/faces/Chavez_hrt_face.RSB")).unwrap();
+        let texture = Texture::from_rsb(&rsb, Format::B4G4R4A4, true).unwrap();
+        assert_eq!(texture.levels.len(), 7);
+        let first = rsb.pixels.as_bgra16().unwrap()[0];
+        assert_eq!(texture.levels[0][..2], first.to_le_bytes());
+
+        let mut dds = Vec::new();
+        texture.write_dds(&mut dds).unwrap();
+        let data = 64 * 64 * 2 + 32 * 32 * 2 + 16 * 16 * 2 + 8 * 8 * 2
+            + 4 * 4 * 2 + 2 * 2 * 2 + 2;
+        assert_eq!(dds.len(), 4 + 124 + data);
+        assert_eq!(&dds[128..130], &texture.levels[0][..2]);
+    }
+
+    #[test]
+    fn ktx2_header_follows_the_transfer() {
+        let mut rsb = crate::rsb::read(std::path::Path::new(
+            "data/texture/faces/Chavez_hrt_face.RSB")).unwrap();
+        // (vkFormat, DFD transfer, DFD channel byte of the alpha sample)
+        let header = |rsb: &Rsb, format| {
+            let texture = Texture::from_rsb(rsb, format, false).unwrap();
+            let mut ktx2 = Vec::new();
+            texture.write_ktx2(&mut ktx2).unwrap();
+            let u32_at = |i: usize| {
+                u32::from_le_bytes(ktx2[i..i + 4].try_into().unwrap())
+            };
+            let dfd = u32_at(48) as usize;
+            let alpha = match format {
+                Format::Rgba8 => 3,
+                _ => 0,
+            };
+            (u32_at(12), ktx2[dfd + 14], ktx2[dfd + 28 + 16 * alpha + 3])
+        };
+
+        assert_eq!(rsb.color_model.transfer, Transfer::Srgb);
+        assert_eq!(header(&rsb, Format::Rgba8), (43, 2, 0x1f));
+        assert_eq!(header(&rsb, Format::Bc3), (138, 2, 0x1f));
+        assert_eq!(header(&rsb, Format::Bc1).0, 134);
+        assert_eq!(header(&rsb, Format::B4G4R4A4), (1000340000, 1, 2));
+
+        rsb.color_model.transfer = Transfer::Linear;
+        assert_eq!(header(&rsb, Format::Rgba8), (37, 1, 15));
+        assert_eq!(header(&rsb, Format::Bc3), (137, 1, 15));
+        assert_eq!(header(&rsb, Format::Bc1).0, 133);
+    }
+
+    #[test]
+    fn ktx2_levels_are_indexed_smallest_last() {
+        let levels = gradient(8, 4).mipmaps(Filter::Box);
+        let texture = Texture::encode(&levels, Format::Bc3).unwrap();
+        let mut ktx2 = Vec::new();
+        texture.write_ktx2(&mut ktx2).unwrap();
+
+        let u32_at = |i: usize| {
+            u32::from_le_bytes(ktx2[i..i + 4].try_into().unwrap())
+        };
+        let u64_at = |i: usize| {
+            u64::from_le_bytes(ktx2[i..i + 8].try_into().unwrap()) as usize
+        };
+        assert_eq!(u32_at(12), 137);
+        assert_eq!(u32_at(40), 4); // level count
+        for (i, level) in texture.levels.iter().enumerate() {
+            let entry = 80 + i * 24;
+            let (offset, length) = (u64_at(entry), u64_at(entry + 8));
+            assert_eq!(offset % 16, 0);
+            assert_eq!(&ktx2[offset..offset + length], level.as_slice());
+        }
+        // The largest level is stored last
+        assert_eq!(u64_at(80) + u64_at(88), ktx2.len());
+    }
+}
@@ -0,0 +1,283 @@
+use crate::rsb::{BitMask, Rsb};
+
+/// A decoded 8-bit RGBA image to filter, resize and build mipmaps from.
+/// Filtering is done on premultiplied alpha so that fully transparent texels,
+/// whose colour in a 4444 RSB is often garbage, don't bleed into their
+/// neighbours.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    /// 8-bit RGBA, row by row
+    pub rgba: Vec<u8>,
+}
+
+/// Resampling filter. Each is usable both to shrink and to enlarge.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Filter {
+    /// Pick the closest texel. Keeps hard pixel edges when enlarging.
+    Nearest,
+    /// Average of the covered texels, the classic mipmap filter
+    Box,
+    /// Linear interpolation, tent shaped
+    Triangle,
+    /// Windowed sinc with three lobes. Sharpest, but can ring on hard edges.
+    Lanczos3,
+}
+
+impl Filter {
+    /// Kernel radius in source texels at a scale of 1
+    fn support(self) -> f32 {
+        match self {
+            Self::Nearest | Self::Box => 0.5,
+            Self::Triangle => 1.0,
+            Self::Lanczos3 => 3.0,
+        }
+    }
+
+    fn weight(self, x: f32) -> f32 {
+        let x = x.abs();
+        match self {
+            Self::Nearest | Self::Box => if x <= 0.5 { 1.0 } else { 0.0 },
+            Self::Triangle => (1.0 - x).max(0.0),
+            Self::Lanczos3 => {
+                if x >= 3.0 {
+                    0.0
+                } else {
+                    sinc(x) * sinc(x / 3.0)
+                }
+            }
+        }
+    }
+}
+
+fn sinc(x: f32) -> f32 {
+    if x < 1e-6 {
+        return 1.0;
+    }
+    let x = x * std::f32::consts::PI;
+    x.sin() / x
+}
+
+impl Image {
+    /// Resampling clamps to the edge texels, so an image must have at least
+    /// one
+    pub fn new(width: u32, height: u32, rgba: Vec<u8>) -> anyhow::Result<Self> {
+        anyhow::ensure!(width > 0 && height > 0,
+            "{width}x{height} image is empty");
+        anyhow::ensure!(rgba.len() == width as usize * height as usize * 4,
+            "expected {width}x{height} RGBA pixels but have {} bytes",
+            rgba.len());
+        Ok(Self { width, height, rgba })
+    }
+
+    /// Decode with the RSB's `ColorModel` alpha applied, see
+    /// `Rsb::to_rgba8_straight`
+    pub fn from_rsb(rsb: &Rsb) -> anyhow::Result<Self> {
+        Self::new(rsb.width, rsb.height, rsb.to_rgba8_straight())
+    }
+
+    /// Quantize back to a 16-bit RSB, see `Rsb::from_rgba8`
+    pub fn to_rsb(&self, bitmask: BitMask) -> anyhow::Result<Rsb> {
+        Rsb::from_rgba8(self.width, self.height, &self.rgba, bitmask)
+    }
+
+    /// Resample to `width` by `height`. Edges are clamped.
+    pub fn resize(&self, width: u32, height: u32, filter: Filter) -> Self {
+        let width = width.max(1);
+        let height = height.max(1);
+        if filter == Filter::Nearest {
+            return self.resize_nearest(width, height);
+        }
+
+        // Premultiply, then filter rows and columns separately
+        let premultiplied = self.rgba.chunks_exact(4)
+            .flat_map(|p| {
+                let a = p[3] as f32 / 255.0;
+                [p[0] as f32 * a, p[1] as f32 * a, p[2] as f32 * a,
+                    p[3] as f32]
+            })
+            .collect::<Vec<_>>();
+        let (w, h) = (self.width as usize, self.height as usize);
+        let (new_w, new_h) = (width as usize, height as usize);
+
+        let columns = weights(w, new_w, filter);
+        let mut horizontal = vec![0.0; new_w * h * 4];
+        for y in 0..h {
+            for (x, taps) in columns.iter().enumerate() {
+                let out = (y * new_w + x) * 4;
+                for &(source, weight) in taps {
+                    let i = (y * w + source) * 4;
+                    for c in 0..4 {
+                        horizontal[out + c] += premultiplied[i + c] * weight;
+                    }
+                }
+            }
+        }
+
+        let rows = weights(h, new_h, filter);
+        let mut rgba = Vec::with_capacity(new_w * new_h * 4);
+        for taps in &rows {
+            for x in 0..new_w {
+                let mut p = [0.0f32; 4];
+                for &(source, weight) in taps {
+                    let i = (source * new_w + x) * 4;
+                    for c in 0..4 {
+                        p[c] += horizontal[i + c] * weight;
+                    }
+                }
+                rgba.extend(unpremultiply(p));
+            }
+        }
+        Self { width, height, rgba }
+    }
+
+    fn resize_nearest(&self, width: u32, height: u32) -> Self {
+        let source = |i: u32, from: u32, to: u32| {
+            ((i as u64 * 2 + 1) * from as u64 / (to as u64 * 2)) as usize
+        };
+        let mut rgba = Vec::with_capacity((width * height * 4) as usize);
+        for y in 0..height {
+            let sy = source(y, self.height, height);
+            for x in 0..width {
+                let sx = source(x, self.width, width);
+                let i = (sy * self.width as usize + sx) * 4;
+                rgba.extend_from_slice(&self.rgba[i..i + 4]);
+            }
+        }
+        Self { width, height, rgba }
+    }
+
+    /// Half the size in each dimension, rounding down but never below 1
+    pub fn downsample(&self, filter: Filter) -> Self {
+        self.resize(self.width / 2, self.height / 2, filter)
+    }
+
+    /// The full mipmap chain starting with this image and halving down to
+    /// 1x1. Each level is filtered from the one before it.
+    pub fn mipmaps(&self, filter: Filter) -> Vec<Self> {
+        let mut levels = vec![self.clone()];
+        loop {
+            let last = &levels[levels.len() - 1];
+            if last.width == 1 && last.height == 1 {
+                break;
+            }
+            levels.push(last.downsample(filter));
+        }
+        levels
+    }
+}
+
+/// For each of `to` output texels along one axis, the source texels and
+/// weights that make it up. Weights sum to 1.
+fn weights(from: usize, to: usize, filter: Filter) -> Vec<Vec<(usize, f32)>> {
+    let scale = from as f32 / to as f32;
+    // Shrinking widens the kernel to cover every source texel
+    let stretch = scale.max(1.0);
+    let support = filter.support() * stretch;
+    (0..to)
+        .map(|i| {
+            let centre = (i as f32 + 0.5) * scale - 0.5;
+            let first = (centre - support).floor() as i64;
+            let last = (centre + support).ceil() as i64;
+            let mut taps = Vec::new();
+            for j in first..=last {
+                let weight = filter.weight((j as f32 - centre) / stretch);
+                if weight != 0.0 {
+                    let source = j.clamp(0, from as i64 - 1) as usize;
+                    taps.push((source, weight));
+                }
+            }
+            let total = taps.iter().map(|(_, w)| w).sum::<f32>();
+            if total != 0.0 {
+                for (_, w) in &mut taps {
+                    *w /= total;
+                }
+            }
+            taps
+        })
+        .collect()
+}
+
+fn unpremultiply([r, g, b, a]: [f32; 4]) -> [u8; 4] {
+    let a = a.clamp(0.0, 255.0);
+    if a < 0.5 {
+        return [0, 0, 0, 0];
+    }
+    // Ringing filters can push colour past alpha, which has no meaning
+    let channel = |c: f32| (c.clamp(0.0, a) * 255.0 / a).round() as u8;
+    [channel(r), channel(g), channel(b), a.round() as u8]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn solid(width: u32, height: u32, pixel: [u8; 4]) -> Image {
+        let rgba = pixel.repeat((width * height) as usize);
+        Image::new(width, height, rgba).unwrap()
+    }
+
+    #[test]
+    fn box_downsampling_averages() {
+        let image = Image::new(2, 2, vec![
+            0, 0, 0, 255, 200, 100, 40, 255,
+            200, 100, 40, 255, 0, 0, 0, 255,
+        ]).unwrap();
+        assert_eq!(image.downsample(Filter::Box).rgba, [100, 50, 20, 255]);
+    }
+
+    #[test]
+    fn transparent_texels_dont_bleed() {
+        // A fully transparent red texel beside opaque blue
+        let image = Image::new(2, 1, vec![255, 0, 0, 0, 0, 0, 255, 255])
+            .unwrap();
+        for filter in [Filter::Box, Filter::Triangle, Filter::Lanczos3] {
+            let half = image.resize(1, 1, filter);
+            assert_eq!(half.rgba[..3], [0, 0, 255], "{filter:?}");
+            assert!((127..=128).contains(&half.rgba[3]), "{filter:?}");
+        }
+    }
+
+    #[test]
+    fn mipmaps_halve_down_to_one_texel() {
+        let levels = solid(64, 16, [10, 20, 30, 40]).mipmaps(Filter::Box);
+        let sizes = levels.iter()
+            .map(|level| (level.width, level.height))
+            .collect::<Vec<_>>();
+        assert_eq!(sizes, [(64, 16), (32, 8), (16, 4), (8, 2), (4, 1), (2, 1),
+            (1, 1)]);
+        assert_eq!(levels[6].rgba, [10, 20, 30, 40]);
+    }
+
+    #[test]
+    fn upscaling_keeps_flat_colour_and_nearest_keeps_texels() {
+        let image = solid(3, 5, [90, 180, 45, 255]);
+        let large = image.resize(7, 11, Filter::Lanczos3);
+        assert!(large.rgba.chunks_exact(4).all(|p| p == [90, 180, 45, 255]));
+
+        let image = Image::new(2, 1, vec![1, 2, 3, 4, 5, 6, 7, 8]).unwrap();
+        let large = image.resize(4, 1, Filter::Nearest);
+        assert_eq!(large.rgba,
+            [1, 2, 3, 4, 1, 2, 3, 4, 5, 6, 7, 8, 5, 6, 7, 8]);
+    }
+
+    #[test]
+    fn empty_images_are_rejected() {
+        assert!(Image::new(0, 0, Vec::new()).is_err());
+        assert!(Image::new(4, 0, Vec::new()).is_err());
+        let bitmask = BitMask { r: 4, g: 4, b: 4, a: 4 };
+        let rsb = Rsb::from_rgba8(0, 0, &[], bitmask).unwrap();
+        let error = Image::from_rsb(&rsb).unwrap_err();
+        assert_eq!(error.to_string(), "0x0 image is empty");
+    }
+
+    #[test]
+    fn shipped_face_round_trips_through_a_mip_level() {
+        let rsb = crate::rsb::read(std::path::Path::new(
+            "data/texture/faces/Chavez_hrt_face.RSB")).unwrap();
+        let half = Image::from_rsb(&rsb).unwrap().downsample(Filter::Lanczos3);
+        let rsb = half.to_rsb(rsb.bitmask.clone()).unwrap();
+        assert_eq!((rsb.width, rsb.height), (32, 32));
+    }
+}
@@ -1,2 +1,9 @@
+pub mod animation;
+pub mod assets;
+pub mod gpu;
+pub mod image;
+pub mod limits;
 pub mod map;
 pub mod rsb;
+pub mod scan;
+pub mod vec3;
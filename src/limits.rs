@@ -0,0 +1,144 @@
+use anyhow::ensure;
+
+/// Upper bounds on the sizes the RSB and MAP readers accept from a file.
+/// Every count and length is also checked against the bytes left in the
+/// input, so a small file can't make a reader allocate more than it could
+/// possibly hold. These limits tighten that further for untrusted input.
+///
+/// The defaults are well above anything in the shipped game data.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Limits {
+    /// Longest string, including its null terminator
+    pub max_string_len: u32,
+    /// Most vertices in one list, e.g. an object's vertices or texture
+    /// vertices
+    pub max_vertices: u32,
+    /// Most faces, or face indices, in one list
+    pub max_faces: u32,
+    /// Most items in any other list: materials, objects, portals, names, etc.
+    pub max_objects: u32,
+    /// Widest or tallest RSB texture
+    pub max_texture_dimension: u32,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_string_len: 4096,
+            max_vertices: 1 << 20,
+            max_faces: 1 << 20,
+            max_objects: 1 << 16,
+            max_texture_dimension: 8192,
+        }
+    }
+}
+
+/// The kinds of list `Limits` bounds separately
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Count {
+    Vertices,
+    Faces,
+    Objects,
+}
+
+impl Limits {
+    /// Limits that only check against the remaining input
+    pub fn none() -> Self {
+        Self {
+            max_string_len: u32::MAX,
+            max_vertices: u32::MAX,
+            max_faces: u32::MAX,
+            max_objects: u32::MAX,
+            max_texture_dimension: u32::MAX,
+        }
+    }
+
+    pub fn max(&self, count: Count) -> u32 {
+        match count {
+            Count::Vertices => self.max_vertices,
+            Count::Faces => self.max_faces,
+            Count::Objects => self.max_objects,
+        }
+    }
+
+    /// Check `n` items of at least `item_size` bytes each against the limit
+    /// for `count` and the `remaining` bytes of input
+    pub fn check_count(
+        &self,
+        count: Count,
+        n: u32,
+        item_size: usize,
+        remaining: usize,
+    ) -> anyhow::Result<()> {
+        let max = self.max(count);
+        ensure!(n <= max, "{n} {} exceed the limit of {max}",
+            count.name());
+        check_remaining(n as usize, item_size, remaining, count.name())
+    }
+
+    /// Check a string of `len` bytes against `max_string_len` and the
+    /// `remaining` bytes of input
+    pub fn check_string(&self, len: u32, remaining: usize)
+        -> anyhow::Result<()>
+    {
+        ensure!(len <= self.max_string_len,
+            "string of {len} bytes exceeds the limit of {}",
+            self.max_string_len);
+        check_remaining(len as usize, 1, remaining, "string bytes")
+    }
+
+    /// Check RSB dimensions against `max_texture_dimension`
+    pub fn check_texture(&self, width: u32, height: u32) -> anyhow::Result<()> {
+        let max = self.max_texture_dimension;
+        ensure!(width <= max && height <= max,
+            "{width}x{height} texture exceeds the limit of {max}x{max}");
+        Ok(())
+    }
+}
+
+impl Count {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Vertices => "vertices",
+            Self::Faces => "faces",
+            Self::Objects => "items",
+        }
+    }
+}
+
+/// Check that `n` items of at least `item_size` bytes fit in `remaining`
+pub fn check_remaining(
+    n: usize,
+    item_size: usize,
+    remaining: usize,
+    what: &str,
+) -> anyhow::Result<()> {
+    let needed = n.saturating_mul(item_size);
+    ensure!(needed <= remaining,
+        "{n} {what} need at least {needed} bytes but only {remaining} remain");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_are_checked_against_limits_and_input() {
+        let limits = Limits { max_vertices: 10, ..Default::default() };
+        assert!(limits.check_count(Count::Vertices, 10, 12, 120).is_ok());
+        let error = limits.check_count(Count::Vertices, 11, 12, 1000)
+            .unwrap_err();
+        assert_eq!(error.to_string(), "11 vertices exceed the limit of 10");
+        let error = limits.check_count(Count::Vertices, 10, 12, 119)
+            .unwrap_err();
+        assert_eq!(error.to_string(),
+            "10 vertices need at least 120 bytes but only 119 remain");
+
+        let limits = Limits::none();
+        assert!(limits.check_count(Count::Objects, u32::MAX, 0, 0).is_ok());
+        assert!(limits.check_string(u32::MAX, 16).is_err());
+        assert!(limits.check_texture(u32::MAX, 1).is_ok());
+        assert!(Limits::default().check_texture(8193, 1).is_err());
+    }
+}
@@ -4,6 +4,43 @@ use std::path::Path;
 
 use anyhow::{Context, Result, bail, ensure};
 use byteorder::{LE, ReadBytesExt};
+use serde::Serialize;
+
+use crate::limits::{Count, Limits};
+
+mod aabb;
+mod atlas;
+mod audio;
+mod diff;
+mod effects;
+mod export;
+mod fields;
+mod glass;
+mod nav;
+mod refs;
+mod sim;
+mod validate;
+
+pub use aabb::Aabb;
+pub use atlas::{
+    Atlas, AtlasImage, AtlasOptions, AtlasPage, Exclusion, Placement,
+};
+pub use audio::{AudioManifest, SoundReference};
+pub use diff::{Changed, FieldChange, MapDiff, SectionDiff, Value, diff};
+pub use effects::{Emitter, EmitterKind};
+pub use export::{to_gltf, to_mtl, to_obj};
+pub use fields::{
+    Bin, Correlation, Distribution, FieldSamples, ValueCount,
+};
+pub use glass::{Fragment, Pane, ShatterOptions};
+pub use nav::{
+    NavEdge, NavGraph, NavLink, NavNode, TransitionKind, TransitionVolume,
+};
+pub use refs::{
+    Reference, ReferenceField, ReferenceGraph, ReferenceTarget,
+};
+pub use sim::{Motion, Mover, Simulation, Touchplate};
+pub use validate::{ValidationIssue, ValidationReport};
 
 const MAGIC: &[u8] = b"BeginMapv2.1";
 const END: &[u8] = b"EndMap";
@@ -15,7 +52,7 @@ const END: &[u8] = b"EndMap";
 /// The `Map` type does not represent the on-disk format exactly. Where the
 /// on-disk format has list lengths, the in-memory `Map` type uses `Vec<T>` and
 /// omits the explicit length.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct Map {
     pub header: MapHeader,
     pub materials: Materials,
@@ -29,7 +66,7 @@ pub struct Map {
 }
 
 impl Map {
-    fn read(buf: &mut Cursor<Vec<u8>>) -> Result<Self> {
+    fn read(buf: &mut Input<'_>) -> Result<Self> {
         let header = MapHeader::read(buf).context("MAP Header")?;
         let materials = Materials::read(buf).context("Materials List")?;
         let geometries = Geometries::read(buf).context("Geometry List")?;
@@ -65,18 +102,32 @@ pub fn read(filename: &Path) -> anyhow::Result<Map> {
     let mut reader = BufReader::new(file);
     let mut buf = Vec::new();
     reader.read_to_end(&mut buf).context("failed to read MAP file")?;
-    let mut buf = Cursor::new(buf);
-    Map::read(&mut buf)
+    read_bytes(&buf)
 }
 
-#[derive(Clone, Debug)]
+/// Parse a MAP already in memory, e.g. an upload that was never written to
+/// disk, with the default `Limits`
+pub fn read_bytes(bytes: &[u8]) -> anyhow::Result<Map> {
+    read_bytes_with_limits(bytes, &Limits::default())
+}
+
+/// `read_bytes` with every count and string length checked against
+/// `limits`
+pub fn read_bytes_with_limits(
+    bytes: &[u8],
+    limits: &Limits,
+) -> anyhow::Result<Map> {
+    Map::read(&mut Input { cursor: Cursor::new(bytes), limits })
+}
+
+#[derive(Clone, Debug, Serialize)]
 pub struct MapHeader {
     /// Unix timestamp of when the MAP file was created
     pub timestamp: u32,
 }
 
 impl MapHeader {
-    fn read(buf: &mut Cursor<Vec<u8>>) -> Result<Self> {
+    fn read(buf: &mut Input<'_>) -> Result<Self> {
         let magic = buf.read_cstring().context("missing magic")?;
         if magic != MAGIC {
             anyhow::bail!("incorrect magic: '{magic:?}'");
@@ -90,18 +141,20 @@ impl MapHeader {
 }
 
 /// List of all `Material`s for the level
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct Materials {
     pub id: u32,
     pub materials: Vec<Material>,
 }
 
 impl Materials {
-    fn read(buf: &mut Cursor<Vec<u8>>) -> Result<Self> {
+    fn read(buf: &mut Input<'_>) -> Result<Self> {
         let (id, _material_list) = section_header(buf)
             .context("material list section header")?;
 
-        let n = buf.read_u32::<LE>().context("missing number of materials")?;
+        let size = MIN_SECTION_HEADER + MIN_CSTRING + 4 * 3 + 16 * 3 + 4 + 1;
+        let n = buf.read_count(Count::Objects, size)
+            .context("missing number of materials")?;
         let mut materials = Vec::with_capacity(n as usize);
         for i in 0..n {
             materials.push(Material::read(buf)
@@ -117,7 +170,7 @@ impl Materials {
 }
 
 /// Texture material reference and rendering parameters
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct Material {
     pub id: u32,
     pub filename: String,
@@ -133,7 +186,7 @@ pub struct Material {
 }
 
 impl Material {
-    fn read(buf: &mut Cursor<Vec<u8>>) -> Result<Self> {
+    fn read(buf: &mut Input<'_>) -> Result<Self> {
         let (id, name) = section_header(buf)
             .context("material section header")?;
 
@@ -167,7 +220,7 @@ impl Material {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
 pub enum TextureAddressMode {
     Opaque,
     Wrap,
@@ -175,7 +228,7 @@ pub enum TextureAddressMode {
 }
 
 impl TextureAddressMode {
-    fn read(buf: &mut Cursor<Vec<u8>>) -> Result<Self> {
+    fn read(buf: &mut Input<'_>) -> Result<Self> {
         let address_mode = buf.read_u32::<LE>()
             .context("texture address mode")?;
         Ok(match address_mode {
@@ -188,7 +241,7 @@ impl TextureAddressMode {
 }
 
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct Color4f {
     pub r: f32,
     pub g: f32,
@@ -197,7 +250,7 @@ pub struct Color4f {
 }
 
 impl Color4f {
-    fn read(buf: &mut Cursor<Vec<u8>>) -> Result<Self> {
+    fn read(buf: &mut Input<'_>) -> Result<Self> {
         let r = buf.read_f32::<LE>().context("red")?;
         let g = buf.read_f32::<LE>().context("green")?;
         let b = buf.read_f32::<LE>().context("blue")?;
@@ -206,18 +259,19 @@ impl Color4f {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct Geometries {
     pub id: u32,
     pub objects: Vec<Object>,
 }
 
 impl Geometries {
-    fn read(buf: &mut Cursor<Vec<u8>>) -> Result<Self> {
+    fn read(buf: &mut Input<'_>) -> Result<Self> {
         let (id, _material_list) = section_header(buf)
             .context("geometry list section header")?;
 
-        let n = buf.read_u32::<LE>().context("missing number of objects")?;
+        let n = buf.read_count(Count::Objects, MIN_SECTION_HEADER * 2 + 4 * 6)
+            .context("missing number of objects")?;
         let mut objects = Vec::with_capacity(n as usize);
         for _ in 0..n {
             objects.push(Object::read(buf)?);
@@ -230,7 +284,7 @@ impl Geometries {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct Object {
     pub id: u32,
     pub name: String,
@@ -245,7 +299,7 @@ pub struct Object {
 }
 
 impl Object {
-    fn read(buf: &mut Cursor<Vec<u8>>) -> Result<Self> {
+    fn read(buf: &mut Input<'_>) -> Result<Self> {
         let (id, name) = section_header(buf)
             .context("section header")?;
 
@@ -253,13 +307,15 @@ impl Object {
         let (object_id, object_name) = section_header(buf)
             .context("object section header")?;
 
-        let n = buf.read_u32::<LE>().context("vertex count")?;
+        let n = buf.read_count(Count::Vertices, 12)
+            .context("vertex count")?;
         let mut vertices = Vec::with_capacity(n as usize);
         for _ in 0..n {
             vertices.push(Vertex::read(buf)?);
         }
 
-        let n = buf.read_u32::<LE>().context("objects data count")?;
+        let n = buf.read_count(Count::Objects, 4 * 3)
+            .context("objects data count")?;
         let mut object_datas = Vec::with_capacity(n as usize);
         for _ in 0..n {
             object_datas.push(ObjectData::read(buf)?);
@@ -267,14 +323,16 @@ impl Object {
 
         let collisions = Collisions::read(buf)?;
 
-        let n = buf.read_u32::<LE>().context("object tag count")?;
+        let n = buf.read_count(Count::Objects, 16)
+            .context("object tag count")?;
         let mut tags = Vec::with_capacity(n as usize);
         for _ in 0..n {
             tags.push(Tag::read(buf)?);
         }
 
         // TODO: these field names are wonky
-        let ff_count = buf.read_u32::<LE>().context("FF count")?;
+        let ff_count = buf.read_count(Count::Objects, MIN_CSTRING + 4 + 4)
+            .context("FF count")?;
         let mut ind = Vec::with_capacity(ff_count as usize);
         for i in 0..ff_count {
             let index = EIndices::read(buf)
@@ -296,7 +354,7 @@ impl Object {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct Vertex {
     pub x: f32,
     pub y: f32,
@@ -304,22 +362,25 @@ pub struct Vertex {
 }
 
 impl Vertex {
-    fn read(buf: &mut Cursor<Vec<u8>>) -> Result<Self> {
+    fn read(buf: &mut Input<'_>) -> Result<Self> {
         let (x, y, z) = buf.read_f32_xyz()?;
         Ok(Self { x, y, z })
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct ObjectData {
-    // TODO: what is this?
+    /// Index into `Materials::materials`, or `ObjectData::NO_MATERIAL`
     pub mn: u32,
     pub faces: Faces,
     pub texture_vertices: TextureVertices,
 }
 
 impl ObjectData {
-    fn read(buf: &mut Cursor<Vec<u8>>) -> Result<Self> {
+    /// The `mn` value used by geometry that isn't textured by any material
+    pub const NO_MATERIAL: u32 = u32::MAX;
+
+    fn read(buf: &mut Input<'_>) -> Result<Self> {
         let mn = buf.read_u32::<LE>().context("MN")?;
         let faces = Faces::read(buf)?;
         let texture_vertices = TextureVertices::read(buf)?;
@@ -331,7 +392,7 @@ impl ObjectData {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct Faces {
     pub normals: Vec<FaceNormal>,
     pub face_indices: Vec<(u16, u16, u16)>,
@@ -339,8 +400,9 @@ pub struct Faces {
 }
 
 impl Faces {
-    fn read(buf: &mut Cursor<Vec<u8>>) -> Result<Self> {
-        let n = buf.read_u32::<LE>().context("face count")? as usize;
+    fn read(buf: &mut Input<'_>) -> Result<Self> {
+        let n = buf.read_count(Count::Faces, 16 + 6 + 6)
+            .context("face count")? as usize;
 
         let mut normals = Vec::with_capacity(n);
         for i in 0..n {
@@ -379,7 +441,7 @@ impl Faces {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct FaceNormal {
     pub x: f32,
     pub y: f32,
@@ -389,7 +451,7 @@ pub struct FaceNormal {
 }
 
 impl FaceNormal {
-    fn read(buf: &mut Cursor<Vec<u8>>) -> Result<Self> {
+    fn read(buf: &mut Input<'_>) -> Result<Self> {
         let (x, y, z) = buf.read_f32_xyz()?;
         let dist = buf.read_f32::<LE>().context("distance origin to face")?;
         Ok(Self { x, y, z, distance_origin_to_face: dist })
@@ -397,7 +459,7 @@ impl FaceNormal {
 }
 
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct TextureVertices {
     pub normals: Vec<NormalCoord>,
     pub uv_coords: Vec<UvCoord>,
@@ -405,8 +467,9 @@ pub struct TextureVertices {
 }
 
 impl TextureVertices {
-    fn read(buf: &mut Cursor<Vec<u8>>) -> Result<Self> {
-        let n = buf.read_u32::<LE>().context("vertices count")? as usize;
+    fn read(buf: &mut Input<'_>) -> Result<Self> {
+        let n = buf.read_count(Count::Vertices, 12 + 8 + 16)
+            .context("vertices count")? as usize;
 
         let mut normals = Vec::with_capacity(n);
         for i in 0..n {
@@ -434,7 +497,7 @@ impl TextureVertices {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct NormalCoord {
     pub x: f32,
     pub y: f32,
@@ -442,42 +505,44 @@ pub struct NormalCoord {
 }
 
 impl NormalCoord {
-    fn read(buf: &mut Cursor<Vec<u8>>) -> Result<Self> {
+    fn read(buf: &mut Input<'_>) -> Result<Self> {
         let (x, y, z) = buf.read_f32_xyz()?;
         Ok(Self { x, y, z })
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct UvCoord {
     pub u: f32,
     pub v: f32,
 }
 
 impl UvCoord {
-    fn read(buf: &mut Cursor<Vec<u8>>) -> Result<Self> {
+    fn read(buf: &mut Input<'_>) -> Result<Self> {
         let u = buf.read_f32::<LE>().context("u")?;
         let v = buf.read_f32::<LE>().context("v")?;
         Ok(Self { u, v })
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct Collisions {
     pub vertices: Vec<Vertex>,
     pub faces: Vec<FaceNormal>,
 }
 
 impl Collisions {
-    fn read(buf: &mut Cursor<Vec<u8>>) -> Result<Self> {
-        let n = buf.read_u32::<LE>().context("collision vertices count")?;
+    fn read(buf: &mut Input<'_>) -> Result<Self> {
+        let n = buf.read_count(Count::Vertices, 12)
+            .context("collision vertices count")?;
         let mut vertices = Vec::with_capacity(n as usize);
         for i in 0..n {
             vertices.push(Vertex::read(buf)
                 .with_context(|| format!("collision vertex {i}"))?);
         }
 
-        let n = buf.read_u32::<LE>().context("collision faces count")?;
+        let n = buf.read_count(Count::Faces, 16)
+            .context("collision faces count")?;
         let mut faces = Vec::with_capacity(n as usize);
         for i in 0..n {
             faces.push(FaceNormal::read(buf)
@@ -488,7 +553,7 @@ impl Collisions {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct Tag {
     pub coord1: (u16, u16, u16),
     pub face_index_1: u16,
@@ -497,7 +562,7 @@ pub struct Tag {
 }
 
 impl Tag {
-    fn read(buf: &mut Cursor<Vec<u8>>) -> Result<Self> {
+    fn read(buf: &mut Input<'_>) -> Result<Self> {
         let p11 = buf.read_u16::<LE>().context("coord1 p1")?;
         let p21 = buf.read_u16::<LE>().context("coord1 p2")?;
         let p31 = buf.read_u16::<LE>().context("coord1 p3")?;
@@ -517,7 +582,7 @@ impl Tag {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct EIndices {
     // TODO: what is this?
     pub text: String,
@@ -526,10 +591,11 @@ pub struct EIndices {
 }
 
 impl EIndices {
-    fn read(buf: &mut Cursor<Vec<u8>>) -> Result<Self> {
+    fn read(buf: &mut Input<'_>) -> Result<Self> {
         let text = buf.read_cstring().context("EIndex text")?;
         let mn = buf.read_u32::<LE>().context("EIndex MN")?;
-        let n = buf.read_u32::<LE>().context("EIndex indices count")?;
+        let n = buf.read_count(Count::Faces, 2)
+            .context("EIndex indices count")?;
         let mut indices = Vec::with_capacity(n as usize);
         for _ in 0..n {
             let p1 = buf.read_u16::<LE>()?;
@@ -543,7 +609,7 @@ impl EIndices {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct Portals {
     pub id: u32,
     pub name: String,
@@ -551,9 +617,10 @@ pub struct Portals {
 }
 
 impl Portals {
-    fn read(buf: &mut Cursor<Vec<u8>>) -> Result<Self> {
+    fn read(buf: &mut Input<'_>) -> Result<Self> {
         let (id, name) = section_header(buf).context("portals")?;
-        let n = buf.read_u32::<LE>().context("portal count")?;
+        let n = buf.read_count(Count::Objects, MIN_SECTION_HEADER + 4 * 3)
+            .context("portal count")?;
         let mut portals = Vec::with_capacity(n as usize);
         for i in 0..n {
             portals.push(Portal::read(buf)
@@ -567,7 +634,7 @@ impl Portals {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct Portal {
     pub id: u32,
     pub name: String,
@@ -577,9 +644,10 @@ pub struct Portal {
 }
 
 impl Portal {
-    fn read(buf: &mut Cursor<Vec<u8>>) -> Result<Self> {
+    fn read(buf: &mut Input<'_>) -> Result<Self> {
         let (id, name) = section_header(buf).context("portal")?;
-        let n = buf.read_u32::<LE>().context("coordinates count")?;
+        let n = buf.read_count(Count::Vertices, 12)
+            .context("coordinates count")?;
         let mut coordinates = Vec::with_capacity(n as usize);
         for i in 0..n {
             coordinates.push(Vertex::read(buf)
@@ -599,7 +667,7 @@ impl Portal {
 
 // TODO: light count is zero for every RS map I tested. I think lights are
 // in the DMP files for RS.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct Lights {
     pub id: u32,
     pub name: String,
@@ -608,14 +676,14 @@ pub struct Lights {
 }
 
 impl Lights {
-    fn read(buf: &mut Cursor<Vec<u8>>) -> Result<Self> {
+    fn read(buf: &mut Input<'_>) -> Result<Self> {
         let (id, name) = section_header(buf).context("lights")?;
         let n = buf.read_u32::<LE>().context("light count")?;
         Ok(Self { id, name, light_count: n })
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct DynamicObjects {
     pub id: u32,
     pub name: String,
@@ -623,10 +691,12 @@ pub struct DynamicObjects {
 }
 
 impl DynamicObjects {
-    fn read(buf: &mut Cursor<Vec<u8>>) -> Result<Self> {
+    fn read(buf: &mut Input<'_>) -> Result<Self> {
         let (id, name) = section_header(buf)
             .context("dynamic objects section header")?;
-        let n = buf.read_u32::<LE>().context("dynamic object count")?;
+        let size = MIN_SECTION_HEADER + MIN_CSTRING + 48;
+        let n = buf.read_count(Count::Objects, size)
+            .context("dynamic object count")?;
         let mut dynamic_objects = Vec::with_capacity(n as usize);
         for i in 0..n {
             dynamic_objects.push(DynamicObject::read(buf)
@@ -636,7 +706,7 @@ impl DynamicObjects {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct DynamicObject {
     // TODO: if keeping the section header, then make a `SectionHeader` type
     // that holds the version and any additional names.
@@ -654,7 +724,7 @@ pub struct DynamicObject {
 
 /// Mappings of section header ID to the object type. This list is
 /// non-exhaustive at the moment and only used in `DynamicObject`.
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Serialize)]
 pub enum Id {
     /// An object with dynamic properties, such as televisions
     Dynamic = 14,
@@ -699,7 +769,7 @@ impl std::convert::TryFrom<u32> for Id {
 }
 
 impl DynamicObject {
-    fn read(buf: &mut Cursor<Vec<u8>>) -> Result<Self> {
+    fn read(buf: &mut Input<'_>) -> Result<Self> {
         let (section_id, section_name) = section_header(buf)
             .context("dynamic object section header")?;
         let name = buf.read_cstring().context("name")?;
@@ -719,7 +789,7 @@ impl DynamicObject {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct TransformationMatrix {
     pub x_axis: Vec3f,
     pub y_axis: Vec3f,
@@ -728,7 +798,19 @@ pub struct TransformationMatrix {
 }
 
 impl TransformationMatrix {
-    fn read(buf: &mut Cursor<Vec<u8>>) -> Result<Self> {
+    /// Map a point in the object's own space into map space
+    pub fn transform_point(&self, [x, y, z]: [f32; 3]) -> [f32; 3] {
+        let axes = [&self.x_axis, &self.y_axis, &self.z_axis];
+        let mut point = [self.position.x, self.position.y, self.position.z];
+        for (axis, scale) in axes.into_iter().zip([x, y, z]) {
+            point[0] += axis.x * scale;
+            point[1] += axis.y * scale;
+            point[2] += axis.z * scale;
+        }
+        point
+    }
+
+    fn read(buf: &mut Input<'_>) -> Result<Self> {
         let x_axis = Vec3f::read(buf).context("transformation matrix x-axis")?;
         let y_axis = Vec3f::read(buf).context("transformation matrix y-axis")?;
         let z_axis = Vec3f::read(buf).context("transformation matrix z-axis")?;
@@ -738,7 +820,7 @@ impl TransformationMatrix {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct Vec3f {
     pub x: f32,
     pub y: f32,
@@ -746,13 +828,13 @@ pub struct Vec3f {
 }
 
 impl Vec3f {
-    fn read(buf: &mut Cursor<Vec<u8>>) -> Result<Self> {
+    fn read(buf: &mut Input<'_>) -> Result<Self> {
         let (x, y, z) = buf.read_f32_xyz()?;
         Ok(Self { x, y, z })
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct Vec6f {
     pub x1: f32,
     pub y1: f32,
@@ -763,56 +845,57 @@ pub struct Vec6f {
 }
 
 impl Vec6f {
-    fn read(buf: &mut Cursor<Vec<u8>>) -> Result<Self> {
+    fn read(buf: &mut Input<'_>) -> Result<Self> {
         let (x1, y1, z1) = buf.read_f32_xyz()?;
         let (x2, y2, z2) = buf.read_f32_xyz()?;
         Ok(Self { x1, y1, z1, x2, y2, z2 })
     }
 }
 
-// TODO: I don't think we really have an 8-D vector in the game. Rename this
-// once I figure out what a 6-D vector with an additional X+Y is.
-#[derive(Clone, Debug)]
-pub struct Vec8f {
-    pub x1: f32,
-    pub y1: f32,
-    pub z1: f32,
-    pub x2: f32,
-    pub y2: f32,
-    pub z2: f32,
-    pub x3: f32,
-    pub y3: f32,
+/// One glow sprite of a `Halo`, drawn around a light
+#[derive(Clone, Debug, Serialize)]
+pub struct HaloSprite {
+    pub name: String,
+    /// Centre in map space
+    pub position: Vec3f,
+    /// Width of the glow in map units, 80 to 200 in the shipped maps
+    pub size: f32,
+    /// The light's colour. Alpha is 1 in the shipped maps.
+    pub color: Color4f,
 }
 
-impl Vec8f {
-    fn read(buf: &mut Cursor<Vec<u8>>) -> Result<Self> {
-        let (x1, y1, z1) = buf.read_f32_xyz()?;
-        let (x2, y2, z2) = buf.read_f32_xyz()?;
-        let (x3, y3) = buf.read_f32_xy()?;
-        Ok(Self { x1, y1, z1, x2, y2, z2, x3, y3 })
+impl HaloSprite {
+    fn read(buf: &mut Input<'_>) -> Result<Self> {
+        let name = buf.read_cstring().context("name")?;
+        let position = Vec3f::read(buf).context("position")?;
+        let size = buf.read_f32::<LE>().context("size")?;
+        let color = Color4f::read(buf).context("colour")?;
+        Ok(Self { name: latin1_to_utf8(&name), position, size, color })
     }
 }
 
-
-/// When the dynamic object section header is value 14
-#[derive(Clone, Debug)]
+/// When the dynamic object section header is value 14. Names below are
+/// inferred from the shipped maps, where every value but the positions and
+/// directions is the same; `rogue params` prints the distributions.
+#[derive(Clone, Debug, Serialize)]
 pub enum KindDynamicParams {
-    // TODO: bad names; do better
-
-    /// Count field is greater than zero
+    /// Count field is greater than zero: props that throw sparks when shot,
+    /// like control panels and monitors
     Struct(Vec<KindDynamicParamStruct>),
 
-    /// Count field is zero
+    /// Count field is zero: props without sparks, like dials
     Flat {
         names: Vec<String>,
-        // TODO: something about duration or color?
+        /// Always 1, 25, 0 and 0 in the shipped maps. The first two match the
+        /// last two of `KindDynamicParamStruct::unknown1`.
         unknown: [f32; 4],
     },
 }
 
 impl KindDynamicParams {
-    fn read(buf: &mut Cursor<Vec<u8>>) -> Result<Self> {
-        let count = buf.read_u32::<LE>().context("dynamic object kind count")?;
+    fn read(buf: &mut Input<'_>) -> Result<Self> {
+        let count = buf.read_count(Count::Objects, MIN_CSTRING + 4 * 11)
+            .context("dynamic object kind count")?;
         Ok(if count > 0 {
             let mut structs = Vec::with_capacity(count as usize);
             for i in 0..count {
@@ -823,7 +906,7 @@ impl KindDynamicParams {
             Self::Struct(structs)
         } else {
             // Yes, another count that shadows the previous one. MAP quirk.
-            let count = buf.read_u32::<LE>()
+            let count = buf.read_count(Count::Objects, MIN_CSTRING)
                 .context("dynamic object flat count")?;
             let mut names = Vec::with_capacity(count as usize);
             for i in 0..count {
@@ -834,74 +917,77 @@ impl KindDynamicParams {
                 names.push(latin1);
             }
 
-            let mut unknowns = vec![0f32; 4];
-            for (i, unknown) in unknowns.iter_mut().enumerate() {
-                *unknown = buf.read_f32::<LE>().with_context(|| {
+            let mut unknown = [0f32; 4];
+            for (i, x) in unknown.iter_mut().enumerate() {
+                *x = buf.read_f32::<LE>().with_context(|| {
                     format!("dynamic object kind flat unknown {i}")
                 })?;
             }
-            let unknown = unknowns.try_into()
-                .expect("dynamic object kind flat unknown is 4 elements");
 
             Self::Flat { names, unknown }
         })
     }
 }
 
-#[derive(Clone, Debug)]
+/// A spark emitter on a dynamic prop
+#[derive(Clone, Debug, Serialize)]
 pub struct KindDynamicParamStruct {
+    /// The effect's own name, e.g. "spark07". Nothing else in the MAP
+    /// refers to it.
     pub name: String,
-    // TODO: translation matrix?
-    pub unknown1: [f32; 9],
+    /// Map-space origin of the sparks, on or near the prop's surface
+    pub position: Vec3f,
+    /// Unit vector the sparks fly along, out of the prop's face
+    pub direction: Vec3f,
+    /// Always 0, 1 and 25 in the shipped maps
+    pub unknown1: [f32; 3],
+    /// Always 0 in the shipped maps
     pub unknown2: [u32; 2],
 }
 
 impl KindDynamicParamStruct {
-    fn read(buf: &mut Cursor<Vec<u8>>) -> Result<Self> {
+    fn read(buf: &mut Input<'_>) -> Result<Self> {
         let name = latin1_to_utf8(&buf.read_cstring()
             .context("dynamic object kind struct name")?);
-
-        let mut unknown1 = vec![0f32; 9];
+        let position = Vec3f::read(buf)
+            .context("dynamic object kind struct position")?;
+        let direction = Vec3f::read(buf)
+            .context("dynamic object kind struct direction")?;
+        let mut unknown1 = [0f32; 3];
         for (i, x) in unknown1.iter_mut().enumerate() {
             *x = buf.read_f32::<LE>().with_context(|| {
                 format!("dynamic object kind struct unknown1 {i}")
             })?;
         }
-        let unknown1 = unknown1.try_into()
-            .expect("dynamic object kind struct unknown1 is 9 elements");
-
-        let mut unknown2 = vec![0u32; 2];
+        let mut unknown2 = [0u32; 2];
         for (i, x) in unknown2.iter_mut().enumerate() {
             *x = buf.read_u32::<LE>().with_context(|| {
                 format!("dynamic object kind struct unknown2 {i}")
             })?;
         }
-        let unknown2 = unknown2.try_into()
-            .expect("dynamic object kind struct unknown2 is 2 elements");
 
-        Ok(Self { name, unknown1, unknown2 })
+        Ok(Self { name, position, direction, unknown1, unknown2 })
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct DynamicObjectKindCommon {
     pub tm: TransformationMatrix,
     pub name: String,
     // TODO: Always zero? zero is documented
     pub unknown1: u32,
     pub sounds: [String; 4],
-    // TODO: convert these strings to enums once all variants are known
-    pub collision_type_2d: String,
-    pub collision_type_3d: String,
-    pub destruction_action: String,
-    pub destruction_category: String,
-    pub penetration_type: String,
+    pub collision_type_2d: CollisionType,
+    pub collision_type_3d: CollisionType,
+    pub destruction_action: DestructionAction,
+    pub destruction_category: DestructionCategory,
+    pub penetration_type: PenetrationType,
     pub name2: String,
-    pub destruction_category2: String,
+    pub destruction_category2: DestructionCategory,
 }
 
 impl DynamicObjectKindCommon {
-    fn read(buf: &mut Cursor<Vec<u8>>) -> Result<Self> {
+    fn read(buf: &mut Input<'_>) -> Result<Self> {
         let tm = TransformationMatrix::read(buf)
             .context("transformation matrix")?;
         let name = buf.read_cstring().context("name")?;
@@ -934,18 +1020,191 @@ impl DynamicObjectKindCommon {
             name: latin1_to_utf8(&name),
             unknown1,
             sounds,
-            collision_type_2d: latin1_to_utf8(&collision_type_2d),
-            collision_type_3d: latin1_to_utf8(&collision_type_3d),
-            destruction_action: latin1_to_utf8(&destruction_action),
-            destruction_category: latin1_to_utf8(&destruction_category),
-            penetration_type: latin1_to_utf8(&penetration_type),
+            collision_type_2d: latin1_to_utf8(&collision_type_2d).into(),
+            collision_type_3d: latin1_to_utf8(&collision_type_3d).into(),
+            destruction_action: latin1_to_utf8(&destruction_action).into(),
+            destruction_category: latin1_to_utf8(&destruction_category).into(),
+            penetration_type: latin1_to_utf8(&penetration_type).into(),
             name2: latin1_to_utf8(&name2),
-            destruction_category2: latin1_to_utf8(&destruction_category2),
+            destruction_category2: latin1_to_utf8(&destruction_category2)
+                .into(),
         })
     }
 }
 
-#[derive(Clone, Debug)]
+// The string fields of dynamic objects. Only the values seen in the shipped
+// maps have variants, and they match the spelling used there. The game data
+// mixes "NONE" and "none", so `None` keeps whichever was read. Anything else
+// is kept as `Other`, so `as_str` always gives back the MAP's spelling.
+
+/// How an object collides in the 2D planning view or the 3D world
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub enum CollisionType {
+    None(String),
+    /// The object's bounding box
+    Box,
+    /// The object's collision faces
+    Polygon,
+    Other(String),
+}
+
+impl From<String> for CollisionType {
+    fn from(value: String) -> Self {
+        match value.as_str() {
+            "box" => Self::Box,
+            "polygon" => Self::Polygon,
+            _ if value.eq_ignore_ascii_case("none") => Self::None(value),
+            _ => Self::Other(value),
+        }
+    }
+}
+
+impl CollisionType {
+    /// The value as spelled in the MAP
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Box => "box",
+            Self::Polygon => "polygon",
+            Self::None(value) | Self::Other(value) => value,
+        }
+    }
+}
+
+/// The effect played when an object is destroyed
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub enum DestructionAction {
+    None(String),
+    SparkShower,
+    Other(String),
+}
+
+impl From<String> for DestructionAction {
+    fn from(value: String) -> Self {
+        match value.as_str() {
+            "spark shower" => Self::SparkShower,
+            _ if value.eq_ignore_ascii_case("none") => Self::None(value),
+            _ => Self::Other(value),
+        }
+    }
+}
+
+impl DestructionAction {
+    /// The value as spelled in the MAP
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::SparkShower => "spark shower",
+            Self::None(value) | Self::Other(value) => value,
+        }
+    }
+}
+
+/// How much punishment an object takes before it's destroyed
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub enum DestructionCategory {
+    /// Spelled "indestructable" in the game data
+    Indestructible,
+    Light,
+    Heavy,
+    Other(String),
+}
+
+impl From<String> for DestructionCategory {
+    fn from(value: String) -> Self {
+        match value.as_str() {
+            "indestructable" => Self::Indestructible,
+            "light" => Self::Light,
+            "heavy" => Self::Heavy,
+            _ => Self::Other(value),
+        }
+    }
+}
+
+impl DestructionCategory {
+    /// The value as spelled in the MAP
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Indestructible => "indestructable",
+            Self::Light => "light",
+            Self::Heavy => "heavy",
+            Self::Other(value) => value,
+        }
+    }
+}
+
+/// The material bullets pass through, which decides whether and how much
+/// they penetrate
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub enum PenetrationType {
+    None(String),
+    HollowThickMetal,
+    SolidThickMetal,
+    SolidThinMetal,
+    SolidThinWood,
+    Other(String),
+}
+
+impl From<String> for PenetrationType {
+    fn from(value: String) -> Self {
+        match value.as_str() {
+            "hollowThickMetal" => Self::HollowThickMetal,
+            "solidThickMetal" => Self::SolidThickMetal,
+            "solidThinMetal" => Self::SolidThinMetal,
+            "solidThinWood" => Self::SolidThinWood,
+            _ if value.eq_ignore_ascii_case("none") => Self::None(value),
+            _ => Self::Other(value),
+        }
+    }
+}
+
+impl PenetrationType {
+    /// The value as spelled in the MAP
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::HollowThickMetal => "hollowThickMetal",
+            Self::SolidThickMetal => "solidThickMetal",
+            Self::SolidThinMetal => "solidThinMetal",
+            Self::SolidThinWood => "solidThinWood",
+            Self::None(value) | Self::Other(value) => value,
+        }
+    }
+}
+
+/// How an animated object or door moves along its `direction`
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub enum AnimationType {
+    /// Rotate once, e.g. a swinging door
+    OneTimeRotation,
+    /// Translate once, e.g. a sliding door
+    OneTimeTranslation,
+    /// Rotate continuously, e.g. a fan
+    LoopRotation,
+    Other(String),
+}
+
+impl From<String> for AnimationType {
+    fn from(value: String) -> Self {
+        match value.as_str() {
+            "oneTimeRotation" => Self::OneTimeRotation,
+            "oneTimeTranslation" => Self::OneTimeTranslation,
+            "loopRotation" => Self::LoopRotation,
+            _ => Self::Other(value),
+        }
+    }
+}
+
+impl AnimationType {
+    /// The value as spelled in the MAP
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::OneTimeRotation => "oneTimeRotation",
+            Self::OneTimeTranslation => "oneTimeTranslation",
+            Self::LoopRotation => "loopRotation",
+            Self::Other(value) => value,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
 pub enum DynamicObjectKind {
     /// An object with dynamic properties like a television
     // Dynamic = 14,
@@ -968,8 +1227,7 @@ pub enum DynamicObjectKind {
         name3: String,
         name4: String,
 
-        // TODO: convert to enum once all variants are known
-        animation_type: String,
+        animation_type: AnimationType,
         direction: Vec3f,
         distance: f32,
         velocity: f32,
@@ -992,43 +1250,47 @@ pub enum DynamicObjectKind {
         name2: String,
         name3: String,
 
-        // TODO: convert to enum once all variants are known
-        animation_type: String,
+        animation_type: AnimationType,
         direction: Vec3f,
         distance: f32,
         velocity: f32,
     },
 
-    /// Breakable glass
+    /// Breakable glass. The pane is the mesh named by
+    /// `DynamicObject::name`, see `Map::glass_panes`.
     // Glass = 20,
     Glass {
-        name: String,
+        /// What bullets pass through. The shipped maps also use
+        /// "safetyGlass" here, which is kept as `PenetrationType::Other`.
+        penetration_type: PenetrationType,
     },
 
     /// A one-time interaction, such as some doors that open once
     // OneTimeTouchplate = 25,
     OneTimeTouchplate {
-        collision_type_2d: String,
-        collision_type_3d: String,
+        collision_type_2d: CollisionType,
+        collision_type_3d: CollisionType,
 
         coordinates: Vec6f,
         attachments: Vec<String>,
     },
 
-    /// Halo
+    /// Glows around lights. `DynamicObject::tm` of a halo isn't a transform:
+    /// it holds small integers, and each sprite has its own position.
     // Halo = 31,
     Halo {
-        /// A list of (name, coords) of halos
-        halos: Vec<(String, Vec8f)>,
+        halos: Vec<HaloSprite>,
     },
 
-    /// Static world effects like manhole steam and smoke stacks
+    /// Static world effects like manhole steam and smoke stacks, see
+    /// `Map::emitters`. Nothing is read after the transform, but that's
+    /// unverified: neither sample map has a static effect.
     // StaticEffect = 36,
     StaticEffect,
 }
 
 impl DynamicObjectKind {
-    fn read(id: Id, buf: &mut Cursor<Vec<u8>>) -> Result<Self> {
+    fn read(id: Id, buf: &mut Input<'_>) -> Result<Self> {
         let reader = match id {
             Id::Dynamic => Self::dynamic,
             Id::Animation => Self::animation,
@@ -1038,11 +1300,11 @@ impl DynamicObjectKind {
             Id::Halo => Self::halo,
             Id::StaticEffect => Self::static_effect,
         };
-        Ok(reader(buf)?)
+        reader(buf)
     }
 
     /// An object with dynamic properties like a television
-    fn dynamic(buf: &mut Cursor<Vec<u8>>) -> Result<Self> {
+    fn dynamic(buf: &mut Input<'_>) -> Result<Self> {
         let common = DynamicObjectKindCommon::read(buf)?;
         let params = KindDynamicParams::read(buf)?;
         Ok(Self::Dynamic {
@@ -1052,12 +1314,13 @@ impl DynamicObjectKind {
     }
 
     /// An object with an attached animation
-    fn animation(buf: &mut Cursor<Vec<u8>>) -> Result<Self> {
+    fn animation(buf: &mut Input<'_>) -> Result<Self> {
         let common = DynamicObjectKindCommon::read(buf)?;
 
         let unknown2 = buf.read_u32::<LE>().context("unknown2")?;
 
-        let n = buf.read_u32::<LE>().context("name count")?;
+        let n = buf.read_count(Count::Objects, MIN_CSTRING)
+            .context("name count")?;
         let mut names = Vec::with_capacity(n as usize);
         for i in 0..n {
             let name = buf.read_cstring()
@@ -1078,7 +1341,6 @@ impl DynamicObjectKind {
         let name3 = buf.read_cstring().context("animation name3")?;
         let name4 = buf.read_cstring().context("animation name4")?;
 
-        // // TODO: convert to enum once all variants are known
         let animation_type = buf.read_cstring().context("animation type")?;
         let direction = Vec3f::read(buf).context("animation direction")?;
         let distance = buf.read_f32::<LE>().context("animation distance")?;
@@ -1092,7 +1354,7 @@ impl DynamicObjectKind {
             unknown4,
             name3: latin1_to_utf8(&name3),
             name4: latin1_to_utf8(&name4),
-            animation_type: latin1_to_utf8(&animation_type),
+            animation_type: latin1_to_utf8(&animation_type).into(),
             direction,
             distance,
             velocity,
@@ -1102,10 +1364,11 @@ impl DynamicObjectKind {
     /// A door or automatic door that the player can interact with more than
     /// once. These often have the name "ADT" in MAPs. I think that stands for
     /// "Automatic Door Touchplate".
-    fn repeatable_touchplate(buf: &mut Cursor<Vec<u8>>) -> Result<Self> {
+    fn repeatable_touchplate(buf: &mut Input<'_>) -> Result<Self> {
         let common = DynamicObjectKindCommon::read(buf)?;
         let unknown1 = buf.read_u32::<LE>().context("ADT unknown1")?;
-        let n = buf.read_u32::<LE>().context("ADT attachment count")?;
+        let n = buf.read_count(Count::Objects, MIN_CSTRING)
+            .context("ADT attachment count")?;
         let mut attachments = Vec::with_capacity(n as usize);
         for i in 0..n {
             let attachment = buf.read_cstring()
@@ -1122,7 +1385,8 @@ impl DynamicObjectKind {
         let unknown2 = unknown2.try_into()
             .expect("ADT unknown2 is 3 elements");
 
-        let n = buf.read_u32::<LE>().context("name count")?;
+        let n = buf.read_count(Count::Objects, MIN_CSTRING)
+            .context("name count")?;
         let mut names = Vec::with_capacity(n as usize);
         for i in 0..n {
             let name = buf.read_cstring()
@@ -1133,9 +1397,8 @@ impl DynamicObjectKind {
         let name2 = latin1_to_utf8(&buf.read_cstring().context("ADT name2")?);
         let name3 = latin1_to_utf8(&buf.read_cstring().context("ADT name3")?);
 
-        // TODO: convert to enum once all variants are known
         let animation_type = latin1_to_utf8(&buf.read_cstring()
-            .context("animation type")?);
+            .context("animation type")?).into();
         let direction = Vec3f::read(buf).context("animation direction")?;
         let distance = buf.read_f32::<LE>().context("animation distance")?;
         let velocity = buf.read_f32::<LE>().context("animation velocity")?;
@@ -1156,22 +1419,25 @@ impl DynamicObjectKind {
     }
 
     /// Breakable glass
-    fn glass(buf: &mut Cursor<Vec<u8>>) -> Result<Self> {
-        let name = buf.read_cstring()?;
-        Ok(Self::Glass { name: String::from_utf8(name)? })
+    fn glass(buf: &mut Input<'_>) -> Result<Self> {
+        let penetration_type = buf.read_cstring()
+            .context("glass penetration type")?;
+        Ok(Self::Glass {
+            penetration_type: latin1_to_utf8(&penetration_type).into(),
+        })
     }
 
     /// A one-time interaction, such as some doors that open once
-    fn one_time_touchplate(buf: &mut Cursor<Vec<u8>>) -> Result<Self> {
+    fn one_time_touchplate(buf: &mut Input<'_>) -> Result<Self> {
         let collision_type_2d = latin1_to_utf8(&buf.read_cstring()
-            .context("one-time touchplate 2D collision type")?);
+            .context("one-time touchplate 2D collision type")?).into();
         let collision_type_3d = latin1_to_utf8(&buf.read_cstring()
-            .context("one-time touchplate 3D collision type")?);
+            .context("one-time touchplate 3D collision type")?).into();
 
         let coordinates = Vec6f::read(buf)
             .context("one-time touchplate coordinates")?;
 
-        let n = buf.read_u32::<LE>()
+        let n = buf.read_count(Count::Objects, MIN_CSTRING)
             .context("one-time touchplate attachment count")?;
         let mut attachments = Vec::with_capacity(n as usize);
         for i in 0..n {
@@ -1189,29 +1455,26 @@ impl DynamicObjectKind {
         })
     }
 
-    /// Halo
-    fn halo(buf: &mut Cursor<Vec<u8>>) -> Result<Self> {
-        let count = buf.read_u32::<LE>().context("halo count")?;
+    /// Glows around lights
+    fn halo(buf: &mut Input<'_>) -> Result<Self> {
+        let count = buf.read_count(Count::Objects, MIN_CSTRING + 4 * 8)
+            .context("halo count")?;
         let mut halos = Vec::with_capacity(count as usize);
         for i in 0..count {
-            let name = latin1_to_utf8(&buf.read_cstring().with_context(|| {
-                format!("halo name {i} of {count}")
-            })?);
-            let vec = Vec8f::read(buf)
-                .with_context(|| format!("halo vec {i} of {count}"))?;
-            halos.push((name, vec));
+            halos.push(HaloSprite::read(buf)
+                .with_context(|| format!("halo {i} of {count}"))?);
         }
         Ok(Self::Halo { halos })
     }
 
     /// Static world effects like manhole steam and smoke stacks
-    fn static_effect(_buf: &mut Cursor<Vec<u8>>) -> Result<Self> {
-        // nothing?
+    fn static_effect(_buf: &mut Input<'_>) -> Result<Self> {
+        // nothing? No sample map has one to check against
         Ok(Self::StaticEffect)
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct Rooms {
     pub section_id: u32,
     pub section_name: String,
@@ -1219,11 +1482,12 @@ pub struct Rooms {
 }
 
 impl Rooms {
-    fn read(buf: &mut Cursor<Vec<u8>>) -> Result<Self> {
+    fn read(buf: &mut Input<'_>) -> Result<Self> {
         let (section_id, section_name) = section_header(buf)
             .context("room list")?;
 
-        let n = buf.read_u32::<LE>().context("room count")?;
+        let n = buf.read_count(Count::Objects, 4 + MIN_CSTRING + 3 + 4 * 3)
+            .context("room count")?;
         let mut rooms = Vec::with_capacity(n as usize);
         for i in 0..n {
             rooms.push(Room::read(buf).with_context(|| format!("room {i}"))?);
@@ -1233,21 +1497,20 @@ impl Rooms {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct Room {
     pub section_id: u32,
     pub section_name: String,
 
-    pub unknown1: u8,
-    pub unknown2: u8,
-    pub unknown3: u8,
-    // Optionals below are influenced by the unknown values above
-    /// Set when `unknown1 == 0`
-    pub unknown4: Option<u8>,
-    /// Set when `unknown3 == 1`
-    pub unknown5: Option<[f32; 6]>,
-    /// Set when `unknown4 == 1`
-    pub unknown6: Option<[f32; 6]>,
+    /// Which of the boxes below are present
+    pub kind: RoomKind,
+    /// Present when `kind.has_bounds()`. Unverified: no shipped room has
+    /// one, so reading six floats as a minimum then maximum corner is a
+    /// guess.
+    pub bounds: Option<Aabb>,
+    /// Present when `kind.has_secondary_bounds()`, and as unverified as
+    /// `bounds`
+    pub secondary_bounds: Option<Aabb>,
 
     pub sherman_levels: Vec<ShermanLevel>,
 
@@ -1256,45 +1519,24 @@ pub struct Room {
 }
 
 impl Room {
-    fn read(buf: &mut Cursor<Vec<u8>>) -> Result<Self> {
+    fn read(buf: &mut Input<'_>) -> Result<Self> {
         let (section_id, section_name) = section_header_short(buf)
             .context("room section header short")?;
 
-        let unknown1 = buf.read_u8().context("room unknown1")?;
-        let unknown2 = buf.read_u8().context("room unknown2")?;
-        let unknown3 = buf.read_u8().context("room unknown3")?;
-
-        let unknown4 = if unknown1 == 0 {
-            Some(buf.read_u8().context("room unknown4")?)
-        } else {
-            None
-        };
-
-        let unknown5 = if unknown3 == 1 {
-            let mut array = [0f32; 6];
-            for (i, x) in array.iter_mut().enumerate() {
-                *x = buf.read_f32::<LE>().with_context(|| {
-                    format!("room unknown5 {i}")
-                })?;
-            }
-            Some(array)
+        let kind = RoomKind::read(buf)?;
+        let bounds = if kind.has_bounds() {
+            Some(read_aabb(buf).context("room bounds")?)
         } else {
             None
         };
-
-        let unknown6 = if unknown4.is_some_and(|x| x == 1) {
-            let mut array = [0f32; 6];
-            for (i, x) in array.iter_mut().enumerate() {
-                *x = buf.read_f32::<LE>().with_context(|| {
-                    format!("room unknown6 {i}")
-                })?;
-            }
-            Some(array)
+        let secondary_bounds = if kind.has_secondary_bounds() {
+            Some(read_aabb(buf).context("room secondary bounds")?)
         } else {
             None
         };
 
-        let n = buf.read_u32::<LE>().context("room level count")?;
+        let n = buf.read_count(Count::Objects, MIN_CSTRING + 4 + 4 + 1)
+            .context("room level count")?;
         let mut levels = Vec::with_capacity(n as usize);
         for i in 0..n {
             levels.push(ShermanLevel::read(buf).with_context(|| {
@@ -1302,7 +1544,8 @@ impl Room {
             })?);
         }
 
-        let n = buf.read_u32::<LE>().context("room level heights count")?;
+        let n = buf.read_count(Count::Objects, 4 * 2)
+            .context("room level heights count")?;
         let unknown7 = buf.read_f32::<LE>().context("room unknown7")?;
         let mut heights = Vec::with_capacity(n as usize);
         for i in 0..n {
@@ -1314,12 +1557,9 @@ impl Room {
         Ok(Self {
             section_id,
             section_name,
-            unknown1,
-            unknown2,
-            unknown3,
-            unknown4,
-            unknown5,
-            unknown6,
+            kind,
+            bounds,
+            secondary_bounds,
             sherman_levels: levels,
             unknown7,
             level_heights: heights,
@@ -1327,7 +1567,74 @@ impl Room {
     }
 }
 
-#[derive(Clone, Debug)]
+/// The bytes at the start of a room, which decide which of its boxes
+/// follow. The raw bytes are kept so a room writes back exactly as read: a
+/// box follows only when its flag is exactly 1, and the fourth byte only
+/// exists when the first is 0.
+///
+/// Neither shipped map sets a box flag, so both what the boxes bound and how
+/// they're laid out are unverified.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct RoomKind {
+    /// Meaning unknown, other than that `secondary_flag` follows only when
+    /// it's 0. 1 in about a third of rm19's rooms and none of
+    /// citystreet_large's.
+    pub unknown1: u8,
+    /// Meaning unknown. 0 or 1 and not tied to anything else in the room:
+    /// set in every room of citystreet_large and some of rm19's.
+    pub unknown2: u8,
+    /// 1 when `Room::bounds` follows
+    pub bounds_flag: u8,
+    /// Only present when `unknown1` is 0. 1 when `Room::secondary_bounds`
+    /// follows.
+    pub secondary_flag: Option<u8>,
+}
+
+impl RoomKind {
+    fn read(buf: &mut Input<'_>) -> Result<Self> {
+        let unknown1 = buf.read_u8().context("room unknown1")?;
+        let unknown2 = buf.read_u8().context("room unknown2")?;
+        let bounds_flag = buf.read_u8().context("room bounds flag")?;
+        let secondary_flag = if unknown1 == 0 {
+            Some(buf.read_u8().context("room secondary bounds flag")?)
+        } else {
+            None
+        };
+        Ok(Self { unknown1, unknown2, bounds_flag, secondary_flag })
+    }
+
+    /// True when the fourth byte, `secondary_flag`, is present
+    pub fn is_extended(&self) -> bool {
+        self.unknown1 == 0
+    }
+
+    /// True when `Room::bounds` is present
+    pub fn has_bounds(&self) -> bool {
+        self.bounds_flag == 1
+    }
+
+    /// True when `Room::secondary_bounds` is present
+    pub fn has_secondary_bounds(&self) -> bool {
+        self.secondary_flag == Some(1)
+    }
+
+    /// The flags as the MAP stores them, three or four bytes
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![self.unknown1, self.unknown2, self.bounds_flag];
+        bytes.extend(self.secondary_flag);
+        bytes
+    }
+}
+
+/// Six floats, taken to be the minimum corner then the maximum as in
+/// `TransformationWithAABB::aabb`
+fn read_aabb(buf: &mut Input<'_>) -> Result<Aabb> {
+    let min = buf.read_f32_xyz().context("minimum")?;
+    let max = buf.read_f32_xyz().context("maximum")?;
+    Ok(Aabb { min: [min.0, min.1, min.2], max: [max.0, max.1, max.2] })
+}
+
+#[derive(Clone, Debug, Serialize)]
 pub struct ShermanLevel {
     pub name: String,
     pub tm_with_aabb: Vec<TransformationWithAABB>,
@@ -1338,10 +1645,11 @@ pub struct ShermanLevel {
 }
 
 impl ShermanLevel {
-    fn read(buf: &mut Cursor<Vec<u8>>) -> Result<Self> {
+    fn read(buf: &mut Input<'_>) -> Result<Self> {
         let name = latin1_to_utf8(&buf.read_cstring().context("level name")?);
 
-        let n = buf.read_u32::<LE>().context("level TM + AABB count")?;
+        let n = buf.read_count(Count::Objects, 4 * 12 + 4 * 6)
+            .context("level TM + AABB count")?;
         let mut tm_with_aabb = Vec::with_capacity(n as usize);
         for i in 0..n {
             let tm = TransformationWithAABB::read(buf).with_context(|| {
@@ -1350,7 +1658,8 @@ impl ShermanLevel {
             tm_with_aabb.push(tm);
         }
 
-        let n = buf.read_u32::<LE>().context("unknown count")?;
+        let n = buf.read_count(Count::Objects, 4)
+            .context("unknown count")?;
         let mut unknown1 = Vec::with_capacity(n as usize);
         for i in 0..n {
             let value = buf.read_f32::<LE>().with_context(|| {
@@ -1365,14 +1674,14 @@ impl ShermanLevel {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct TransformationWithAABB {
     pub tm: TransformationMatrix,
     pub aabb: [f32; 6],
 }
 
 impl TransformationWithAABB {
-    fn read(buf: &mut Cursor<Vec<u8>>) -> Result<Self> {
+    fn read(buf: &mut Input<'_>) -> Result<Self> {
         let tm = TransformationMatrix::read(buf).context("TM + AABB")?;
         let mut aabb = [0f32; 6];
         for (i, side) in aabb.iter_mut().enumerate() {
@@ -1384,7 +1693,7 @@ impl TransformationWithAABB {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct LevelHeight {
     pub height: f32,
     pub unknown: f32,
@@ -1394,14 +1703,14 @@ pub struct LevelHeight {
 }
 
 impl LevelHeight {
-    fn read(buf: &mut Cursor<Vec<u8>>) -> Result<Self> {
+    fn read(buf: &mut Input<'_>) -> Result<Self> {
         let height = buf.read_f32::<LE>().context("level height")?;
         let unknown = buf.read_f32::<LE>().context("level height unknown")?;
         Ok(Self { height, unknown })
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct Transitions {
     pub section_id: u32,
     pub section_name: String,
@@ -1409,11 +1718,12 @@ pub struct Transitions {
 }
 
 impl Transitions {
-    fn read(buf: &mut Cursor<Vec<u8>>) -> Result<Self> {
+    fn read(buf: &mut Input<'_>) -> Result<Self> {
         let (section_id, section_name) = section_header(buf)
             .context("transitions")?;
 
-        let n = buf.read_u32::<LE>().context("transitions count")?;
+        let n = buf.read_count(Count::Objects, MIN_CSTRING + 4 * 6)
+            .context("transitions count")?;
         let mut transitions = Vec::with_capacity(n as usize);
         for i in 0..n {
             transitions.push(Transition::read(buf).with_context(|| {
@@ -1425,35 +1735,35 @@ impl Transitions {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct Transition {
     pub name: String,
     pub coords: TransitionCoords,
 }
 
 impl Transition {
-    fn read(buf: &mut Cursor<Vec<u8>>) -> Result<Self> {
+    fn read(buf: &mut Input<'_>) -> Result<Self> {
         let name = latin1_to_utf8(&buf.read_cstring().context("transition")?);
         let coords = TransitionCoords::read(buf)?;
         Ok(Self { name, coords })
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct TransitionCoords {
     pub p1: Vec3f,
     pub p2: Vec3f,
 }
 
 impl TransitionCoords {
-    fn read(buf: &mut Cursor<Vec<u8>>) -> Result<Self> {
+    fn read(buf: &mut Input<'_>) -> Result<Self> {
         let p1 = Vec3f::read(buf).context("transition coords P1")?;
         let p2 = Vec3f::read(buf).context("transition coords P2")?;
         Ok(Self { p1, p2 })
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct PlanningLevels {
     pub section_id: u32,
     pub section_name: String,
@@ -1461,10 +1771,11 @@ pub struct PlanningLevels {
 }
 
 impl PlanningLevels {
-    fn read(buf: &mut Cursor<Vec<u8>>) -> Result<Self> {
+    fn read(buf: &mut Input<'_>) -> Result<Self> {
         let (section_id, section_name) = section_header(buf)
             .context("planning levels")?;
-        let n = buf.read_u32::<LE>().context("planning levels count")?;
+        let n = buf.read_count(Count::Objects, 4 * 3)
+            .context("planning levels count")?;
         let mut levels = Vec::with_capacity(n as usize);
         for i in 0..n {
             levels.push(PlanningLevel::read(buf).with_context(|| {
@@ -1475,7 +1786,7 @@ impl PlanningLevels {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct PlanningLevel {
     pub level_number: f32,
     pub floor_height: f32,
@@ -1483,13 +1794,14 @@ pub struct PlanningLevel {
 }
 
 impl PlanningLevel {
-    fn read(buf: &mut Cursor<Vec<u8>>) -> Result<Self> {
+    fn read(buf: &mut Input<'_>) -> Result<Self> {
         let level_number = buf.read_f32::<LE>()
             .context("planning level number")?;
         let floor_height = buf.read_f32::<LE>()
             .context("planning level floor height")?;
 
-        let n = buf.read_u32::<LE>().context("planning level room count")?;
+        let n = buf.read_count(Count::Objects, MIN_CSTRING)
+            .context("planning level room count")?;
         let mut room_names = Vec::with_capacity(n as usize);
         for i in 0..n {
             let room_name = latin1_to_utf8(&buf.read_cstring().with_context(|| {
@@ -1505,14 +1817,14 @@ impl PlanningLevel {
 /// Read and parse a section header that precedes the section data. Discards the
 /// section size in bytes and name. The total size isn't used in our reader
 /// implementation and the section name is encoded in the return type.
-fn section_header(buf: &mut Cursor<Vec<u8>>) -> Result<(u32, String)> {
+fn section_header(buf: &mut Input<'_>) -> Result<(u32, String)> {
     let _section_size = buf.read_u32::<LE>()
         .context("failed to read section size")?;
     section_header_short(buf)
 }
 
 /// Read the id and return the non-Version name
-fn section_header_short(buf: &mut Cursor<Vec<u8>>) -> Result<(u32, String)> {
+fn section_header_short(buf: &mut Input<'_>) -> Result<(u32, String)> {
     let id = buf.read_u32::<LE>()
         .context("failed to read material id")?;
 
@@ -1528,21 +1840,53 @@ fn section_header_short(buf: &mut Cursor<Vec<u8>>) -> Result<(u32, String)> {
     Ok((id, latin1_to_utf8(&name)))
 }
 
+/// MAP bytes being read and the `Limits` their counts are checked against
+struct Input<'a> {
+    cursor: Cursor<&'a [u8]>,
+    limits: &'a Limits,
+}
+
+impl Input<'_> {
+    fn remaining(&self) -> usize {
+        let len = self.cursor.get_ref().len() as u64;
+        len.saturating_sub(self.cursor.position()) as usize
+    }
+}
+
+impl Read for Input<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.cursor.read(buf)
+    }
+}
+
+/// The fewest bytes a string takes: its length and null terminator
+const MIN_CSTRING: usize = 4 + 1;
+
+/// The fewest bytes `section_header` reads
+const MIN_SECTION_HEADER: usize = 4 + 4 + MIN_CSTRING;
+
 /// Read primitive data types that are common in the MAP format
 trait ReadMapBytes: ReadBytesExt {
+    /// Read a list length, checked against the `Limits` for `count` and
+    /// what's left of the input given items of at least `item_size` bytes
+    fn read_count(&mut self, count: Count, item_size: usize) -> Result<u32>;
     fn read_cstring(&mut self) -> Result<Vec<u8>>;
     fn read_bool(&mut self) -> Result<bool>;
     fn read_f32_xy(&mut self) -> Result<(f32, f32)>;
     fn read_f32_xyz(&mut self) -> Result<(f32, f32, f32)>;
 }
 
-impl<T> ReadMapBytes for Cursor<T>
-where
-    T: AsRef<[u8]>
-{
+impl ReadMapBytes for Input<'_> {
+    fn read_count(&mut self, count: Count, item_size: usize) -> Result<u32> {
+        let n = self.read_u32::<LE>().context("could not read count")?;
+        self.limits.check_count(count, n, item_size, self.remaining())?;
+        Ok(n)
+    }
+
     fn read_cstring(&mut self) -> Result<Vec<u8>> {
-        let len = self.read_u32::<LE>()
-            .context("could not read length")? as usize;
+        let len = self.read_u32::<LE>().context("could not read length")?;
+        self.limits.check_string(len, self.remaining())?;
+        let len = len as usize;
         anyhow::ensure!(len >= 1, "empty string");
         let mut buf = vec![0; len];
         self.read_exact(&mut buf)
@@ -1575,3 +1919,159 @@ where
 fn latin1_to_utf8(s: &[u8]) -> String {
     s.iter().map(|&c| c as char).collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn truncated_maps_are_errors() {
+        let bytes = std::fs::read("data/map/m00/citystreet_large.map")
+            .unwrap();
+        assert!(read_bytes(&bytes).is_ok());
+        let lens = (0..bytes.len()).step_by(17_777).chain([bytes.len() - 1]);
+        for len in lens {
+            assert!(read_bytes(&bytes[..len]).is_err(), "{len} bytes");
+        }
+    }
+
+    #[test]
+    fn counts_are_limited() {
+        let mut bytes = Vec::new();
+        let cstring = |bytes: &mut Vec<u8>, s: &[u8]| {
+            bytes.extend((s.len() as u32 + 1).to_le_bytes());
+            bytes.extend(s);
+            bytes.push(0);
+        };
+        cstring(&mut bytes, MAGIC);
+        // Timestamp, then the material list's size and id
+        bytes.extend([0; 4 * 3]);
+        cstring(&mut bytes, b"MaterialList");
+        bytes.extend(u32::MAX.to_le_bytes());
+
+        let error = read_bytes(&bytes).unwrap_err();
+        assert_eq!(format!("{:#}", error.root_cause()),
+            "4294967295 items exceed the limit of 65536");
+        let error = read_bytes_with_limits(&bytes, &Limits::none())
+            .unwrap_err();
+        assert!(format!("{:#}", error.root_cause())
+            .contains("but only 0 remain"));
+
+        // Strings are checked before they're allocated
+        let limits = Limits { max_string_len: 8, ..Default::default() };
+        let error = read_bytes_with_limits(&bytes, &limits).unwrap_err();
+        assert_eq!(format!("{:#}", error.root_cause()),
+            "string of 13 bytes exceeds the limit of 8");
+    }
+
+    #[test]
+    fn glass_penetration_type_is_latin1() {
+        let mut bytes = 12u32.to_le_bytes().to_vec();
+        bytes.extend(b"safetyGlass\0");
+        let limits = Limits::default();
+        let mut buf = Input { cursor: Cursor::new(&bytes), limits: &limits };
+        let glass = DynamicObjectKind::glass(&mut buf).unwrap();
+        assert!(matches!(glass, DynamicObjectKind::Glass {
+            penetration_type: PenetrationType::Other(value),
+        } if value == "safetyGlass"));
+    }
+
+    #[test]
+    fn shipped_dynamic_object_strings_are_all_known() {
+        let paths = [
+            "data/map/m00/citystreet_large.map",
+            "data/map/rm19/rm19.map",
+        ];
+        for path in paths {
+            let map = read(Path::new(path)).unwrap();
+            for object in &map.dynamic_objects.dynamic_objects {
+                let collision = |c: &CollisionType| {
+                    !matches!(c, CollisionType::Other(_))
+                };
+                let known = match &object.kind {
+                    DynamicObjectKind::Dynamic { common, .. } => {
+                        common_is_known(common)
+                    }
+                    DynamicObjectKind::Animation {
+                        common, animation_type, ..
+                    }
+                    | DynamicObjectKind::RepeatableTouchplate {
+                        common, animation_type, ..
+                    } => {
+                        common_is_known(common) && !matches!(animation_type,
+                            AnimationType::Other(_))
+                    }
+                    DynamicObjectKind::OneTimeTouchplate {
+                        collision_type_2d, collision_type_3d, ..
+                    } => {
+                        collision(collision_type_2d)
+                            && collision(collision_type_3d)
+                    }
+                    _ => continue,
+                };
+                assert!(known, "{path}: {object:?}");
+            }
+        }
+
+        // Every value keeps the MAP's spelling, whichever case "none" is in
+        for none in ["none", "NONE"] {
+            let value = CollisionType::from(none.to_string());
+            assert_eq!(value, CollisionType::None(none.to_string()));
+            assert_eq!(value.as_str(), none);
+            let value = DestructionAction::from(none.to_string());
+            assert_eq!(value.as_str(), none);
+        }
+        let other = PenetrationType::from("solidThinGlass".to_string());
+        assert_eq!(other.as_str(), "solidThinGlass");
+        let other = PenetrationType::from("SolidThinWood".to_string());
+        assert_eq!(other, PenetrationType::Other("SolidThinWood".to_string()));
+    }
+
+    /// True when none of `common`'s strings fell through to `Other`
+    fn common_is_known(common: &DynamicObjectKindCommon) -> bool {
+        !matches!(common.collision_type_2d, CollisionType::Other(_))
+            && !matches!(common.collision_type_3d, CollisionType::Other(_))
+            && !matches!(common.destruction_action, DestructionAction::Other(_))
+            && !matches!(common.destruction_category,
+                DestructionCategory::Other(_))
+            && !matches!(common.penetration_type, PenetrationType::Other(_))
+            && !matches!(common.destruction_category2,
+                DestructionCategory::Other(_))
+    }
+
+    #[test]
+    fn room_flags_decide_which_boxes_follow() {
+        let mut bytes = Vec::new();
+        bytes.extend(7u32.to_le_bytes());
+        bytes.extend(3u32.to_le_bytes());
+        bytes.extend(b"r1\0");
+        let flags = [0, 1, 1, 1];
+        bytes.extend(flags);
+        for x in [1.0f32, 2.0, 3.0, 4.0, 5.0, 6.0, -1.0, -2.0, -3.0, 0.0, 0.0,
+            0.0]
+        {
+            bytes.extend(x.to_le_bytes());
+        }
+        // No sherman levels or level heights, then unknown7
+        bytes.extend([0; 4 * 3]);
+
+        let limits = Limits::default();
+        let room = Room::read(&mut Input { cursor: Cursor::new(&bytes),
+            limits: &limits }).unwrap();
+        assert!(room.kind.is_extended());
+        assert_eq!(room.kind.to_bytes(), flags);
+        assert_eq!(room.bounds,
+            Some(Aabb { min: [1.0, 2.0, 3.0], max: [4.0, 5.0, 6.0] }));
+        assert_eq!(room.secondary_bounds,
+            Some(Aabb { min: [-1.0, -2.0, -3.0], max: [0.0; 3] }));
+
+        let map = read(Path::new("data/map/rm19/rm19.map")).unwrap();
+        for room in &map.rooms.rooms {
+            let kind = room.kind;
+            assert_eq!(kind.to_bytes().len(), 3 + kind.is_extended() as usize);
+            assert_eq!(room.bounds.is_some(), kind.has_bounds());
+            assert_eq!(room.secondary_bounds.is_some(),
+                kind.has_secondary_bounds());
+        }
+    }
+}
@@ -0,0 +1,41 @@
+use serde::Serialize;
+
+/// An axis-aligned box in map units
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub struct Aabb {
+    pub min: [f32; 3],
+    pub max: [f32; 3],
+}
+
+impl Aabb {
+    /// The smallest box holding every point, or `None` if there are none
+    pub fn from_points(points: impl IntoIterator<Item = [f32; 3]>)
+        -> Option<Self>
+    {
+        let mut points = points.into_iter();
+        let first = points.next()?;
+        let mut aabb = Self { min: first, max: first };
+        for point in points {
+            for (axis, &value) in point.iter().enumerate() {
+                aabb.min[axis] = aabb.min[axis].min(value);
+                aabb.max[axis] = aabb.max[axis].max(value);
+            }
+        }
+        Some(aabb)
+    }
+
+    /// True if `point` is inside or on the boundary
+    pub fn contains(&self, point: [f32; 3]) -> bool {
+        (0..3).all(|axis| {
+            self.min[axis] <= point[axis] && point[axis] <= self.max[axis]
+        })
+    }
+
+    /// True if the boxes share any volume or touch
+    pub fn intersects(&self, other: &Aabb) -> bool {
+        (0..3).all(|axis| {
+            self.min[axis] <= other.max[axis]
+                && other.min[axis] <= self.max[axis]
+        })
+    }
+}
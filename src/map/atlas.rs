@@ -0,0 +1,402 @@
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+use super::{Map, TextureAddressMode};
+use crate::assets::AssetIndex;
+use crate::rsb;
+
+/// UVs this far outside `0..=1` still count as inside, since exporters
+/// leave a little float noise on edges that are meant to be exactly 0 or 1.
+const UV_EPSILON: f32 = 1e-3;
+
+#[derive(Clone, Debug, Serialize)]
+pub struct AtlasOptions {
+    /// Largest page width and height, rounded down to a power of two. Pages
+    /// shrink to the smallest power of two that fits what's packed into them.
+    pub max_size: u32,
+    /// Texels of edge colour repeated around each texture so that filtering
+    /// and mipmapping don't bleed neighbours in
+    pub padding: u32,
+}
+
+impl Default for AtlasOptions {
+    fn default() -> Self {
+        Self { max_size: 2048, padding: 2 }
+    }
+}
+
+/// A texture to pack, already decoded to 8-bit RGBA
+#[derive(Clone, Debug)]
+pub struct AtlasImage {
+    /// Index into `Materials::materials`
+    pub material: usize,
+    pub width: u32,
+    pub height: u32,
+    pub rgba: Vec<u8>,
+}
+
+/// Material textures packed into power-of-two pages, and where each material
+/// ended up. Materials that couldn't be packed keep their own texture and are
+/// listed in `excluded`.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Atlas {
+    pub pages: Vec<AtlasPage>,
+    /// Keyed by material index
+    pub placements: BTreeMap<usize, Placement>,
+    /// Keyed by material index
+    pub excluded: BTreeMap<usize, Exclusion>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct AtlasPage {
+    pub width: u32,
+    pub height: u32,
+    /// 8-bit RGBA, row by row
+    #[serde(skip)]
+    pub rgba: Vec<u8>,
+}
+
+/// Where a material's texture is inside its page, in texels and without the
+/// padding
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct Placement {
+    pub page: usize,
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Why a material isn't in the atlas
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub enum Exclusion {
+    /// The texture repeats: some of its UVs are outside `0..=1` and its
+    /// `TextureAddressMode` is `Wrap`, which an atlas can't reproduce
+    Tiled,
+    /// Some UVs are outside `0..=1` with a non-wrapping address mode. Clamping
+    /// in an atlas would need padding as wide as the overhang.
+    OutOfRange(TextureAddressMode),
+    /// No RSB for the material's texture in the `AssetIndex`
+    Missing,
+    /// The RSB exists but couldn't be read
+    Unreadable(String),
+    /// Wider or taller than `AtlasOptions::max_size` with padding
+    TooLarge,
+    /// Zero texels wide or tall, so there are no edge texels to pad with
+    Empty,
+}
+
+impl Map {
+    /// Pack the textures of every material in this MAP into atlas pages.
+    /// Textures are found with `assets`. Materials no geometry uses are
+    /// skipped entirely.
+    pub fn build_atlas(&self, assets: &AssetIndex, options: &AtlasOptions)
+        -> Atlas
+    {
+        let mut excluded = BTreeMap::new();
+        let mut images = Vec::new();
+        for (material, in_range) in self.material_uv_ranges() {
+            let m = &self.materials.materials[material];
+            if !in_range {
+                let exclusion = match m.address_mode {
+                    TextureAddressMode::Wrap => Exclusion::Tiled,
+                    mode => Exclusion::OutOfRange(mode),
+                };
+                excluded.insert(material, exclusion);
+                continue;
+            }
+
+            let Some(path) = assets.find_texture(&m.filename) else {
+                excluded.insert(material, Exclusion::Missing);
+                continue;
+            };
+            match rsb::read(path) {
+                Ok(rsb) => images.push(AtlasImage {
+                    material,
+                    width: rsb.width,
+                    height: rsb.height,
+                    rgba: rsb.to_rgba8(),
+                }),
+                Err(e) => {
+                    excluded.insert(material, Exclusion::Unreadable(
+                        format!("{}: {e:#}", path.display())));
+                }
+            }
+        }
+
+        let mut atlas = Atlas::pack(images, options);
+        atlas.excluded.extend(excluded);
+        atlas
+    }
+
+    /// For every material used by geometry, whether all the UVs its faces
+    /// use are within `0..=1`
+    fn material_uv_ranges(&self) -> BTreeMap<usize, bool> {
+        let mut ranges = BTreeMap::new();
+        for data in self.geometries.objects.iter()
+            .flat_map(|object| &object.object_datas)
+        {
+            let material = data.mn as usize;
+            if material >= self.materials.materials.len() {
+                continue;
+            }
+
+            let uvs = &data.texture_vertices.uv_coords;
+            let inside = |x: f32| (-UV_EPSILON..=1.0 + UV_EPSILON).contains(&x);
+            let in_range = data.faces.texture_indices.iter()
+                .flat_map(|&(a, b, c)| [a, b, c])
+                .filter_map(|i| uvs.get(i as usize))
+                // V is stored negated, see `UvCoord`
+                .all(|uv| inside(uv.u) && inside(-uv.v));
+            *ranges.entry(material).or_insert(true) &= in_range;
+        }
+        ranges
+    }
+}
+
+impl Atlas {
+    /// Pack `images` into as few pages as a shelf packer manages, tallest
+    /// first. Images that can't fit in an empty page are `TooLarge` and
+    /// images with no texels are `Empty`.
+    pub fn pack(mut images: Vec<AtlasImage>, options: &AtlasOptions) -> Self {
+        let padding = options.padding;
+        // Pages round up to a power of two, so only a power of two can bound
+        // them
+        let max_size = match options.max_size {
+            0 => 0,
+            size => 1 << size.ilog2(),
+        };
+        let mut atlas = Self::default();
+        images.sort_by_key(|image| {
+            (std::cmp::Reverse(image.height), std::cmp::Reverse(image.width),
+                image.material)
+        });
+
+        let mut shelves: Vec<Shelves> = Vec::new();
+        for image in &images {
+            if image.width == 0 || image.height == 0 {
+                atlas.excluded.insert(image.material, Exclusion::Empty);
+                continue;
+            }
+            let width = image.width + 2 * padding;
+            let height = image.height + 2 * padding;
+            if width > max_size || height > max_size {
+                atlas.excluded.insert(image.material, Exclusion::TooLarge);
+                continue;
+            }
+
+            let (page, x, y) = shelves.iter_mut()
+                .enumerate()
+                .find_map(|(page, shelves)| {
+                    let (x, y) = shelves.insert(width, height)?;
+                    Some((page, x, y))
+                })
+                .unwrap_or_else(|| {
+                    let mut page = Shelves::new(max_size);
+                    let (x, y) = page.insert(width, height)
+                        .expect("image fits an empty page");
+                    shelves.push(page);
+                    (shelves.len() - 1, x, y)
+                });
+            atlas.placements.insert(image.material, Placement {
+                page,
+                x: x + padding,
+                y: y + padding,
+                width: image.width,
+                height: image.height,
+            });
+        }
+
+        atlas.pages = shelves.iter()
+            .map(|shelves| {
+                let width = shelves.used_width.next_power_of_two();
+                let height = shelves.used_height().next_power_of_two();
+                AtlasPage {
+                    width,
+                    height,
+                    rgba: vec![0; width as usize * height as usize * 4],
+                }
+            })
+            .collect();
+        for image in &images {
+            if let Some(placement) = atlas.placements.get(&image.material) {
+                let page = &mut atlas.pages[placement.page];
+                blit(page, placement, image, padding);
+            }
+        }
+        atlas
+    }
+
+    /// Rewrite the UVs of every `ObjectData` whose material was packed so that
+    /// they address the material's place in its page. Faces still name their
+    /// original material, so use `placements` to find a material's page.
+    pub fn apply(&self, map: &mut Map) {
+        for data in map.geometries.objects.iter_mut()
+            .flat_map(|object| &mut object.object_datas)
+        {
+            let placement = self.placements.get(&(data.mn as usize));
+            let Some(placement) = placement else {
+                continue;
+            };
+            let page = &self.pages[placement.page];
+            let (page_width, page_height) =
+                (page.width as f32, page.height as f32);
+            for uv in &mut data.texture_vertices.uv_coords {
+                let u = uv.u.clamp(0.0, 1.0);
+                let v = (-uv.v).clamp(0.0, 1.0);
+                uv.u = (placement.x as f32 + u * placement.width as f32)
+                    / page_width;
+                uv.v = -(placement.y as f32 + v * placement.height as f32)
+                    / page_height;
+            }
+        }
+    }
+}
+
+/// Rows of rectangles filled left to right, top to bottom
+struct Shelves {
+    size: u32,
+    /// `(y, height, next free x)` of each shelf
+    shelves: Vec<(u32, u32, u32)>,
+    used_width: u32,
+}
+
+impl Shelves {
+    fn new(size: u32) -> Self {
+        Self { size, shelves: Vec::new(), used_width: 0 }
+    }
+
+    fn used_height(&self) -> u32 {
+        self.shelves.last().map_or(0, |&(y, height, _)| y + height)
+    }
+
+    fn insert(&mut self, width: u32, height: u32) -> Option<(u32, u32)> {
+        let size = self.size;
+        let fits = |&&mut (_, shelf_height, x): &&mut (u32, u32, u32)| {
+            height <= shelf_height && x + width <= size
+        };
+        let (x, y) = match self.shelves.iter_mut().find(fits) {
+            Some(shelf) => {
+                shelf.2 += width;
+                (shelf.2 - width, shelf.0)
+            }
+            None => {
+                let y = self.used_height();
+                if y + height > size {
+                    return None;
+                }
+                self.shelves.push((y, height, width));
+                (0, y)
+            }
+        };
+        self.used_width = self.used_width.max(x + width);
+        Some((x, y))
+    }
+}
+
+/// Copy `image` into `page` at `placement`, repeating its edge texels
+/// `padding` texels outwards
+fn blit(page: &mut AtlasPage, placement: &Placement, image: &AtlasImage,
+    padding: u32)
+{
+    let (width, height) = (image.width as i64, image.height as i64);
+    let padding = padding as i64;
+    for y in -padding..height + padding {
+        let source_y = y.clamp(0, height - 1);
+        for x in -padding..width + padding {
+            let source_x = x.clamp(0, width - 1);
+            let source = ((source_y * width + source_x) * 4) as usize;
+            let target_x = placement.x as i64 + x;
+            let target_y = placement.y as i64 + y;
+            let target = (target_y * page.width as i64 + target_x) as usize * 4;
+            page.rgba[target..target + 4]
+                .copy_from_slice(&image.rgba[source..source + 4]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+
+    fn image(material: usize, width: u32, height: u32) -> AtlasImage {
+        AtlasImage {
+            material,
+            width,
+            height,
+            rgba: vec![material as u8; (width * height * 4) as usize],
+        }
+    }
+
+    #[test]
+    fn packing_spills_into_power_of_two_pages() {
+        let options = AtlasOptions { max_size: 256, padding: 2 };
+        let images = vec![image(0, 128, 128), image(1, 128, 128),
+            image(2, 64, 32), image(3, 300, 8), image(4, 0, 16)];
+        let atlas = Atlas::pack(images, &options);
+
+        assert_eq!(atlas.excluded.get(&3), Some(&Exclusion::TooLarge));
+        assert_eq!(atlas.excluded.get(&4), Some(&Exclusion::Empty));
+        assert_eq!(atlas.pages.len(), 2);
+        for page in &atlas.pages {
+            assert!(page.width.is_power_of_two() && page.width <= 256);
+            assert!(page.height.is_power_of_two() && page.height <= 256);
+        }
+
+        let a = atlas.placements[&0];
+        let c = atlas.placements[&2];
+        assert_eq!((a.page, a.x, a.y), (0, 2, 2));
+        assert_eq!((c.page, c.x, c.y), (0, 2 + 128 + 4, 2));
+        // Padding repeats the edge, so the texel left of a texture is its own
+        let page = &atlas.pages[0];
+        let left = (((c.y * page.width) + c.x - 1) * 4) as usize;
+        assert_eq!(page.rgba[left], 2);
+    }
+
+    #[test]
+    fn pages_stay_within_a_max_size_that_isnt_a_power_of_two() {
+        let options = AtlasOptions { max_size: 300, padding: 2 };
+        let images = vec![image(0, 200, 8), image(1, 260, 8)];
+        let atlas = Atlas::pack(images, &options);
+
+        assert_eq!(atlas.excluded.get(&1), Some(&Exclusion::TooLarge));
+        assert_eq!(atlas.pages.len(), 1);
+        assert_eq!(atlas.pages[0].width, 256);
+    }
+
+    #[test]
+    fn applied_uvs_address_the_placement() {
+        let mut map = crate::map::read(
+            Path::new("data/map/m00/citystreet_large.map")).unwrap();
+        let ranges = map.material_uv_ranges();
+        assert!(ranges.values().any(|&x| x) && ranges.values().any(|&x| !x));
+
+        let images = ranges.iter()
+            .filter(|(_, &in_range)| in_range)
+            .map(|(&material, _)| image(material, 64, 64))
+            .collect();
+        let atlas = Atlas::pack(images, &AtlasOptions::default());
+        atlas.apply(&mut map);
+
+        for data in map.geometries.objects.iter()
+            .flat_map(|object| &object.object_datas)
+        {
+            let Some(placement) = atlas.placements.get(&(data.mn as usize))
+            else {
+                continue;
+            };
+            let page = &atlas.pages[placement.page];
+            for uv in &data.texture_vertices.uv_coords {
+                let x = uv.u * page.width as f32;
+                let y = -uv.v * page.height as f32;
+                assert!(x >= placement.x as f32
+                    && x <= (placement.x + placement.width) as f32);
+                assert!(y >= placement.y as f32
+                    && y <= (placement.y + placement.height) as f32);
+            }
+        }
+    }
+}
@@ -0,0 +1,117 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use super::{Map, ReferenceField, ReferenceTarget};
+use crate::assets::AssetIndex;
+
+/// Every sound a MAP's dynamic objects play. See `Map::audio_manifest`.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct AudioManifest {
+    /// In dynamic object order, then field order within each object
+    pub sounds: Vec<SoundReference>,
+}
+
+/// One sound named by a dynamic object
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct SoundReference {
+    /// Index into `DynamicObjects::dynamic_objects` of the emitting object
+    pub object: usize,
+    /// The emitting object's `section_name`, which is unique where its
+    /// `name` isn't
+    pub object_name: String,
+    /// Which of the object's sounds this is, e.g. `Name2` for a door's
+    /// opening sound
+    pub field: ReferenceField,
+    /// The file name as the MAP spells it
+    pub sound: String,
+    /// Where the object sits in the map
+    pub position: [f32; 3],
+    /// The file in the game data, or `None` if it wasn't found
+    pub path: Option<PathBuf>,
+}
+
+impl AudioManifest {
+    /// Sounds with no file in the game data
+    pub fn missing(&self) -> impl Iterator<Item = &SoundReference> {
+        self.sounds.iter().filter(|s| s.path.is_none())
+    }
+
+    /// Each distinct file with how many times it's referenced, sorted by
+    /// path. Missing sounds aren't included.
+    pub fn files(&self) -> BTreeMap<&Path, usize> {
+        let mut files = BTreeMap::new();
+        for path in self.sounds.iter().filter_map(|s| s.path.as_deref()) {
+            *files.entry(path).or_default() += 1;
+        }
+        files
+    }
+}
+
+impl Map {
+    /// List every sound the dynamic objects name, where each is played
+    /// from, and the file each resolves to in `assets`. Names are matched
+    /// ignoring case and directories, as the game does.
+    pub fn audio_manifest(&self, assets: &AssetIndex) -> AudioManifest {
+        let objects = &self.dynamic_objects.dynamic_objects;
+        let sounds = self.references().references.into_iter()
+            .filter_map(|reference| {
+                let Some(ReferenceTarget::Sound(sound)) = reference.target
+                else {
+                    return None;
+                };
+                let object = &objects[reference.object];
+                let position = &object.tm.position;
+                Some(SoundReference {
+                    object: reference.object,
+                    object_name: object.section_name.clone(),
+                    field: reference.field,
+                    path: assets.find(&sound).map(Path::to_path_buf),
+                    sound,
+                    position: [position.x, position.y, position.z],
+                })
+            })
+            .collect();
+        AudioManifest { sounds }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn sounds_resolve_ignoring_case() {
+        let map = crate::map::read(Path::new("data/map/rm19/rm19.map"))
+            .unwrap();
+        let root = std::env::temp_dir()
+            .join(format!("rogue-audio-{}", std::process::id()));
+        fs::create_dir_all(root.join("sound")).unwrap();
+        let open = root.join("sound/e_DMETOP.WAV");
+        fs::write(&open, b"").unwrap();
+        let assets = AssetIndex::new(&root).unwrap();
+        let manifest = map.audio_manifest(&assets);
+        fs::remove_dir_all(&root).unwrap();
+
+        let door = manifest.sounds.iter()
+            .find(|s| s.object_name == "427_doorglass01"
+                && s.field == ReferenceField::Name2)
+            .unwrap();
+        assert_eq!(door.sound, "E_dmetop.wav");
+        assert_eq!(door.path.as_deref(), Some(open.as_path()));
+        let tm = &map.dynamic_objects.dynamic_objects[door.object].tm;
+        assert_eq!(door.position, [tm.position.x, tm.position.y,
+            tm.position.z]);
+
+        assert!(manifest.sounds.iter().all(|s| {
+            s.sound.to_ascii_lowercase().ends_with(".wav")
+        }));
+        let found = manifest.files();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[open.as_path()] + manifest.missing().count(),
+            manifest.sounds.len());
+    }
+}
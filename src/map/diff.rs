@@ -0,0 +1,544 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use super::{
+    AnimationType, DynamicObject, DynamicObjectKind, DynamicObjectKindCommon,
+    KindDynamicParams, Map, Material, Object, Portal, Room, Transition,
+    TransitionCoords, Vec3f, Vertex,
+};
+
+/// Structural differences between two `Map`s, section by section. Entries are
+/// matched by name. Names aren't unique in every section (e.g. two portals
+/// can share a name) so repeated names are matched in file order and reported
+/// as `name#2`, `name#3`, and so on.
+#[derive(Clone, Debug, Default)]
+pub struct MapDiff {
+    pub materials: SectionDiff,
+    pub objects: SectionDiff,
+    pub dynamic_objects: SectionDiff,
+    pub portals: SectionDiff,
+    pub rooms: SectionDiff,
+    pub transitions: SectionDiff,
+}
+
+impl MapDiff {
+    /// True when no section has any differences
+    pub fn is_empty(&self) -> bool {
+        self.sections().iter().all(|(_, section)| section.is_empty())
+    }
+
+    /// Every section with its display name, in MAP file order
+    pub fn sections(&self) -> [(&'static str, &SectionDiff); 6] {
+        [
+            ("materials", &self.materials),
+            ("objects", &self.objects),
+            ("dynamic objects", &self.dynamic_objects),
+            ("portals", &self.portals),
+            ("rooms", &self.rooms),
+            ("transitions", &self.transitions),
+        ]
+    }
+}
+
+impl fmt::Display for MapDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "no differences");
+        }
+        for (name, section) in self.sections() {
+            if section.is_empty() {
+                continue;
+            }
+            writeln!(f, "{name}:")?;
+            for added in &section.added {
+                writeln!(f, "  + {added}")?;
+            }
+            for removed in &section.removed {
+                writeln!(f, "  - {removed}")?;
+            }
+            for changed in &section.changed {
+                writeln!(f, "  ~ {}", changed.name)?;
+                for field in &changed.fields {
+                    writeln!(f, "      {field}")?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct SectionDiff {
+    /// Names only present in the second map
+    pub added: Vec<String>,
+    /// Names only present in the first map
+    pub removed: Vec<String>,
+    /// Names present in both maps but with different fields
+    pub changed: Vec<Changed>,
+}
+
+impl SectionDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+            && self.changed.is_empty()
+    }
+}
+
+/// An entry present in both maps with at least one differing field
+#[derive(Clone, Debug)]
+pub struct Changed {
+    pub name: String,
+    pub fields: Vec<FieldChange>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct FieldChange {
+    pub field: &'static str,
+    pub old: Value,
+    pub new: Value,
+}
+
+impl FieldChange {
+    /// `new - old` for numeric fields
+    pub fn delta(&self) -> Option<f64> {
+        match (&self.old, &self.new) {
+            (Value::Number(old), Value::Number(new)) => Some(new - old),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for FieldChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {} -> {}", self.field, self.old, self.new)?;
+        if let Some(delta) = self.delta() {
+            write!(f, " ({delta:+})")?;
+        }
+        Ok(())
+    }
+}
+
+/// A compared field value. Counts, indices and floats are all numbers so that
+/// a delta can be reported for them.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Number(f64),
+    Text(String),
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Number(x) => write!(f, "{x}"),
+            Self::Text(s) => write!(f, "'{s}'"),
+        }
+    }
+}
+
+/// Compare two maps section by section. See `MapDiff`.
+pub fn diff(old: &Map, new: &Map) -> MapDiff {
+    MapDiff {
+        materials: diff_section(
+            &old.materials.materials,
+            &new.materials.materials,
+            |x| &x.name,
+            material_fields,
+        ),
+        objects: diff_section(
+            &old.geometries.objects,
+            &new.geometries.objects,
+            |x| &x.name,
+            object_fields,
+        ),
+        dynamic_objects: diff_section(
+            &old.dynamic_objects.dynamic_objects,
+            &new.dynamic_objects.dynamic_objects,
+            |x| &x.name,
+            dynamic_object_fields,
+        ),
+        portals: diff_section(
+            &old.portals.portals,
+            &new.portals.portals,
+            |x| &x.name,
+            portal_fields,
+        ),
+        rooms: diff_section(
+            &old.rooms.rooms,
+            &new.rooms.rooms,
+            |x| &x.section_name,
+            room_fields,
+        ),
+        transitions: diff_section(
+            &old.transitions.transitions,
+            &new.transitions.transitions,
+            |x| &x.name,
+            transition_fields,
+        ),
+    }
+}
+
+type Fields = Vec<(&'static str, Value)>;
+
+fn diff_section<T>(
+    old: &[T],
+    new: &[T],
+    name: impl Fn(&T) -> &String,
+    fields: impl Fn(&T) -> Fields,
+) -> SectionDiff {
+    let old = keyed(old, &name);
+    let new = keyed(new, &name);
+    let lookup = new.iter()
+        .map(|(key, item)| (key.as_str(), *item))
+        .collect::<HashMap<_, _>>();
+
+    let mut section = SectionDiff::default();
+    for (key, old_item) in &old {
+        let Some(new_item) = lookup.get(key.as_str()) else {
+            section.removed.push(key.clone());
+            continue;
+        };
+        // Matched by name, since objects of different kinds have different
+        // fields
+        let old_fields = fields(old_item);
+        let new_fields = fields(new_item);
+        let value = |fields: &Fields, field: &str| {
+            fields.iter()
+                .find(|(f, _)| *f == field)
+                .map_or_else(|| text("absent"), |(_, value)| value.clone())
+        };
+        let names = old_fields.iter().chain(&new_fields)
+            .map(|(field, _)| *field)
+            .collect::<Vec<_>>();
+        let mut seen = HashSet::new();
+        let changes = names.into_iter()
+            .filter(|field| seen.insert(*field))
+            .map(|field| FieldChange {
+                field,
+                old: value(&old_fields, field),
+                new: value(&new_fields, field),
+            })
+            .filter(|change| change.old != change.new)
+            .collect::<Vec<_>>();
+        if !changes.is_empty() {
+            section.changed.push(Changed { name: key.clone(), fields: changes });
+        }
+    }
+
+    let old_keys = old.iter()
+        .map(|(key, _)| key.as_str())
+        .collect::<HashSet<_>>();
+    section.added = new.iter()
+        .filter(|(key, _)| !old_keys.contains(key.as_str()))
+        .map(|(key, _)| key.clone())
+        .collect();
+
+    section
+}
+
+/// Pair each item with a unique key built from its name
+fn keyed<'a, T>(
+    items: &'a [T],
+    name: &impl Fn(&T) -> &String,
+) -> Vec<(String, &'a T)> {
+    let mut seen = HashMap::new();
+    items.iter()
+        .map(|item| {
+            let name = name(item);
+            let count = seen.entry(name.as_str()).or_insert(0);
+            *count += 1;
+            let key = if *count == 1 {
+                name.clone()
+            } else {
+                format!("{name}#{count}")
+            };
+            (key, item)
+        })
+        .collect()
+}
+
+fn number(x: impl Into<f64>) -> Value {
+    Value::Number(x.into())
+}
+
+fn count(n: usize) -> Value {
+    Value::Number(n as f64)
+}
+
+fn text(s: &str) -> Value {
+    Value::Text(s.to_string())
+}
+
+fn material_fields(m: &Material) -> Fields {
+    vec![
+        ("filename", text(&m.filename)),
+        ("opacity", number(m.opacity)),
+        ("emissive strength", number(m.emissive_strength)),
+        ("address mode", text(&format!("{:?}", m.address_mode))),
+        ("ambient r", number(m.ambient.r)),
+        ("ambient g", number(m.ambient.g)),
+        ("ambient b", number(m.ambient.b)),
+        ("ambient a", number(m.ambient.a)),
+        ("diffuse r", number(m.diffuse.r)),
+        ("diffuse g", number(m.diffuse.g)),
+        ("diffuse b", number(m.diffuse.b)),
+        ("diffuse a", number(m.diffuse.a)),
+        ("specular r", number(m.specular.r)),
+        ("specular g", number(m.specular.g)),
+        ("specular b", number(m.specular.b)),
+        ("specular a", number(m.specular.a)),
+        ("specular level", number(m.specular_level)),
+        ("two sided", text(&m.two_sided.to_string())),
+    ]
+}
+
+fn object_fields(o: &Object) -> Fields {
+    let faces = o.object_datas.iter()
+        .map(|data| data.faces.normals.len())
+        .sum();
+    vec![
+        ("vertex count", count(o.vertices.len())),
+        ("object data count", count(o.object_datas.len())),
+        ("face count", count(faces)),
+        ("collision vertex count", count(o.collisions.vertices.len())),
+        ("collision face count", count(o.collisions.faces.len())),
+        ("tag count", count(o.tags.len())),
+    ]
+}
+
+fn dynamic_object_fields(d: &DynamicObject) -> Fields {
+    let kind = match d.kind {
+        DynamicObjectKind::Dynamic { .. } => "Dynamic",
+        DynamicObjectKind::Animation { .. } => "Animation",
+        DynamicObjectKind::RepeatableTouchplate { .. } => {
+            "RepeatableTouchplate"
+        }
+        DynamicObjectKind::Glass { .. } => "Glass",
+        DynamicObjectKind::OneTimeTouchplate { .. } => "OneTimeTouchplate",
+        DynamicObjectKind::Halo { .. } => "Halo",
+        DynamicObjectKind::StaticEffect => "StaticEffect",
+    };
+    let tm = &d.tm;
+    let mut fields = vec![
+        ("kind", text(kind)),
+        ("position x", number(tm.position.x)),
+        ("position y", number(tm.position.y)),
+        ("position z", number(tm.position.z)),
+        ("x-axis x", number(tm.x_axis.x)),
+        ("x-axis y", number(tm.x_axis.y)),
+        ("x-axis z", number(tm.x_axis.z)),
+        ("y-axis x", number(tm.y_axis.x)),
+        ("y-axis y", number(tm.y_axis.y)),
+        ("y-axis z", number(tm.y_axis.z)),
+        ("z-axis x", number(tm.z_axis.x)),
+        ("z-axis y", number(tm.z_axis.y)),
+        ("z-axis z", number(tm.z_axis.z)),
+    ];
+    match &d.kind {
+        DynamicObjectKind::Dynamic { common, params } => {
+            fields.extend(common_fields(common));
+            let (layout, names) = match params {
+                KindDynamicParams::Struct(structs) => {
+                    let names = structs.iter()
+                        .map(|s| s.name.clone())
+                        .collect::<Vec<_>>();
+                    ("struct", names)
+                }
+                KindDynamicParams::Flat { names, .. } => {
+                    ("flat", names.clone())
+                }
+            };
+            fields.push(("params", text(layout)));
+            fields.push(("param count", count(names.len())));
+            fields.push(("param names", text(&names.join(", "))));
+        }
+        DynamicObjectKind::Animation {
+            common, names, name3, name4, animation_type, direction, distance,
+            velocity, ..
+        } => {
+            fields.extend(common_fields(common));
+            fields.push(("name count", count(names.len())));
+            fields.push(("names", text(&names.join(", "))));
+            fields.push(("name3", text(name3)));
+            fields.push(("name4", text(name4)));
+            fields.extend(motion_fields(animation_type, direction, *distance,
+                *velocity));
+        }
+        DynamicObjectKind::RepeatableTouchplate {
+            common, attachments, names, name2, name3, animation_type,
+            direction, distance, velocity, ..
+        } => {
+            fields.extend(common_fields(common));
+            fields.push(("attachment count", count(attachments.len())));
+            fields.push(("attachments", text(&attachments.join(", "))));
+            fields.push(("name count", count(names.len())));
+            fields.push(("names", text(&names.join(", "))));
+            fields.push(("name2", text(name2)));
+            fields.push(("name3", text(name3)));
+            fields.extend(motion_fields(animation_type, direction, *distance,
+                *velocity));
+        }
+        DynamicObjectKind::Glass { penetration_type } => {
+            fields.push(("penetration type", text(penetration_type.as_str())));
+        }
+        DynamicObjectKind::OneTimeTouchplate {
+            collision_type_2d, collision_type_3d, attachments, ..
+        } => {
+            fields.push(("2D collision type",
+                text(collision_type_2d.as_str())));
+            fields.push(("3D collision type",
+                text(collision_type_3d.as_str())));
+            fields.push(("attachment count", count(attachments.len())));
+            fields.push(("attachments", text(&attachments.join(", "))));
+        }
+        DynamicObjectKind::Halo { halos } => {
+            fields.push(("halo count", count(halos.len())));
+        }
+        DynamicObjectKind::StaticEffect => {}
+    }
+    fields
+}
+
+fn common_fields(c: &DynamicObjectKindCommon) -> Fields {
+    vec![
+        ("kind name", text(&c.name)),
+        ("sound 1", text(&c.sounds[0])),
+        ("sound 2", text(&c.sounds[1])),
+        ("sound 3", text(&c.sounds[2])),
+        ("sound 4", text(&c.sounds[3])),
+        ("2D collision type", text(c.collision_type_2d.as_str())),
+        ("3D collision type", text(c.collision_type_3d.as_str())),
+        ("destruction action", text(c.destruction_action.as_str())),
+        ("destruction category", text(c.destruction_category.as_str())),
+        ("penetration type", text(c.penetration_type.as_str())),
+        ("name2", text(&c.name2)),
+        ("destruction category2", text(c.destruction_category2.as_str())),
+    ]
+}
+
+fn motion_fields(
+    animation_type: &AnimationType,
+    direction: &Vec3f,
+    distance: f32,
+    velocity: f32,
+) -> Fields {
+    vec![
+        ("animation type", text(animation_type.as_str())),
+        ("direction x", number(direction.x)),
+        ("direction y", number(direction.y)),
+        ("direction z", number(direction.z)),
+        ("distance", number(distance)),
+        ("velocity", number(velocity)),
+    ]
+}
+
+fn portal_fields(p: &Portal) -> Fields {
+    let (x, y, z) = centroid(&p.coordinates);
+    vec![
+        ("room", number(p.room)),
+        ("opposite room", number(p.opposite_room)),
+        ("vertex count", count(p.coordinates.len())),
+        ("centroid x", number(x)),
+        ("centroid y", number(y)),
+        ("centroid z", number(z)),
+    ]
+}
+
+fn room_fields(r: &Room) -> Fields {
+    vec![
+        ("unknown1", number(r.kind.unknown1)),
+        ("unknown2", number(r.kind.unknown2)),
+        ("bounds flag", number(r.kind.bounds_flag)),
+        ("secondary flag", match r.kind.secondary_flag {
+            Some(flag) => number(flag),
+            None => text("absent"),
+        }),
+        ("sherman level count", count(r.sherman_levels.len())),
+        ("level height count", count(r.level_heights.len())),
+    ]
+}
+
+fn transition_fields(t: &Transition) -> Fields {
+    let TransitionCoords { p1, p2 } = &t.coords;
+    vec![
+        ("p1 x", number(p1.x)),
+        ("p1 y", number(p1.y)),
+        ("p1 z", number(p1.z)),
+        ("p2 x", number(p2.x)),
+        ("p2 y", number(p2.y)),
+        ("p2 z", number(p2.z)),
+    ]
+}
+
+fn centroid(vertices: &[Vertex]) -> (f64, f64, f64) {
+    if vertices.is_empty() {
+        return (0.0, 0.0, 0.0);
+    }
+    let n = vertices.len() as f64;
+    let (x, y, z) = vertices.iter().fold((0.0, 0.0, 0.0), |(x, y, z), v| {
+        (x + v.x as f64, y + v.y as f64, z + v.z as f64)
+    });
+    (x / n, y / n, z / n)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+    use crate::map;
+
+    #[test]
+    fn identical_maps_have_no_differences() {
+        let path = Path::new("data/map/m00/citystreet_large.map");
+        let map = map::read(path).unwrap();
+        assert!(diff(&map, &map).is_empty());
+    }
+
+    #[test]
+    fn edits_are_reported_per_section() {
+        let path = Path::new("data/map/m00/citystreet_large.map");
+        let old = map::read(path).unwrap();
+        let mut new = old.clone();
+
+        new.materials.materials.retain(|m| m.name != "woodfloor");
+        new.transitions.transitions[0].coords.p1.z += 10.0;
+        let mut portal = new.portals.portals[2].clone();
+        portal.name = "p_new".into();
+        new.portals.portals.push(portal);
+
+        let d = diff(&old, &new);
+        assert_eq!(d.materials.removed, vec!["woodfloor".to_string()]);
+        assert_eq!(d.portals.added, vec!["p_new".to_string()]);
+
+        assert_eq!(d.transitions.changed.len(), 1);
+        let change = &d.transitions.changed[0];
+        assert_eq!(change.name, old.transitions.transitions[0].name);
+        assert_eq!(change.fields.len(), 1);
+        assert_eq!(change.fields[0].field, "p1 z");
+        assert_eq!(change.fields[0].delta(), Some(10.0));
+    }
+
+    #[test]
+    fn door_settings_are_compared() {
+        let path = Path::new("data/map/rm19/rm19.map");
+        let old = map::read(path).unwrap();
+        let mut new = old.clone();
+        let door = &mut new.dynamic_objects.dynamic_objects[45];
+        let DynamicObjectKind::RepeatableTouchplate {
+            attachments, direction, ..
+        } = &mut door.kind else {
+            panic!("{} isn't a door", door.name);
+        };
+        attachments.pop();
+        direction.x += 1.0;
+
+        let d = diff(&old, &new);
+        assert_eq!(d.dynamic_objects.changed.len(), 1);
+        let change = &d.dynamic_objects.changed[0];
+        assert_eq!(change.name, "427_doorglass01");
+        let fields = change.fields.iter()
+            .map(|f| f.field)
+            .collect::<Vec<_>>();
+        assert_eq!(fields, ["attachment count", "attachments", "direction x"]);
+    }
+}
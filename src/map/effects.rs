@@ -0,0 +1,97 @@
+use serde::Serialize;
+
+use super::{DynamicObjectKind, Map};
+use crate::vec3::length;
+
+/// Something a renderer draws as sprites or particles rather than a mesh
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Emitter {
+    /// Index into `DynamicObjects::dynamic_objects`
+    pub object: usize,
+    /// The halo sprite's name, or the effect's `section_name`
+    pub name: String,
+    /// Map-space centre
+    pub position: [f32; 3],
+    pub kind: EmitterKind,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub enum EmitterKind {
+    /// A glow billboard `size` map units across, tinted by `color` as RGBA
+    Halo { size: f32, color: [f32; 4] },
+
+    /// A `StaticEffect` that rises along `direction`, the unit Y axis of its
+    /// transform. Which effect it is isn't known to be stored anywhere;
+    /// `DynamicObject::name` may say.
+    Effect { direction: [f32; 3] },
+}
+
+impl Map {
+    /// Every halo sprite and static effect, in dynamic object order
+    pub fn emitters(&self) -> Vec<Emitter> {
+        let mut emitters = Vec::new();
+        let objects = &self.dynamic_objects.dynamic_objects;
+        for (object, dynamic) in objects.iter().enumerate() {
+            match &dynamic.kind {
+                DynamicObjectKind::Halo { halos } => {
+                    emitters.extend(halos.iter().map(|halo| {
+                        let p = &halo.position;
+                        let c = &halo.color;
+                        Emitter {
+                            object,
+                            name: halo.name.clone(),
+                            position: [p.x, p.y, p.z],
+                            kind: EmitterKind::Halo {
+                                size: halo.size,
+                                color: [c.r, c.g, c.b, c.a],
+                            },
+                        }
+                    }));
+                }
+                DynamicObjectKind::StaticEffect => {
+                    let tm = &dynamic.tm;
+                    let p = &tm.position;
+                    let y = [tm.y_axis.x, tm.y_axis.y, tm.y_axis.z];
+                    let len = length(y);
+                    let direction = if len > 0.0 {
+                        y.map(|x| x / len)
+                    } else {
+                        [0.0, 1.0, 0.0]
+                    };
+                    emitters.push(Emitter {
+                        object,
+                        name: dynamic.section_name.clone(),
+                        position: [p.x, p.y, p.z],
+                        kind: EmitterKind::Effect { direction },
+                    });
+                }
+                _ => {}
+            }
+        }
+        emitters
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+
+    #[test]
+    fn halos_are_tinted_sprites() {
+        let map = crate::map::read(Path::new("data/map/rm19/rm19.map"))
+            .unwrap();
+        let emitters = map.emitters();
+        assert_eq!(emitters.len(), 21);
+        let first = &emitters[0];
+        assert_eq!(first.name, "500_haloaoutside01");
+        assert_eq!(first.position, [21387.0, 33667.0, 43417.0]);
+        let EmitterKind::Halo { size, color } = first.kind else {
+            panic!("{first:?}");
+        };
+        assert_eq!(size, 100.0);
+        let bytes = color.map(|c| (c * 255.0).round() as u8);
+        assert_eq!(bytes, [244, 219, 151, 255]);
+    }
+}
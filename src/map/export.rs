@@ -0,0 +1,427 @@
+use std::fmt::Write;
+
+use serde_json::json;
+
+use super::{Map, Material, Object, ObjectData, UvCoord};
+
+/// Wavefront OBJ export of every geometry object. Materials are referenced
+/// with `usemtl` by the names `to_mtl` writes, so write both files next to
+/// each other and pass the MTL's filename as `mtl_filename`.
+///
+/// OBJ's texture origin is bottom-left, so V is flipped from `top_left`.
+/// Faces indexing past their object's vertices or texture vertices are left
+/// out; `Map::validate` reports them.
+pub fn to_obj(map: &Map, mtl_filename: &str) -> String {
+    let mut obj = String::new();
+    writeln!(obj, "mtllib {mtl_filename}").unwrap();
+
+    // OBJ indices are 1-based and global to the file
+    let mut vertex_offset = 1;
+    let mut texture_offset = 1;
+    for object in &map.geometries.objects {
+        writeln!(obj, "o {}", object.name).unwrap();
+        for v in &object.vertices {
+            writeln!(obj, "v {} {} {}", v.x, v.y, v.z).unwrap();
+        }
+
+        for data in &object.object_datas {
+            let tv = &data.texture_vertices;
+            for uv in &tv.uv_coords {
+                let [u, v] = top_left(uv);
+                writeln!(obj, "vt {} {}", u, 1.0 - v).unwrap();
+            }
+            for n in &tv.normals {
+                writeln!(obj, "vn {} {} {}", n.x, n.y, n.z).unwrap();
+            }
+
+            match material(map, data) {
+                Some((index, material)) => {
+                    writeln!(obj, "usemtl {}", material_name(index, material))
+                        .unwrap();
+                }
+                None => writeln!(obj, "usemtl {NO_MATERIAL}").unwrap(),
+            }
+
+            for [(v1, t1), (v2, t2), (v3, t3)] in triangles(object, data) {
+                let v = |i: usize| i + vertex_offset;
+                let t = |i: usize| i + texture_offset;
+                writeln!(obj, "f {}/{}/{} {}/{}/{} {}/{}/{}",
+                    v(v1), t(t1), t(t1),
+                    v(v2), t(t2), t(t2),
+                    v(v3), t(t3), t(t3)).unwrap();
+            }
+
+            texture_offset += tv.uv_coords.len();
+        }
+
+        vertex_offset += object.vertices.len();
+    }
+
+    obj
+}
+
+/// The `usemtl` name of faces whose material index is out of range. Real
+/// names start with their index, so it can't clash.
+const NO_MATERIAL: &str = "none";
+
+/// Wavefront MTL companion to `to_obj`. A plain grey `none` material is added
+/// when some faces have no material.
+pub fn to_mtl(map: &Map) -> String {
+    let mut mtl = String::new();
+    for (index, m) in map.materials.materials.iter().enumerate() {
+        writeln!(mtl, "newmtl {}", material_name(index, m)).unwrap();
+        writeln!(mtl, "Ka {} {} {}", m.ambient.r, m.ambient.g, m.ambient.b)
+            .unwrap();
+        writeln!(mtl, "Kd {} {} {}", m.diffuse.r, m.diffuse.g, m.diffuse.b)
+            .unwrap();
+        writeln!(mtl, "Ks {} {} {}", m.specular.r, m.specular.g, m.specular.b)
+            .unwrap();
+        writeln!(mtl, "d {}", m.opacity).unwrap();
+        writeln!(mtl, "map_Kd {}", m.filename).unwrap();
+        writeln!(mtl).unwrap();
+    }
+
+    let unmatched = map.geometries.objects.iter()
+        .flat_map(|object| &object.object_datas)
+        .any(|data| material(map, data).is_none());
+    if unmatched {
+        writeln!(mtl, "newmtl {NO_MATERIAL}").unwrap();
+        writeln!(mtl, "Kd 0.5 0.5 0.5").unwrap();
+        writeln!(mtl).unwrap();
+    }
+    mtl
+}
+
+/// Self-contained glTF 2.0 export with the geometry in an embedded base64
+/// buffer. Each geometry object is a mesh and each of its `ObjectData` is a
+/// primitive. The source textures are BMP/TGA (or RSB) which glTF can't
+/// reference, so each material keeps its texture filename in `extras`.
+///
+/// Texture coordinates are `top_left`, glTF's own convention. Faces are
+/// left out as in `to_obj`.
+pub fn to_gltf(map: &Map) -> serde_json::Value {
+    let mut gltf = Gltf::default();
+
+    let materials = map.materials.materials.iter()
+        .enumerate()
+        .map(|(index, m)| json!({
+            "name": material_name(index, m),
+            "pbrMetallicRoughness": {
+                "baseColorFactor": [
+                    m.diffuse.r, m.diffuse.g, m.diffuse.b, m.opacity,
+                ],
+                "metallicFactor": 0.0,
+            },
+            "alphaMode": if m.opacity < 1.0 { "BLEND" } else { "OPAQUE" },
+            "doubleSided": m.two_sided,
+            "extras": { "filename": m.filename },
+        }))
+        .collect::<Vec<_>>();
+
+    let mut meshes = Vec::new();
+    let mut nodes = Vec::new();
+    for object in &map.geometries.objects {
+        let primitives = object.object_datas.iter()
+            .filter(|data| triangles(object, data).next().is_some())
+            .map(|data| gltf.primitive(map, object, data))
+            .collect::<Vec<_>>();
+        if primitives.is_empty() {
+            continue;
+        }
+        nodes.push(json!({ "name": object.name, "mesh": meshes.len() }));
+        meshes.push(json!({ "name": object.name, "primitives": primitives }));
+    }
+
+    let uri = format!("data:application/octet-stream;base64,{}",
+        base64(&gltf.buffer));
+    json!({
+        "asset": { "version": "2.0", "generator": "rogue-reborn" },
+        "scene": 0,
+        "scenes": [{ "nodes": (0..nodes.len()).collect::<Vec<_>>() }],
+        "nodes": nodes,
+        "meshes": meshes,
+        "materials": materials,
+        "accessors": gltf.accessors,
+        "bufferViews": gltf.buffer_views,
+        "buffers": [{ "byteLength": gltf.buffer.len(), "uri": uri }],
+    })
+}
+
+/// Buffer, view and accessor bookkeeping for `to_gltf`
+#[derive(Default)]
+struct Gltf {
+    buffer: Vec<u8>,
+    buffer_views: Vec<serde_json::Value>,
+    accessors: Vec<serde_json::Value>,
+}
+
+impl Gltf {
+    /// glTF has a single index per vertex but MAP faces index positions and
+    /// texture vertices separately, so primitives are written unindexed.
+    fn primitive(
+        &mut self,
+        map: &Map,
+        object: &Object,
+        data: &ObjectData,
+    ) -> serde_json::Value {
+        let tv = &data.texture_vertices;
+        let mut positions = Vec::new();
+        let mut normals = Vec::new();
+        let mut uvs = Vec::new();
+        for triangle in triangles(object, data) {
+            for (v, t) in triangle {
+                let p = &object.vertices[v];
+                positions.push([p.x, p.y, p.z]);
+                let n = &tv.normals[t];
+                normals.push([n.x, n.y, n.z]);
+                uvs.push(top_left(&tv.uv_coords[t]));
+            }
+        }
+
+        let mut min = [f32::MAX; 3];
+        let mut max = [f32::MIN; 3];
+        for p in &positions {
+            for axis in 0..3 {
+                min[axis] = min[axis].min(p[axis]);
+                max[axis] = max[axis].max(p[axis]);
+            }
+        }
+
+        let position = self.accessor(&positions, "VEC3",
+            json!({ "min": min, "max": max }));
+        let normal = self.accessor(&normals, "VEC3", json!({}));
+        let texcoord = self.accessor(&uvs, "VEC2", json!({}));
+
+        let mut primitive = json!({
+            "attributes": {
+                "POSITION": position,
+                "NORMAL": normal,
+                "TEXCOORD_0": texcoord,
+            },
+        });
+        if let Some((index, _)) = material(map, data) {
+            primitive["material"] = json!(index);
+        }
+        primitive
+    }
+
+    /// Append float vectors to the buffer and return the new accessor index
+    fn accessor<const N: usize>(
+        &mut self,
+        values: &[[f32; N]],
+        kind: &str,
+        extra: serde_json::Value,
+    ) -> usize {
+        let offset = self.buffer.len();
+        for value in values {
+            for x in value {
+                self.buffer.extend(x.to_le_bytes());
+            }
+        }
+
+        let view = self.buffer_views.len();
+        self.buffer_views.push(json!({
+            "buffer": 0,
+            "byteOffset": offset,
+            "byteLength": self.buffer.len() - offset,
+        }));
+
+        let mut accessor = json!({
+            "bufferView": view,
+            "componentType": 5126, // FLOAT
+            "count": values.len(),
+            "type": kind,
+        });
+        if let (Some(accessor), Some(extra)) =
+            (accessor.as_object_mut(), extra.as_object())
+        {
+            accessor.extend(extra.clone());
+        }
+        self.accessors.push(accessor);
+        self.accessors.len() - 1
+    }
+}
+
+/// The (vertex, texture vertex) indices of each corner of `data`'s faces,
+/// leaving out faces with an index past the end of its list
+fn triangles<'a>(object: &'a Object, data: &'a ObjectData)
+    -> impl Iterator<Item = [(usize, usize); 3]> + 'a
+{
+    let tv = &data.texture_vertices;
+    let textures = tv.uv_coords.len().min(tv.normals.len());
+    let faces = &data.faces;
+    faces.face_indices.iter().zip(&faces.texture_indices)
+        .map(|(&(v1, v2, v3), &(t1, t2, t3))| {
+            [(v1, t1), (v2, t2), (v3, t3)]
+                .map(|(v, t)| (usize::from(v), usize::from(t)))
+        })
+        .filter(move |corners| {
+            corners.iter().all(|&(v, t)| {
+                v < object.vertices.len() && t < textures
+            })
+        })
+}
+
+/// The MAP stores V negated from a top-left origin, so this is (u, -v). The
+/// OBJ and glTF exports both start from it so they sample the same texels.
+fn top_left(uv: &UvCoord) -> [f32; 2] {
+    [uv.u, -uv.v]
+}
+
+/// The material used by `data`, if any, with its index
+fn material<'a>(map: &'a Map, data: &ObjectData)
+    -> Option<(usize, &'a Material)>
+{
+    let index = data.mn as usize;
+    map.materials.materials.get(index).map(|m| (index, m))
+}
+
+/// Material names aren't unique and often have spaces, which OBJ and MTL
+/// can't handle, so prefix the index and replace whitespace.
+fn material_name(index: usize, material: &Material) -> String {
+    let name = material.name.split_whitespace().collect::<Vec<_>>().join("_");
+    format!("{index}_{name}")
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8] =
+        b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let b = [
+            chunk[0],
+            chunk.get(1).copied().unwrap_or(0),
+            chunk.get(2).copied().unwrap_or(0),
+        ];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+
+    fn rm19() -> Map {
+        crate::map::read(Path::new("data/map/rm19/rm19.map")).unwrap()
+    }
+
+    /// The (object, data) of the first `ObjectData` with faces
+    fn first_faces(map: &Map) -> (usize, usize) {
+        map.geometries.objects.iter().enumerate()
+            .find_map(|(o, object)| {
+                let d = object.object_datas.iter()
+                    .position(|d| !d.faces.face_indices.is_empty())?;
+                Some((o, d))
+            })
+            .unwrap()
+    }
+
+    fn lines<'a>(obj: &'a str, prefix: &str) -> Vec<&'a str> {
+        obj.lines().filter_map(|line| line.strip_prefix(prefix)).collect()
+    }
+
+    #[test]
+    fn obj_faces_index_written_vertices_and_flip_v() {
+        let map = rm19();
+        let obj = to_obj(&map, "rm19.mtl");
+        let (v, vt) = (lines(&obj, "v ").len(), lines(&obj, "vt ").len());
+        let faces = lines(&obj, "f ");
+        let expected: usize = map.geometries.objects.iter()
+            .flat_map(|o| &o.object_datas)
+            .map(|d| d.faces.face_indices.len())
+            .sum();
+        assert_eq!(faces.len(), expected);
+        for face in faces {
+            for corner in face.split(' ') {
+                let i: Vec<usize> = corner.split('/')
+                    .map(|x| x.parse().unwrap())
+                    .collect();
+                assert!((1..=v).contains(&i[0]), "{face}");
+                assert!((1..=vt).contains(&i[1]), "{face}");
+            }
+        }
+
+        let uv = &map.geometries.objects[0].object_datas[0]
+            .texture_vertices.uv_coords[0];
+        assert_eq!(lines(&obj, "vt ")[0], format!("{} {}", uv.u, 1.0 + uv.v));
+        assert!(to_mtl(&map).starts_with("newmtl 0_"));
+    }
+
+    #[test]
+    fn faces_without_a_material_use_a_defined_one() {
+        let mut map = rm19();
+        map.geometries.objects[0].object_datas[0].mn = 9999;
+        let obj = to_obj(&map, "rm19.mtl");
+        let mtl = to_mtl(&map);
+        for name in lines(&obj, "usemtl ") {
+            assert!(mtl.lines().any(|line| line == format!("newmtl {name}")),
+                "{name}");
+        }
+        assert!(lines(&obj, "usemtl ").contains(&"none"));
+    }
+
+    #[test]
+    fn gltf_corners_follow_the_faces_and_negate_v() {
+        let map = rm19();
+        let (o, d) = first_faces(&map);
+        let (object, data) = (&map.geometries.objects[o],
+            &map.geometries.objects[o].object_datas[d]);
+        let mut gltf = Gltf::default();
+        let primitive = gltf.primitive(&map, object, data);
+        let count = 3 * data.faces.face_indices.len();
+        let texcoord = &gltf.accessors[
+            primitive["attributes"]["TEXCOORD_0"].as_u64().unwrap() as usize];
+        assert_eq!(texcoord["count"], count);
+
+        let offset = gltf.buffer_views[texcoord["bufferView"].as_u64()
+            .unwrap() as usize]["byteOffset"].as_u64().unwrap() as usize;
+        let float = |i: usize| {
+            let at = offset + 4 * i;
+            f32::from_le_bytes(gltf.buffer[at..at + 4].try_into().unwrap())
+        };
+        let t = data.faces.texture_indices[0].0 as usize;
+        let uv = &data.texture_vertices.uv_coords[t];
+        assert_eq!([float(0), float(1)], [uv.u, -uv.v]);
+
+        let json = to_gltf(&map);
+        assert!(!json["meshes"].as_array().unwrap().is_empty());
+    }
+
+    #[test]
+    fn out_of_range_faces_are_left_out() {
+        let mut map = rm19();
+        let (o, d) = first_faces(&map);
+        let before = lines(&to_obj(&map, "rm19.mtl"), "f ").len();
+        let data = &mut map.geometries.objects[o].object_datas[d];
+        data.faces.face_indices[0].1 = u16::MAX;
+        let faces = data.faces.face_indices.len();
+
+        assert_eq!(lines(&to_obj(&map, "rm19.mtl"), "f ").len(), before - 1);
+        let mut gltf = Gltf::default();
+        let object = &map.geometries.objects[o];
+        let primitive = gltf.primitive(&map, object, &object.object_datas[d]);
+        let position = primitive["attributes"]["POSITION"].as_u64().unwrap();
+        assert_eq!(gltf.accessors[position as usize]["count"],
+            3 * (faces - 1));
+        to_gltf(&map);
+    }
+
+    #[test]
+    fn base64_pads_partial_chunks() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(b"foob"), "Zm9vYg==");
+    }
+}
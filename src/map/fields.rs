@@ -0,0 +1,487 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use serde::Serialize;
+
+use super::{DynamicObjectKind, KindDynamicParams, Map};
+use crate::vec3;
+
+/// How many of a field's most common values a `Distribution` keeps
+const COMMON: usize = 8;
+
+/// How many equal-width bins a `Distribution` histogram has
+const BINS: usize = 10;
+
+/// Fewest records two fields must share to be correlated
+const MIN_SHARED: usize = 3;
+
+/// Numeric field values gathered across many maps, for reverse engineering
+/// what the fields mean. Values are gathered in records, e.g. one room, and
+/// fields are named by what holds them, like "room.unknown7".
+#[derive(Clone, Debug, Default)]
+pub struct FieldSamples {
+    /// Where each record came from, e.g. "rm19.map 105_dyncontrolpanel01"
+    examples: Vec<String>,
+    /// Each field's values, with the index into `examples` of their record
+    fields: BTreeMap<String, Vec<(usize, f32)>>,
+}
+
+/// One field's values across every record
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Distribution {
+    pub field: String,
+    pub count: usize,
+    pub min: f32,
+    pub max: f32,
+    /// How many different values the field took
+    pub distinct: usize,
+    /// The most common values, most common first
+    pub common: Vec<ValueCount>,
+    /// Counts in equal-width bins from `min` to `max`. Empty when the field
+    /// took a single value or any value isn't finite.
+    pub histogram: Vec<Bin>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ValueCount {
+    pub value: f32,
+    pub count: usize,
+    /// Where the value was first seen, e.g. "rm19.map 105_dyncontrolpanel01"
+    pub example: String,
+}
+
+/// Values from `start` up to `end`, or up to and including it for the last
+/// bin
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Bin {
+    pub start: f32,
+    pub end: f32,
+    pub count: usize,
+}
+
+/// How closely two fields of the same records move together
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Correlation {
+    pub a: String,
+    pub b: String,
+    /// Pearson's coefficient, from -1 to 1
+    pub r: f32,
+    /// How many records hold both fields
+    pub count: usize,
+}
+
+impl FieldSamples {
+    /// Record the `fields` of one thing, found at `example`
+    pub fn record(&mut self, example: &str, fields: &[(&str, f32)]) {
+        let record = self.examples.len();
+        self.examples.push(example.to_string());
+        for (field, value) in fields {
+            self.fields.entry(field.to_string()).or_default()
+                .push((record, *value));
+        }
+    }
+
+    /// Record the `KindDynamicParams` of every dynamic prop in `map`.
+    /// `source` names the map in examples.
+    pub fn add_params(&mut self, source: &str, map: &Map) {
+        for dynamic in &map.dynamic_objects.dynamic_objects {
+            let DynamicObjectKind::Dynamic { params, .. } = &dynamic.kind
+            else {
+                continue;
+            };
+            let example = format!("{source} {}", dynamic.section_name);
+            match params {
+                KindDynamicParams::Struct(params) => {
+                    for p in params {
+                        let (pos, dir) = (&p.position, &p.direction);
+                        let origin = &dynamic.tm.position;
+                        let length = vec3::length([dir.x, dir.y, dir.z]);
+                        self.record(&example, &[
+                            ("params.position.x", pos.x),
+                            ("params.position.y", pos.y),
+                            ("params.position.z", pos.z),
+                            ("params.offset.x", pos.x - origin.x),
+                            ("params.offset.y", pos.y - origin.y),
+                            ("params.offset.z", pos.z - origin.z),
+                            ("params.direction.x", dir.x),
+                            ("params.direction.y", dir.y),
+                            ("params.direction.z", dir.z),
+                            ("params.direction.length", length),
+                            ("params.unknown1.0", p.unknown1[0]),
+                            ("params.unknown1.1", p.unknown1[1]),
+                            ("params.unknown1.2", p.unknown1[2]),
+                            ("params.unknown2.0", p.unknown2[0] as f32),
+                            ("params.unknown2.1", p.unknown2[1] as f32),
+                        ]);
+                    }
+                }
+                KindDynamicParams::Flat { names, unknown } => {
+                    self.record(&example, &[
+                        ("flat.names", names.len() as f32),
+                        ("flat.unknown.0", unknown[0]),
+                        ("flat.unknown.1", unknown[1]),
+                        ("flat.unknown.2", unknown[2]),
+                        ("flat.unknown.3", unknown[3]),
+                    ]);
+                }
+            }
+        }
+    }
+
+    /// Record every field of `map` still named `unknown`, alongside known
+    /// fields of the same things, such as the room box flags, to correlate
+    /// them with. Optional fields are recorded when
+    /// present, and whether they are as 0 or 1. The `KindDynamicParams`
+    /// unknowns are recorded as `add_params` does.
+    pub fn add_unknowns(&mut self, source: &str, map: &Map) {
+        for room in &map.rooms.rooms {
+            let example = format!("{source} {}", room.section_name);
+            let kind = &room.kind;
+            let mut fields = vec![
+                ("room.unknown1", kind.unknown1 as f32),
+                ("room.unknown2", kind.unknown2 as f32),
+                ("room.bounds_flag", kind.bounds_flag as f32),
+                ("room.secondary_flag.present", present(&kind.secondary_flag)),
+                ("room.bounds.present", present(&room.bounds)),
+                ("room.secondary_bounds.present",
+                    present(&room.secondary_bounds)),
+                ("room.unknown7", room.unknown7),
+                ("room.sherman_levels", room.sherman_levels.len() as f32),
+                ("room.level_heights", room.level_heights.len() as f32),
+            ];
+            if let Some(flag) = kind.secondary_flag {
+                fields.push(("room.secondary_flag", flag as f32));
+            }
+            const BOUNDS: [&str; 6] = ["room.bounds.min.x", "room.bounds.min.y",
+                "room.bounds.min.z", "room.bounds.max.x", "room.bounds.max.y",
+                "room.bounds.max.z"];
+            const SECONDARY: [&str; 6] = ["room.secondary_bounds.min.x",
+                "room.secondary_bounds.min.y", "room.secondary_bounds.min.z",
+                "room.secondary_bounds.max.x", "room.secondary_bounds.max.y",
+                "room.secondary_bounds.max.z"];
+            for (names, aabb) in [(BOUNDS, room.bounds),
+                (SECONDARY, room.secondary_bounds)]
+            {
+                if let Some(aabb) = aabb {
+                    let values = aabb.min.into_iter().chain(aabb.max);
+                    fields.extend(names.into_iter().zip(values));
+                }
+            }
+            self.record(&example, &fields);
+
+            for level in &room.sherman_levels {
+                let example = format!("{example} {}", level.name);
+                let names: Vec<_> = (0..level.unknown1.len())
+                    .map(|i| format!("sherman_level.unknown1.{i}"))
+                    .collect();
+                let mut fields = vec![
+                    ("sherman_level.unknown1.count",
+                        level.unknown1.len() as f32),
+                    ("sherman_level.unknown2", level.unknown2 as f32),
+                    ("sherman_level.areas", level.tm_with_aabb.len() as f32),
+                ];
+                fields.extend(names.iter().map(String::as_str)
+                    .zip(level.unknown1.iter().copied()));
+                self.record(&example, &fields);
+            }
+
+            for (i, height) in room.level_heights.iter().enumerate() {
+                self.record(&format!("{example} height {i}"), &[
+                    ("level_height.index", i as f32),
+                    ("level_height.height", height.height),
+                    ("level_height.unknown", height.unknown),
+                ]);
+            }
+        }
+
+        for dynamic in &map.dynamic_objects.dynamic_objects {
+            let example = format!("{source} {}", dynamic.section_name);
+            match &dynamic.kind {
+                DynamicObjectKind::Dynamic { common, .. } => {
+                    self.record(&example, &[
+                        ("common.kind", dynamic.section_id as f32),
+                        ("common.unknown1", common.unknown1 as f32),
+                    ]);
+                }
+                DynamicObjectKind::RepeatableTouchplate {
+                    common, unknown1, attachments, unknown2, names, direction,
+                    distance, velocity, ..
+                } => {
+                    self.record(&example, &[
+                        ("common.kind", dynamic.section_id as f32),
+                        ("common.unknown1", common.unknown1 as f32),
+                        ("repeatable.unknown1", *unknown1 as f32),
+                        ("repeatable.attachments", attachments.len() as f32),
+                        ("repeatable.unknown2.0", unknown2[0]),
+                        ("repeatable.unknown2.1", unknown2[1]),
+                        ("repeatable.unknown2.2", unknown2[2]),
+                        ("repeatable.names", names.len() as f32),
+                        ("repeatable.direction.x", direction.x),
+                        ("repeatable.direction.y", direction.y),
+                        ("repeatable.direction.z", direction.z),
+                        ("repeatable.distance", *distance),
+                        ("repeatable.velocity", *velocity),
+                    ]);
+                }
+                DynamicObjectKind::Animation {
+                    common, unknown2, names, unknown3, unknown4, direction,
+                    distance, velocity, ..
+                } => {
+                    self.record(&example, &[
+                        ("common.kind", dynamic.section_id as f32),
+                        ("common.unknown1", common.unknown1 as f32),
+                        ("animation.unknown2", *unknown2 as f32),
+                        ("animation.names", names.len() as f32),
+                        ("animation.unknown3.0", unknown3[0]),
+                        ("animation.unknown3.1", unknown3[1]),
+                        ("animation.unknown3.2", unknown3[2]),
+                        ("animation.unknown4", *unknown4 as f32),
+                        ("animation.direction.x", direction.x),
+                        ("animation.direction.y", direction.y),
+                        ("animation.direction.z", direction.z),
+                        ("animation.distance", *distance),
+                        ("animation.velocity", *velocity),
+                    ]);
+                }
+                _ => {}
+            }
+        }
+        self.add_params(source, map);
+    }
+
+    /// Each field's distribution, sorted by field name
+    pub fn distributions(&self) -> Vec<Distribution> {
+        self.fields.iter()
+            .map(|(field, samples)| {
+                Distribution::new(field, samples, &self.examples)
+            })
+            .collect()
+    }
+
+    /// Every pair of fields that share at least three records and whose
+    /// correlation is at least `min` either way, strongest first. Fields
+    /// that never change are left out.
+    pub fn correlations(&self, min: f32) -> Vec<Correlation> {
+        let fields: Vec<_> = self.fields.iter().collect();
+        let mut correlations = Vec::new();
+        for (i, (a, a_samples)) in fields.iter().enumerate() {
+            let a_values: BTreeMap<_, _> = a_samples.iter().copied().collect();
+            for (b, b_samples) in &fields[i + 1..] {
+                let pairs: Vec<_> = b_samples.iter()
+                    .filter_map(|(record, b)| {
+                        Some((*a_values.get(record)?, *b))
+                    })
+                    .collect();
+                if pairs.len() < MIN_SHARED {
+                    continue;
+                }
+                let r = pearson(&pairs);
+                if r.is_finite() && r.abs() >= min {
+                    correlations.push(Correlation {
+                        a: a.to_string(),
+                        b: b.to_string(),
+                        r,
+                        count: pairs.len(),
+                    });
+                }
+            }
+        }
+        correlations.sort_by(|x, y| y.r.abs().total_cmp(&x.r.abs()));
+        correlations
+    }
+}
+
+fn present<T>(value: &Option<T>) -> f32 {
+    if value.is_some() { 1.0 } else { 0.0 }
+}
+
+/// NaN when either side never changes
+fn pearson(pairs: &[(f32, f32)]) -> f32 {
+    // f64 so large map coordinates don't swamp the sums
+    let n = pairs.len() as f64;
+    let mean = |f: fn(&(f32, f32)) -> f32| {
+        pairs.iter().map(|p| f(p) as f64).sum::<f64>() / n
+    };
+    let (mean_a, mean_b) = (mean(|p| p.0), mean(|p| p.1));
+    let (mut ab, mut aa, mut bb) = (0.0, 0.0, 0.0);
+    for (a, b) in pairs {
+        let (a, b) = (*a as f64 - mean_a, *b as f64 - mean_b);
+        ab += a * b;
+        aa += a * a;
+        bb += b * b;
+    }
+    (ab / (aa * bb).sqrt()) as f32
+}
+
+impl Distribution {
+    fn new(field: &str, samples: &[(usize, f32)], examples: &[String])
+        -> Self
+    {
+        // Tally by bit pattern so -0 and NaNs stay distinct, in first-seen
+        // order so ties keep the earliest example first
+        let mut tally: Vec<ValueCount> = Vec::new();
+        let mut index = BTreeMap::new();
+        for (record, value) in samples {
+            let i = *index.entry(value.to_bits()).or_insert_with(|| {
+                tally.push(ValueCount {
+                    value: *value,
+                    count: 0,
+                    example: examples[*record].clone(),
+                });
+                tally.len() - 1
+            });
+            tally[i].count += 1;
+        }
+        let distinct = tally.len();
+        tally.sort_by_key(|v| std::cmp::Reverse(v.count));
+        tally.truncate(COMMON);
+
+        let values = samples.iter().map(|(_, value)| *value);
+        let min = values.clone().fold(f32::INFINITY, f32::min);
+        let max = values.clone().fold(f32::NEG_INFINITY, f32::max);
+        let finite = values.clone().all(f32::is_finite);
+        let mut histogram = Vec::new();
+        if finite && distinct > 1 {
+            let width = (max - min) / BINS as f32;
+            histogram = (0..BINS).map(|i| Bin {
+                start: min + width * i as f32,
+                end: if i + 1 == BINS {
+                    max
+                } else {
+                    min + width * (i + 1) as f32
+                },
+                count: 0,
+            }).collect();
+            for value in values {
+                let i = ((value - min) / width) as usize;
+                histogram[i.min(BINS - 1)].count += 1;
+            }
+        }
+
+        Self {
+            field: field.to_string(),
+            count: samples.len(),
+            min,
+            max,
+            distinct,
+            common: tally,
+            histogram,
+        }
+    }
+}
+
+impl fmt::Display for Distribution {
+    /// The histogram is only shown when the common values don't already
+    /// account for every value
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}: {} values, {} distinct, {} to {}", self.field,
+            self.count, self.distinct, self.min, self.max)?;
+        for common in &self.common {
+            writeln!(f, "    {:>12} x{:<5} e.g. {}", common.value,
+                common.count, common.example)?;
+        }
+        if self.distinct > self.common.len() {
+            for bin in &self.histogram {
+                writeln!(f, "    [{}, {}] {}", bin.start, bin.end, bin.count)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Correlation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ~ {}: r = {:.3} over {}", self.a, self.b, self.r,
+            self.count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+
+    #[test]
+    fn spark_params_are_positions_and_directions() {
+        let map = crate::map::read(Path::new("data/map/rm19/rm19.map"))
+            .unwrap();
+        let mut samples = FieldSamples::default();
+        samples.add_params("rm19.map", &map);
+        let distributions = samples.distributions();
+        let field = |name: &str| {
+            distributions.iter().find(|d| d.field == name).unwrap()
+        };
+
+        let length = field("params.direction.length");
+        assert_eq!(length.count, 33);
+        assert!(length.min > 0.999 && length.max < 1.001, "{length}");
+        for (name, value) in [
+            ("params.unknown1.0", 0.0),
+            ("params.unknown1.1", 1.0),
+            ("params.unknown1.2", 25.0),
+            ("params.unknown2.0", 0.0),
+            ("params.unknown2.1", 0.0),
+            ("flat.unknown.0", 1.0),
+            ("flat.unknown.1", 25.0),
+            ("flat.unknown.2", 0.0),
+            ("flat.unknown.3", 0.0),
+        ] {
+            let d = field(name);
+            assert_eq!((d.distinct, d.min), (1, value), "{d}");
+        }
+        let x = field("params.position.x");
+        assert!(x.distinct > 1);
+        assert!(x.common.iter().all(|c| c.example.starts_with("rm19.map ")));
+    }
+
+    #[test]
+    fn unknowns_report_histograms_and_correlations() {
+        let map = crate::map::read(Path::new("data/map/rm19/rm19.map"))
+            .unwrap();
+        let mut samples = FieldSamples::default();
+        samples.add_unknowns("rm19.map", &map);
+        let distributions = samples.distributions();
+        let field = |name: &str| {
+            distributions.iter().find(|d| d.field == name).unwrap()
+        };
+
+        let rooms = field("room.unknown1");
+        assert_eq!(rooms.count, map.rooms.rooms.len());
+        assert_eq!(rooms.histogram.iter().map(|b| b.count).sum::<usize>(),
+            rooms.count);
+        assert_eq!(rooms.histogram.first().unwrap().start, rooms.min);
+        assert_eq!(rooms.histogram.last().unwrap().end, rooms.max);
+        assert!(field("room.bounds_flag").histogram.is_empty());
+
+        // The reader only finds the secondary flag when unknown1 is 0
+        let correlations = samples.correlations(0.99);
+        assert!(correlations.iter().any(|c| {
+            c.a == "room.secondary_flag.present" && c.b == "room.unknown1"
+                && c.r < -0.99
+        }), "{correlations:?}");
+        assert!(correlations.iter().all(|c| c.r.abs() >= 0.99));
+
+        // Every value of a sherman level's unknown1 is recorded
+        let levels: Vec<_> = map.rooms.rooms.iter()
+            .flat_map(|room| &room.sherman_levels)
+            .collect();
+        let longest = levels.iter().map(|l| l.unknown1.len()).max().unwrap();
+        let last = field(&format!("sherman_level.unknown1.{}", longest - 1));
+        assert_eq!(last.count,
+            levels.iter().filter(|l| l.unknown1.len() == longest).count());
+
+        // An animation's common and own fields share one record
+        let records = |name: &str| {
+            samples.fields[name].iter()
+                .map(|&(record, _)| record)
+                .collect::<Vec<_>>()
+        };
+        let common = records("common.kind");
+        assert!(records("animation.unknown2").iter()
+            .all(|record| common.contains(record)));
+        assert!(records("repeatable.unknown1").iter()
+            .all(|record| common.contains(record)));
+        assert!(field("repeatable.unknown2.0").count > 0);
+    }
+}
@@ -0,0 +1,391 @@
+use std::f32::consts::TAU;
+
+use serde::Serialize;
+
+use super::{DynamicObjectKind, Map, PenetrationType};
+use crate::vec3::{add, cross, dot, length, scale, sub};
+
+/// A breakable pane: a `Glass` dynamic object with the outline of its mesh
+#[derive(Clone, Debug, Serialize)]
+pub struct Pane {
+    /// Index into `DynamicObjects::dynamic_objects`
+    pub object: usize,
+    /// Index into `Geometries::objects`
+    pub mesh: usize,
+    /// The `Glass` object's penetration type
+    pub penetration_type: PenetrationType,
+    /// The outline in map space, anticlockwise seen from the side `normal`
+    /// points to
+    pub outline: Vec<[f32; 3]>,
+    /// Unit normal of the pane's plane. Glass meshes are drawn from both
+    /// sides, so which way it faces has no meaning.
+    pub normal: [f32; 3],
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ShatterOptions {
+    /// The same seed always cracks a pane the same way
+    pub seed: u64,
+    /// Cracks running out from the impact. At least 4 are made.
+    pub cracks: u32,
+    /// Rings of cracks around the impact, cutting each wedge between two
+    /// cracks into `rings + 1` fragments
+    pub rings: u32,
+}
+
+impl Default for ShatterOptions {
+    fn default() -> Self {
+        Self { seed: 0, cracks: 8, rings: 2 }
+    }
+}
+
+/// A convex piece of a shattered pane
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Fragment {
+    /// The outline in map space, wound the same way as the pane's
+    pub outline: Vec<[f32; 3]>,
+    /// The average of the outline's points, e.g. for pushing the fragment
+    /// out from the impact
+    pub centroid: [f32; 3],
+}
+
+impl Fragment {
+    /// The fragment as a fan of triangles
+    pub fn triangles(&self) -> impl Iterator<Item = [[f32; 3]; 3]> + '_ {
+        let first = self.outline[0];
+        self.outline.windows(2).skip(1).map(move |edge| {
+            [first, edge[0], edge[1]]
+        })
+    }
+
+    pub fn area(&self) -> f32 {
+        polygon_area(&self.outline)
+    }
+}
+
+impl Map {
+    /// Every `Glass` dynamic object whose mesh was found and is flat enough
+    /// to have an outline. Meshes are matched by `DynamicObject::name`, see
+    /// `Map::mesh_named`. The outline is the convex hull of the mesh, which
+    /// is the pane itself for the rectangles in the shipped maps.
+    pub fn glass_panes(&self) -> Vec<Pane> {
+        let objects = &self.dynamic_objects.dynamic_objects;
+        objects.iter().enumerate()
+            .filter_map(|(object, dynamic)| {
+                let DynamicObjectKind::Glass { penetration_type } =
+                    &dynamic.kind
+                else {
+                    return None;
+                };
+                let mesh = self.mesh_named(&dynamic.name)?;
+                let geometry = &self.geometries.objects[mesh];
+                let vertices: Vec<_> = geometry.vertices.iter()
+                    .map(|v| dynamic.tm.transform_point([v.x, v.y, v.z]))
+                    .collect();
+                let triangles = geometry.object_datas.iter()
+                    .flat_map(|data| &data.faces.face_indices)
+                    .filter_map(|&(a, b, c)| Some([
+                        *vertices.get(a as usize)?,
+                        *vertices.get(b as usize)?,
+                        *vertices.get(c as usize)?,
+                    ]));
+                let (outline, normal) = outline(triangles, &vertices)?;
+                Some(Pane {
+                    object,
+                    mesh,
+                    penetration_type: penetration_type.clone(),
+                    outline,
+                    normal,
+                })
+            })
+            .collect()
+    }
+}
+
+impl Pane {
+    pub fn area(&self) -> f32 {
+        polygon_area(&self.outline)
+    }
+
+    /// Crack the pane into convex fragments around `impact`, which is
+    /// projected onto the pane. Cracks run out from the impact with some
+    /// jitter in their angles and rings of cracks cut across them, like
+    /// struck safety glass. The fragments cover the pane exactly, whether or
+    /// not the impact is inside it, apart from slivers too small to draw.
+    pub fn shatter(&self, impact: [f32; 3], options: &ShatterOptions)
+        -> Vec<Fragment>
+    {
+        let plane = Plane::new(self.outline[0], self.outline[1], self.normal);
+        let pane: Vec<_> = self.outline.iter().map(|&p| plane.project(p))
+            .collect();
+        let impact = plane.project(impact);
+        let mut random = SplitMix64(options.seed);
+
+        let cracks = options.cracks.max(4);
+        let step = TAU / cracks as f32;
+        // Up to half a step of jitter keeps every wedge under half a turn,
+        // so the wedges are convex
+        let angles: Vec<_> = (0..cracks)
+            .map(|i| (i as f32 + random.next_f32() * 0.5) * step)
+            .collect();
+        let reach = pane.iter()
+            .map(|&p| length2(sub2(p, impact)))
+            .fold(0.0, f32::max);
+        let sliver = polygon_area2(&pane) * 1e-6;
+
+        let mut fragments = Vec::new();
+        for (i, &start) in angles.iter().enumerate() {
+            let end = angles.get(i + 1).copied()
+                .unwrap_or(angles[0] + TAU);
+            let from = [start.cos(), start.sin()];
+            let to = [end.cos(), end.sin()];
+            let wedge = clip(&pane, |p| cross2(from, sub2(p, impact)));
+            let mut rest = clip(&wedge, |p| cross2(sub2(p, impact), to));
+
+            let middle = (start + end) / 2.0;
+            let outward = [middle.cos(), middle.sin()];
+            let distance = |p| dot2(sub2(p, impact), outward);
+            let mut pieces = Vec::new();
+            for ring in 0..options.rings {
+                let band = (ring as f32 + 0.25 + random.next_f32() * 0.5)
+                    / (options.rings + 1) as f32;
+                let radius = reach * band;
+                pieces.push(clip(&rest, |p| radius - distance(p)));
+                rest = clip(&rest, |p| distance(p) - radius);
+            }
+            pieces.push(rest);
+
+            for piece in pieces {
+                if piece.len() < 3 || polygon_area2(&piece) <= sliver {
+                    continue;
+                }
+                let outline: Vec<_> = piece.iter().map(|&p| plane.unproject(p))
+                    .collect();
+                let sum = outline.iter().fold([0.0; 3], |sum, &p| add(sum, p));
+                let centroid = scale(sum, 1.0 / outline.len() as f32);
+                fragments.push(Fragment { outline, centroid });
+            }
+        }
+        fragments
+    }
+}
+
+/// The convex outline and normal of the `triangles` of a flat mesh, or
+/// `None` if they're all degenerate or don't enclose any area
+fn outline(
+    mut triangles: impl Iterator<Item = [[f32; 3]; 3]>,
+    vertices: &[[f32; 3]],
+) -> Option<(Vec<[f32; 3]>, [f32; 3])> {
+    let (plane, normal) = triangles.find_map(|[a, b, c]| {
+        let normal = cross(sub(b, a), sub(c, a));
+        let len = length(normal);
+        (len > 1e-6 && length(sub(b, a)) > 0.0).then(|| {
+            let normal = scale(normal, 1.0 / len);
+            (Plane::new(a, b, normal), normal)
+        })
+    })?;
+    let points: Vec<_> = vertices.iter().map(|&p| plane.project(p)).collect();
+    let hull = convex_hull(points);
+    if hull.len() < 3 {
+        return None;
+    }
+    Some((hull.into_iter().map(|p| plane.unproject(p)).collect(), normal))
+}
+
+/// Andrew's monotone chain, anticlockwise without collinear points
+fn convex_hull(mut points: Vec<[f32; 2]>) -> Vec<[f32; 2]> {
+    points.sort_by(|a, b| a[0].total_cmp(&b[0]).then(a[1].total_cmp(&b[1])));
+    points.dedup();
+    if points.len() < 3 {
+        return points;
+    }
+    let mut hull: Vec<[f32; 2]> = Vec::with_capacity(points.len() * 2);
+    for pass in 0..2 {
+        let start = hull.len();
+        for &p in &points {
+            while hull.len() >= start + 2 {
+                let [a, b] = [hull[hull.len() - 2], hull[hull.len() - 1]];
+                if cross2(sub2(b, a), sub2(p, a)) > 0.0 {
+                    break;
+                }
+                hull.pop();
+            }
+            hull.push(p);
+        }
+        hull.pop();
+        if pass == 0 {
+            points.reverse();
+        }
+    }
+    hull
+}
+
+/// Keep the part of a convex polygon where `side` is at least zero
+fn clip(polygon: &[[f32; 2]], side: impl Fn([f32; 2]) -> f32)
+    -> Vec<[f32; 2]>
+{
+    let mut clipped = Vec::with_capacity(polygon.len() + 1);
+    for (i, &a) in polygon.iter().enumerate() {
+        let b = polygon[(i + 1) % polygon.len()];
+        let (sa, sb) = (side(a), side(b));
+        if sa >= 0.0 {
+            clipped.push(a);
+        }
+        if (sa >= 0.0) != (sb >= 0.0) {
+            let t = sa / (sa - sb);
+            clipped.push(add2(a, scale2(sub2(b, a), t)));
+        }
+    }
+    clipped
+}
+
+/// 2D coordinates in a plane through `origin`
+struct Plane {
+    origin: [f32; 3],
+    u: [f32; 3],
+    v: [f32; 3],
+}
+
+impl Plane {
+    /// `u` points from `origin` to `towards`
+    fn new(origin: [f32; 3], towards: [f32; 3], normal: [f32; 3]) -> Self {
+        let u = sub(towards, origin);
+        let u = scale(u, 1.0 / length(u));
+        Self { origin, u, v: cross(normal, u) }
+    }
+
+    fn project(&self, p: [f32; 3]) -> [f32; 2] {
+        let d = sub(p, self.origin);
+        [dot(d, self.u), dot(d, self.v)]
+    }
+
+    fn unproject(&self, [x, y]: [f32; 2]) -> [f32; 3] {
+        add(self.origin, add(scale(self.u, x), scale(self.v, y)))
+    }
+}
+
+/// Deterministic random numbers from a seed, so a pane breaks the same way
+/// every time
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in `0..1`
+    fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+}
+
+fn polygon_area(polygon: &[[f32; 3]]) -> f32 {
+    let first = polygon[0];
+    let twice = polygon.windows(2).skip(1).fold([0.0; 3], |sum, edge| {
+        add(sum, cross(sub(edge[0], first), sub(edge[1], first)))
+    });
+    length(twice) / 2.0
+}
+
+fn polygon_area2(polygon: &[[f32; 2]]) -> f32 {
+    let twice: f32 = polygon.iter().enumerate().map(|(i, &a)| {
+        cross2(a, polygon[(i + 1) % polygon.len()])
+    }).sum();
+    twice.abs() / 2.0
+}
+
+fn add2(a: [f32; 2], b: [f32; 2]) -> [f32; 2] {
+    [a[0] + b[0], a[1] + b[1]]
+}
+
+fn sub2(a: [f32; 2], b: [f32; 2]) -> [f32; 2] {
+    [a[0] - b[0], a[1] - b[1]]
+}
+
+fn scale2(a: [f32; 2], s: f32) -> [f32; 2] {
+    [a[0] * s, a[1] * s]
+}
+
+fn dot2(a: [f32; 2], b: [f32; 2]) -> f32 {
+    a[0] * b[0] + a[1] * b[1]
+}
+
+fn cross2(a: [f32; 2], b: [f32; 2]) -> f32 {
+    a[0] * b[1] - a[1] * b[0]
+}
+
+fn length2(a: [f32; 2]) -> f32 {
+    dot2(a, a).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+
+    fn door_pane() -> (Map, Pane) {
+        let map = crate::map::read(Path::new("data/map/rm19/rm19.map"))
+            .unwrap();
+        let pane = map.glass_panes().into_iter()
+            .find(|pane| {
+                let object = &map.dynamic_objects.dynamic_objects[pane.object];
+                object.name == "427_doorglass01glass0"
+            })
+            .unwrap();
+        (map, pane)
+    }
+
+    #[test]
+    fn every_shipped_pane_has_an_outline() {
+        let (map, pane) = door_pane();
+        let glass = map.dynamic_objects.dynamic_objects.iter()
+            .filter(|o| matches!(o.kind, DynamicObjectKind::Glass { .. }))
+            .count();
+        assert_eq!(map.glass_panes().len(), glass);
+
+        assert_eq!(pane.penetration_type, PenetrationType::HollowThickMetal);
+        assert_eq!(pane.outline.len(), 4);
+        assert!((pane.area() - 130.0 * 240.0).abs() < 1.0);
+        assert!((length(pane.normal) - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn shattering_covers_the_pane_and_is_repeatable() {
+        let (_, pane) = door_pane();
+        let centre = scale(pane.outline.iter().fold([0.0; 3], |s, &p| {
+            add(s, p)
+        }), 0.25);
+        let options = ShatterOptions::default();
+        let fragments = pane.shatter(centre, &options);
+        assert_eq!(fragments, pane.shatter(centre, &options));
+        assert_ne!(fragments, pane.shatter(centre, &ShatterOptions {
+            seed: 1,
+            ..options.clone()
+        }));
+        assert_eq!(fragments.len(), 8 * 3);
+
+        let area: f32 = fragments.iter().map(Fragment::area).sum();
+        assert!((area - pane.area()).abs() < 1.0, "{area}");
+        let triangles: f32 = fragments.iter()
+            .flat_map(Fragment::triangles)
+            .map(|t| polygon_area(&t))
+            .sum();
+        assert!((triangles - area).abs() < 1.0);
+        for point in fragments.iter().flat_map(|f| &f.outline) {
+            let offset = dot(sub(*point, pane.outline[0]), pane.normal);
+            assert!(offset.abs() < 1e-2, "{offset}");
+        }
+
+        // An impact off the pane still breaks all of it
+        let outside = add(pane.outline[0], scale(sub(pane.outline[0],
+            centre), 3.0));
+        let fragments = pane.shatter(outside, &options);
+        let area: f32 = fragments.iter().map(Fragment::area).sum();
+        assert!((area - pane.area()).abs() < 1.0, "{area}");
+    }
+}
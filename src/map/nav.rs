@@ -0,0 +1,403 @@
+use std::collections::{HashMap, VecDeque};
+
+use serde::Serialize;
+
+use super::{Aabb, Map};
+use crate::vec3::{length, sub};
+
+/// What every shipped transition name starts with, before the room's name
+const PREFIX: &str = "shermantransition";
+
+/// How a transition is climbed, from the end of its name
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub enum TransitionKind {
+    /// Names ending in "ladder"
+    Ladder,
+    /// Everything else: stairs and ramps
+    Stairs,
+}
+
+/// A `Transition` read as a volume that moves between floors of a room.
+///
+/// The MAP only stores a horizontal edge, `start` to `end`, which is where
+/// the stairs or ladder meet a floor. The volume is the upright rectangle
+/// over that edge from the lower floor it connects to the upper.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct TransitionVolume {
+    /// Index into `Transitions::transitions`
+    pub transition: usize,
+    pub name: String,
+    pub kind: TransitionKind,
+    /// Index into `Rooms::rooms` of the room in the name, e.g. "301" in
+    /// "shermantransition301_2.35", ignoring leading zeros
+    pub room: Option<usize>,
+    /// Indices into `PlanningLevels::levels` of the floors connected, lower
+    /// first. Empty if the room is unknown or on fewer than two levels.
+    pub levels: Vec<usize>,
+    /// `TransitionCoords::p1`
+    pub start: [f32; 3],
+    /// `TransitionCoords::p2`
+    pub end: [f32; 3],
+    /// Height of the lower floor, or of the edge if `levels` is empty
+    pub bottom: f32,
+    /// Height of the upper floor, or of the edge if `levels` is empty
+    pub top: f32,
+    /// Horizontal unit vector across the edge, the way through the volume
+    pub normal: [f32; 3],
+}
+
+impl TransitionVolume {
+    /// Length of the edge
+    pub fn width(&self) -> f32 {
+        length(sub(self.end, self.start))
+    }
+
+    /// The rectangle's corners: along the bottom from `start` to `end`,
+    /// then back along the top
+    pub fn corners(&self) -> [[f32; 3]; 4] {
+        let at = |p: [f32; 3], y: f32| [p[0], y, p[2]];
+        [
+            at(self.start, self.bottom),
+            at(self.end, self.bottom),
+            at(self.end, self.top),
+            at(self.start, self.top),
+        ]
+    }
+
+    /// The smallest box holding the rectangle and the edge
+    pub fn bounds(&self) -> Aabb {
+        Aabb::from_points(self.corners().into_iter()
+            .chain([self.start, self.end]))
+            .expect("a transition volume has corners")
+    }
+}
+
+/// A place on the map: a room on one of its planning levels
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct NavNode {
+    /// Index into `Rooms::rooms`
+    pub room: usize,
+    /// Index into `PlanningLevels::levels`
+    pub level: usize,
+}
+
+/// How two `NavNode`s connect
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub enum NavLink {
+    /// Index into `Portals::portals`, between two rooms on one level
+    Portal(usize),
+    /// Index into `Transitions::transitions`, between two levels of one room
+    Transition(usize),
+}
+
+/// An undirected edge between two `NavGraph::nodes`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct NavEdge {
+    pub a: usize,
+    pub b: usize,
+    pub link: NavLink,
+}
+
+/// Which rooms and floors connect to which. See `Map::nav_graph`.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct NavGraph {
+    /// In planning level order, then the level's room order
+    pub nodes: Vec<NavNode>,
+    pub edges: Vec<NavEdge>,
+}
+
+impl NavGraph {
+    /// The node for `room` on `level`, if the level lists the room
+    pub fn node(&self, room: usize, level: usize) -> Option<usize> {
+        self.nodes.iter().position(|n| n.room == room && n.level == level)
+    }
+
+    /// The nodes next to `node` and the edges that lead to them
+    pub fn neighbours(&self, node: usize)
+        -> impl Iterator<Item = (usize, &NavEdge)>
+    {
+        self.edges.iter().filter_map(move |edge| {
+            if edge.a == node {
+                Some((edge.b, edge))
+            } else if edge.b == node {
+                Some((edge.a, edge))
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Indices into `edges` of a shortest path from node `from` to node
+    /// `to`, counting edges, or `None` if there's no way there
+    pub fn path(&self, from: usize, to: usize) -> Option<Vec<usize>> {
+        let mut adjacent = vec![Vec::new(); self.nodes.len()];
+        for (i, edge) in self.edges.iter().enumerate() {
+            adjacent[edge.a].push((i, edge.b));
+            adjacent[edge.b].push((i, edge.a));
+        }
+
+        // Breadth-first, remembering the edge each node was first reached by
+        let mut reached_by = vec![None; self.nodes.len()];
+        let mut queue = VecDeque::from([from]);
+        while let Some(node) = queue.pop_front() {
+            if node == to {
+                let mut path = Vec::new();
+                let mut at = to;
+                while at != from {
+                    let edge: usize = reached_by[at]?;
+                    path.push(edge);
+                    let e = &self.edges[edge];
+                    at = if e.a == at { e.b } else { e.a };
+                }
+                path.reverse();
+                return Some(path);
+            }
+            for &(i, next) in &adjacent[node] {
+                if next != from && reached_by[next].is_none() {
+                    reached_by[next] = Some(i);
+                    queue.push_back(next);
+                }
+            }
+        }
+        None
+    }
+}
+
+impl Map {
+    /// Every transition as a volume between floors. The room, kind and floors
+    /// come from the name and height: the two planning levels listing the
+    /// room whose floors are nearest the edge.
+    pub fn transition_volumes(&self) -> Vec<TransitionVolume> {
+        let rooms = self.room_indices();
+        let transitions = &self.transitions.transitions;
+        transitions.iter().enumerate().map(|(transition, t)| {
+            let (p1, p2) = (&t.coords.p1, &t.coords.p2);
+            let (start, end) = ([p1.x, p1.y, p1.z], [p2.x, p2.y, p2.z]);
+            let name = t.name.to_ascii_lowercase();
+            let room_name = name.strip_prefix(PREFIX)
+                .and_then(|rest| rest.split('_').next());
+            // Names pad room numbers, e.g. "07" for room "7"
+            let room = room_name.and_then(|r| {
+                rooms.get(r).or_else(|| rooms.get(r.trim_start_matches('0')))
+                    .copied()
+            });
+            let kind = if name.ends_with("ladder") {
+                TransitionKind::Ladder
+            } else {
+                TransitionKind::Stairs
+            };
+
+            let height = (start[1] + end[1]) / 2.0;
+            let levels = self.nearest_levels(room, height);
+            let floor = |i: usize| self.planning_levels.levels[i].floor_height;
+            let (bottom, top) = match levels[..] {
+                [lower, upper] => (floor(lower), floor(upper)),
+                _ => (height, height),
+            };
+
+            let d = sub(end, start);
+            let len = length([d[0], 0.0, d[2]]);
+            let normal = if len > 0.0 {
+                [d[2] / len, 0.0, -d[0] / len]
+            } else {
+                [0.0; 3]
+            };
+
+            TransitionVolume {
+                transition,
+                name: t.name.clone(),
+                kind,
+                room,
+                levels,
+                start,
+                end,
+                bottom,
+                top,
+                normal,
+            }
+        }).collect()
+    }
+
+    /// Connect each room on each planning level to the rooms its portals
+    /// lead to, on the level nearest the portal's sill, and to its own
+    /// other levels through its transitions. Rooms are matched by section
+    /// name; portals and levels naming missing rooms are left out.
+    pub fn nav_graph(&self) -> NavGraph {
+        let rooms = self.room_indices();
+        let mut graph = NavGraph::default();
+        let mut nodes = HashMap::new();
+        for (level, l) in self.planning_levels.levels.iter().enumerate() {
+            for name in &l.room_names {
+                let Some(&room) = rooms.get(name.as_str()) else {
+                    continue;
+                };
+                nodes.entry((room, level)).or_insert_with(|| {
+                    graph.nodes.push(NavNode { room, level });
+                    graph.nodes.len() - 1
+                });
+            }
+        }
+
+        for (portal, p) in self.portals.portals.iter().enumerate() {
+            let room = rooms.get(p.room.to_string().as_str()).copied();
+            let opposite = rooms.get(p.opposite_room.to_string().as_str())
+                .copied();
+            let (Some(a), Some(b)) = (room, opposite) else {
+                continue;
+            };
+            let sill = p.coordinates.iter().map(|v| v.y)
+                .fold(f32::INFINITY, f32::min);
+            let shared = (0..self.planning_levels.levels.len())
+                .filter(|&l| {
+                    nodes.contains_key(&(a, l)) && nodes.contains_key(&(b, l))
+                });
+            let Some(level) = self.nearest(shared, sill) else {
+                continue;
+            };
+            graph.edges.push(NavEdge {
+                a: nodes[&(a, level)],
+                b: nodes[&(b, level)],
+                link: NavLink::Portal(portal),
+            });
+        }
+
+        for volume in self.transition_volumes() {
+            let (Some(room), [lower, upper]) = (volume.room, &volume.levels[..])
+            else {
+                continue;
+            };
+            let (Some(&a), Some(&b)) = (nodes.get(&(room, *lower)),
+                nodes.get(&(room, *upper)))
+            else {
+                continue;
+            };
+            graph.edges.push(NavEdge {
+                a,
+                b,
+                link: NavLink::Transition(volume.transition),
+            });
+        }
+        graph
+    }
+
+    /// Room indices by section name
+    fn room_indices(&self) -> HashMap<&str, usize> {
+        let mut rooms = HashMap::new();
+        for (i, room) in self.rooms.rooms.iter().enumerate() {
+            rooms.entry(room.section_name.as_str()).or_insert(i);
+        }
+        rooms
+    }
+
+    /// The two planning levels listing `room` with floors nearest `height`,
+    /// lower floor first, or none if it's on fewer than two
+    fn nearest_levels(&self, room: Option<usize>, height: f32) -> Vec<usize> {
+        let Some(room) = room else {
+            return Vec::new();
+        };
+        let levels = &self.planning_levels.levels;
+        let name = &self.rooms.rooms[room].section_name;
+        let mut candidates: Vec<usize> = (0..levels.len())
+            .filter(|&l| levels[l].room_names.contains(name))
+            .collect();
+        if candidates.len() < 2 {
+            return Vec::new();
+        }
+        let distance = |l: &usize| (levels[*l].floor_height - height).abs();
+        candidates.sort_by(|a, b| distance(a).total_cmp(&distance(b)));
+        candidates.truncate(2);
+        candidates.sort_by(|a, b| {
+            levels[*a].floor_height.total_cmp(&levels[*b].floor_height)
+        });
+        candidates
+    }
+
+    /// The level of `levels` whose floor is nearest `height`
+    fn nearest(&self, levels: impl Iterator<Item = usize>, height: f32)
+        -> Option<usize>
+    {
+        let floors = &self.planning_levels.levels;
+        levels.min_by(|a, b| {
+            let a = (floors[*a].floor_height - height).abs();
+            let b = (floors[*b].floor_height - height).abs();
+            a.total_cmp(&b)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+
+    #[test]
+    fn transitions_join_floors_of_their_room() {
+        let map = crate::map::read(Path::new(
+            "data/map/m00/citystreet_large.map")).unwrap();
+        let volumes = map.transition_volumes();
+        assert_eq!(volumes.len(), 10);
+        let ladder = volumes.iter()
+            .find(|v| v.name == "shermantransition07_1.5ladder")
+            .unwrap();
+        assert_eq!(ladder.kind, TransitionKind::Ladder);
+        let room = &map.rooms.rooms[ladder.room.unwrap()];
+        assert_eq!(room.section_name, "7");
+        assert_eq!(ladder.width(), 60.0);
+        assert!(ladder.bottom < ladder.top);
+        let levels = &map.planning_levels.levels;
+        for &level in &ladder.levels {
+            assert!(levels[level].room_names.contains(&room.section_name));
+        }
+        // Floors only ever come from the transition's own room
+        for volume in &volumes {
+            let Some(room) = volume.room else {
+                assert!(volume.levels.is_empty());
+                continue;
+            };
+            let name = &map.rooms.rooms[room].section_name;
+            assert!(volume.levels.iter()
+                .all(|&l| levels[l].room_names.contains(name)));
+        }
+        let n = ladder.normal;
+        assert!((n[0] * n[0] + n[2] * n[2] - 1.0).abs() < 1e-6);
+        assert!(ladder.bounds().contains(ladder.start));
+    }
+
+    #[test]
+    fn paths_change_floors_through_transitions() {
+        let map = crate::map::read(Path::new("data/map/rm19/rm19.map"))
+            .unwrap();
+        let graph = map.nav_graph();
+        let transitions = graph.edges.iter()
+            .filter(|e| matches!(e.link, NavLink::Transition(_)))
+            .count();
+        assert!(transitions > 0);
+
+        // Every transition edge stays in its room
+        for edge in &graph.edges {
+            if let NavLink::Transition(_) = edge.link {
+                let (a, b) = (graph.nodes[edge.a], graph.nodes[edge.b]);
+                assert_eq!(a.room, b.room);
+                assert_ne!(a.level, b.level);
+            }
+        }
+
+        let room = map.rooms.rooms.iter()
+            .position(|r| r.section_name == "301")
+            .unwrap();
+        let mut levels: Vec<_> = graph.nodes.iter()
+            .filter(|n| n.room == room)
+            .map(|n| n.level)
+            .collect();
+        levels.sort();
+        let from = graph.node(room, levels[0]).unwrap();
+        let to = graph.node(room, *levels.last().unwrap()).unwrap();
+        let path = graph.path(from, to).unwrap();
+        assert!(path.iter().any(|&e| {
+            matches!(graph.edges[e].link, NavLink::Transition(_))
+        }));
+        assert_eq!(graph.path(from, from), Some(Vec::new()));
+        assert!(graph.neighbours(from).count() > 0);
+    }
+}
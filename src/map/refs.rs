@@ -0,0 +1,348 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use serde::Serialize;
+
+use super::{DynamicObjectKind, KindDynamicParams, Map};
+
+/// The name the game uses for "no reference"
+const NONE: &str = "NONE";
+
+/// Where in a dynamic object a name was found. Indices are positions in the
+/// list the name came from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub enum ReferenceField {
+    /// `DynamicObject::name`, the mesh drawn for the object
+    Name,
+    /// `DynamicObjectKindCommon::name`, e.g. a television's broken mesh
+    CommonName,
+    /// `DynamicObjectKindCommon::name2`
+    CommonName2,
+    /// `DynamicObjectKindCommon::sounds`
+    Sounds(usize),
+    /// `names` of an `Animation` or `RepeatableTouchplate`, e.g. the glass
+    /// panes of a door
+    Names(usize),
+    /// `attachments` of a touchplate: model files for repeatable ones and the
+    /// doors to open for one-time ones
+    Attachments(usize),
+    /// `name2` of a `RepeatableTouchplate`, its opening sound
+    Name2,
+    /// `name3` of an `Animation` or `RepeatableTouchplate`. The latter's is
+    /// its closing sound.
+    Name3,
+    /// `name4` of an `Animation`
+    Name4,
+    /// `KindDynamicParamStruct::name` or a `KindDynamicParams::Flat` name
+    Params(usize),
+}
+
+/// What a name resolved to
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub enum ReferenceTarget {
+    /// Index into `Geometries::objects`
+    Mesh(usize),
+    /// Index into `DynamicObjects::dynamic_objects`, found by its
+    /// `section_name`
+    DynamicObject(usize),
+    /// A WAV file, recognised by its extension. The file is outside the MAP
+    /// and isn't checked; `Map::audio_manifest` looks for it in the game
+    /// data.
+    Sound(String),
+    /// A QOB model file, recognised by its extension. The file is outside
+    /// the MAP and isn't checked.
+    Model(String),
+}
+
+impl ReferenceTarget {
+    /// True for files outside the MAP, which are never checked to exist
+    pub fn is_external(&self) -> bool {
+        matches!(self, Self::Sound(_) | Self::Model(_))
+    }
+}
+
+/// A name in a dynamic object and what it refers to
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Reference {
+    /// Index into `DynamicObjects::dynamic_objects` of the object holding
+    /// the name
+    pub object: usize,
+    pub field: ReferenceField,
+    pub name: String,
+    /// `None` when nothing in the MAP has the name. File names always
+    /// resolve, see `ReferenceTarget::is_external`.
+    pub target: Option<ReferenceTarget>,
+}
+
+/// Every name in a MAP's dynamic objects, resolved. See `Map::references`.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct ReferenceGraph {
+    /// In dynamic object order, then field order within each object
+    pub references: Vec<Reference>,
+}
+
+impl ReferenceGraph {
+    /// Names that didn't resolve to anything in the MAP. Only names inside
+    /// the MAP are checked, so file names never dangle; see `external`.
+    pub fn dangling(&self) -> impl Iterator<Item = &Reference> {
+        self.references.iter().filter(|r| r.target.is_none())
+    }
+
+    /// References to sound and model files, which are unchecked
+    pub fn external(&self) -> impl Iterator<Item = &Reference> {
+        self.references.iter()
+            .filter(|r| r.target.as_ref().is_some_and(|t| t.is_external()))
+    }
+
+    /// The references held by `object`
+    pub fn held_by(&self, object: usize) -> impl Iterator<Item = &Reference> {
+        self.references.iter().filter(move |r| r.object == object)
+    }
+
+    /// References to `object`, e.g. the touchplates that open a door
+    pub fn to(&self, object: usize) -> impl Iterator<Item = &Reference> {
+        let target = Some(ReferenceTarget::DynamicObject(object));
+        self.references.iter().filter(move |r| r.target == target)
+    }
+
+    /// Indices into `Geometries::objects` of every mesh `object` refers to,
+    /// in order and without repeats. These move and go with the object.
+    pub fn meshes(&self, object: usize) -> Vec<usize> {
+        let mut meshes = Vec::new();
+        for reference in self.held_by(object) {
+            if let Some(ReferenceTarget::Mesh(mesh)) = reference.target {
+                if !meshes.contains(&mesh) {
+                    meshes.push(mesh);
+                }
+            }
+        }
+        meshes
+    }
+}
+
+impl fmt::Display for Reference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "dynamic object {} {:?} \"{}\" -> ", self.object, self.field,
+            self.name)?;
+        match &self.target {
+            Some(ReferenceTarget::Mesh(i)) => write!(f, "mesh {i}"),
+            Some(ReferenceTarget::DynamicObject(i)) => {
+                write!(f, "dynamic object {i}")
+            }
+            Some(ReferenceTarget::Sound(file)) => {
+                write!(f, "sound {file} (unchecked)")
+            }
+            Some(ReferenceTarget::Model(file)) => {
+                write!(f, "model {file} (unchecked)")
+            }
+            None => write!(f, "nothing"),
+        }
+    }
+}
+
+/// Which list a bare name is looked up in first
+#[derive(Clone, Copy)]
+enum Prefer {
+    Mesh,
+    DynamicObject,
+}
+
+struct Resolver {
+    meshes: HashMap<String, usize>,
+    dynamic_objects: HashMap<String, usize>,
+}
+
+impl Resolver {
+    fn new(map: &Map) -> Self {
+        // Names compare case-insensitively and the first of any duplicates
+        // wins, as `Iterator::position` would
+        let mut meshes = HashMap::new();
+        for (i, object) in map.geometries.objects.iter().enumerate() {
+            meshes.entry(object.name.to_ascii_lowercase()).or_insert(i);
+        }
+        let mut dynamic_objects = HashMap::new();
+        let objects = &map.dynamic_objects.dynamic_objects;
+        for (i, object) in objects.iter().enumerate() {
+            dynamic_objects.entry(object.section_name.to_ascii_lowercase())
+                .or_insert(i);
+        }
+        Self { meshes, dynamic_objects }
+    }
+
+    /// Files are recognised by extension; other names are looked up in the
+    /// MAP, `prefer` first
+    fn resolve(&self, name: &str, prefer: Prefer) -> Option<ReferenceTarget> {
+        let lower = name.to_ascii_lowercase();
+        if lower.ends_with(".wav") {
+            return Some(ReferenceTarget::Sound(name.to_string()));
+        }
+        if lower.ends_with(".qob") {
+            return Some(ReferenceTarget::Model(name.to_string()));
+        }
+        let mesh = || {
+            self.meshes.get(&lower).copied().map(ReferenceTarget::Mesh)
+        };
+        let dynamic_object = || {
+            self.dynamic_objects.get(&lower).copied()
+                .map(ReferenceTarget::DynamicObject)
+        };
+        match prefer {
+            Prefer::Mesh => mesh().or_else(dynamic_object),
+            Prefer::DynamicObject => dynamic_object().or_else(mesh),
+        }
+    }
+}
+
+impl Map {
+    /// The index into `Geometries::objects` of the first mesh called `name`,
+    /// ignoring case. Dynamic objects draw the mesh named by their `name`.
+    pub fn mesh_named(&self, name: &str) -> Option<usize> {
+        self.geometries.objects.iter()
+            .position(|mesh| mesh.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Resolve the names in dynamic objects to the meshes, other dynamic
+    /// objects and files they refer to. Empty names and "NONE" aren't
+    /// references.
+    ///
+    /// Glass holds a `PenetrationType` rather than a name. Halo names are the
+    /// names of the halos themselves.
+    pub fn references(&self) -> ReferenceGraph {
+        let resolver = Resolver::new(self);
+        let mut references = Vec::new();
+        let objects = &self.dynamic_objects.dynamic_objects;
+        for (object, dynamic) in objects.iter().enumerate() {
+            let mut add = |field, name: &str, prefer| {
+                if name.is_empty() || name.eq_ignore_ascii_case(NONE) {
+                    return;
+                }
+                references.push(Reference {
+                    object,
+                    field,
+                    name: name.to_string(),
+                    target: resolver.resolve(name, prefer),
+                });
+            };
+
+            add(ReferenceField::Name, &dynamic.name, Prefer::Mesh);
+            let common = match &dynamic.kind {
+                DynamicObjectKind::Dynamic { common, .. }
+                | DynamicObjectKind::Animation { common, .. }
+                | DynamicObjectKind::RepeatableTouchplate { common, .. } => {
+                    Some(common)
+                }
+                _ => None,
+            };
+            if let Some(common) = common {
+                add(ReferenceField::CommonName, &common.name, Prefer::Mesh);
+                add(ReferenceField::CommonName2, &common.name2, Prefer::Mesh);
+                for (i, sound) in common.sounds.iter().enumerate() {
+                    add(ReferenceField::Sounds(i), sound, Prefer::Mesh);
+                }
+            }
+
+            match &dynamic.kind {
+                DynamicObjectKind::Dynamic { params, .. } => {
+                    let names: Vec<_> = match params {
+                        KindDynamicParams::Struct(params) => {
+                            params.iter().map(|p| p.name.as_str()).collect()
+                        }
+                        KindDynamicParams::Flat { names, .. } => {
+                            names.iter().map(String::as_str).collect()
+                        }
+                    };
+                    for (i, name) in names.into_iter().enumerate() {
+                        add(ReferenceField::Params(i), name, Prefer::Mesh);
+                    }
+                }
+                DynamicObjectKind::Animation { names, name3, name4, .. } => {
+                    for (i, name) in names.iter().enumerate() {
+                        add(ReferenceField::Names(i), name, Prefer::Mesh);
+                    }
+                    add(ReferenceField::Name3, name3, Prefer::Mesh);
+                    add(ReferenceField::Name4, name4, Prefer::Mesh);
+                }
+                DynamicObjectKind::RepeatableTouchplate {
+                    attachments, names, name2, name3, ..
+                } => {
+                    for (i, name) in attachments.iter().enumerate() {
+                        add(ReferenceField::Attachments(i), name, Prefer::Mesh);
+                    }
+                    for (i, name) in names.iter().enumerate() {
+                        add(ReferenceField::Names(i), name, Prefer::Mesh);
+                    }
+                    add(ReferenceField::Name2, name2, Prefer::Mesh);
+                    add(ReferenceField::Name3, name3, Prefer::Mesh);
+                }
+                DynamicObjectKind::OneTimeTouchplate { attachments, .. } => {
+                    for (i, name) in attachments.iter().enumerate() {
+                        let field = ReferenceField::Attachments(i);
+                        add(field, name, Prefer::DynamicObject);
+                    }
+                }
+                DynamicObjectKind::Glass { .. }
+                | DynamicObjectKind::Halo { .. }
+                | DynamicObjectKind::StaticEffect => {}
+            }
+        }
+        ReferenceGraph { references }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+
+    #[test]
+    fn doors_resolve_to_meshes_sounds_and_touchplates() {
+        let map = crate::map::read(Path::new("data/map/rm19/rm19.map"))
+            .unwrap();
+        let graph = map.references();
+        let objects = &map.dynamic_objects.dynamic_objects;
+        let find = |name: &str| {
+            objects.iter().position(|o| o.section_name == name).unwrap()
+        };
+
+        let door = find("427_doorglass02");
+        let meshes: Vec<_> = graph.meshes(door).into_iter()
+            .map(|i| map.geometries.objects[i].name.as_str())
+            .collect();
+        assert_eq!(meshes, ["427_doorglass01", "427_doorglass02glass0"]);
+        let sounds: Vec<_> = graph.held_by(door)
+            .filter(|r| {
+                matches!(r.field, ReferenceField::Name2 | ReferenceField::Name3)
+            })
+            .map(|r| r.target.clone())
+            .collect();
+        assert_eq!(sounds, [
+            Some(ReferenceTarget::Sound("E_dmetop.wav".to_string())),
+            Some(ReferenceTarget::Sound("E_dmetcl.wav".to_string())),
+        ]);
+
+        let sliding = find("04_adoorslide01");
+        let plate = find("04_adtplate01");
+        let referrers: Vec<_> = graph.to(sliding).collect();
+        assert_eq!(referrers.len(), 1);
+        assert_eq!(referrers[0].object, plate);
+        assert_eq!(referrers[0].field, ReferenceField::Attachments(0));
+
+        // Only the spark effects of dynamic props name nothing in the MAP
+        for reference in graph.dangling() {
+            assert!(matches!(reference.field, ReferenceField::Params(_)),
+                "{reference}");
+            assert!(reference.name.starts_with("spark"), "{reference}");
+        }
+        assert!(graph.dangling().count() > 0);
+
+        // Sounds are outside the MAP, so they resolve without being checked
+        let external: Vec<_> = graph.external().collect();
+        assert!(external.iter().any(|r| r.name == "E_dmetop.wav"));
+        assert!(external.iter().all(|r| {
+            matches!(r.target, Some(ReferenceTarget::Sound(_)
+                | ReferenceTarget::Model(_)))
+        }));
+        assert!(external[0].to_string().ends_with("(unchecked)"));
+    }
+}
@@ -0,0 +1,439 @@
+use std::f32::consts::TAU;
+
+use serde::Serialize;
+
+use super::{
+    Aabb, AnimationType, DynamicObject, DynamicObjectKind, Map,
+    TransformationMatrix, Vec3f, Vec6f,
+};
+use crate::vec3::{add, length, scale};
+
+/// How a dynamic object moves, decoded from its `animation_type` and
+/// `direction`.
+///
+/// The three `direction` floats aren't a direction. In every shipped map they
+/// read as (rate, extent, sign). Rotations are in radians and slides in map
+/// units, with rates taken to be per second. Swinging doors have a rate of π
+/// or 3.49 and an extent of ±π/2 or ±1.75, fans a rate of π/2 and an extent
+/// of 2π, and sliding doors a rate of 45 and an extent of 280 or 140. The
+/// sign is ±1 for sliding doors and 0 otherwise. `distance` and `velocity`
+/// are 1, 0 or -1 flags that don't change the motion as far as anyone has
+/// worked out, so they're ignored.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub enum Motion {
+    /// Swing about the object's own Y axis, its hinge, by `angle`
+    Swing { angle: f32, speed: f32 },
+
+    /// Slide by `offset`, in map space, along the object's own X axis.
+    ///
+    /// The sign is relative to the map's X axis rather than the object's:
+    /// the two doors of each shipped pair are turned 180° from each other,
+    /// and only in map space do their signs both point away from the gap.
+    /// So the object's X axis is turned to face map +X before the sign
+    /// applies. Every shipped sliding door sits in a wall along X, so for a
+    /// door whose X axis runs along map Z, which way it opens is a guess.
+    Slide { offset: [f32; 3], speed: f32 },
+
+    /// Spin for as long as the map runs. It turns about the object's own Z
+    /// axis, which is a guess from the mesh: the shipped fans are flat in
+    /// their X-Y plane.
+    Spin { speed: f32 },
+}
+
+impl Motion {
+    /// The motion of an `Animation` or `RepeatableTouchplate`, or `None`
+    /// for other kinds and unknown animation types
+    pub fn from_object(object: &DynamicObject) -> Option<Self> {
+        let (animation_type, direction) = match &object.kind {
+            DynamicObjectKind::Animation {
+                animation_type, direction, ..
+            } => (animation_type, direction),
+            DynamicObjectKind::RepeatableTouchplate {
+                animation_type, direction, ..
+            } => (animation_type, direction),
+            _ => return None,
+        };
+        let Vec3f { x: speed, y: extent, z: sign } = *direction;
+        Some(match animation_type {
+            AnimationType::OneTimeRotation => {
+                Self::Swing { angle: extent, speed }
+            }
+            AnimationType::OneTimeTranslation => {
+                let x = columns(&object.tm)[0];
+                let axis = if x[0] < 0.0 { scale(x, -1.0) } else { x };
+                Self::Slide { offset: scale(axis, sign * extent), speed }
+            }
+            AnimationType::LoopRotation => Self::Spin { speed },
+            AnimationType::Other(_) => return None,
+        })
+    }
+
+    /// Seconds to go from closed to fully open. Zero for spins, and for
+    /// motions without a usable rate, which happen instantly.
+    pub fn duration(&self) -> f32 {
+        let (extent, speed) = match *self {
+            Self::Swing { angle, speed } => (angle.abs(), speed),
+            Self::Slide { offset, speed } => (length(offset), speed),
+            Self::Spin { .. } => return 0.0,
+        };
+        if speed.is_finite() && speed > 0.0 {
+            extent / speed
+        } else {
+            0.0
+        }
+    }
+
+    /// `base` moved `progress` of the way open. For spins `progress` is the
+    /// angle turned, in radians.
+    pub fn transform(&self, base: &TransformationMatrix, progress: f32)
+        -> TransformationMatrix
+    {
+        let [x, y, z, position] = columns(base);
+        let [x, y, z, position] = match *self {
+            Self::Swing { angle, .. } => {
+                let (sin, cos) = (angle * progress).sin_cos();
+                let x2 = add(scale(x, cos), scale(z, -sin));
+                let z2 = add(scale(x, sin), scale(z, cos));
+                [x2, y, z2, position]
+            }
+            Self::Slide { offset, .. } => {
+                [x, y, z, add(position, scale(offset, progress))]
+            }
+            Self::Spin { .. } => {
+                let (sin, cos) = progress.sin_cos();
+                let x2 = add(scale(x, cos), scale(y, sin));
+                let y2 = add(scale(x, -sin), scale(y, cos));
+                [x2, y2, z, position]
+            }
+        };
+        TransformationMatrix {
+            x_axis: vec3f(x),
+            y_axis: vec3f(y),
+            z_axis: vec3f(z),
+            position: vec3f(position),
+        }
+    }
+}
+
+/// A dynamic object that moves, with where it is in its motion
+#[derive(Clone, Debug, Serialize)]
+pub struct Mover {
+    /// Index into `DynamicObjects::dynamic_objects`
+    pub object: usize,
+    /// Index into `Geometries::objects` of the mesh that moves, the object
+    /// with the same name as the dynamic object
+    pub mesh: Option<usize>,
+    pub motion: Motion,
+    open: bool,
+    progress: f32,
+}
+
+impl Mover {
+    /// True once told to open, until told to close. Spins are always open.
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    /// How far open from 0 to 1, or the angle turned for spins
+    pub fn progress(&self) -> f32 {
+        self.progress
+    }
+
+    fn advance(&mut self, dt: f32) {
+        if let Motion::Spin { speed } = self.motion {
+            self.progress = (self.progress + speed * dt).rem_euclid(TAU);
+            return;
+        }
+        let target = if self.open { 1.0 } else { 0.0 };
+        let duration = self.motion.duration();
+        if duration <= 0.0 {
+            self.progress = target;
+        } else if self.progress < target {
+            self.progress = (self.progress + dt / duration).min(target);
+        } else {
+            self.progress = (self.progress - dt / duration).max(target);
+        }
+    }
+}
+
+/// A `OneTimeTouchplate`: a box that opens its doors the first time
+/// something enters it
+#[derive(Clone, Debug, Serialize)]
+pub struct Touchplate {
+    /// Index into `DynamicObjects::dynamic_objects`
+    pub object: usize,
+    /// The trigger volume in map space
+    pub bounds: Aabb,
+    /// Indices into `Simulation::movers` of the doors it opens. Attachments
+    /// naming no mover are left out.
+    pub doors: Vec<usize>,
+    /// Whether it has been set off
+    pub fired: bool,
+}
+
+/// Doors, touchplates and fans of a `Map` played forward in time, for
+/// simulating what a player would see and bump into.
+///
+/// Doors start closed. `touch` sets off one-time touchplates, `set_open`
+/// opens and closes doors directly as a player using them would, and
+/// `advance` moves everything on.
+#[derive(Clone, Debug)]
+pub struct Simulation<'a> {
+    map: &'a Map,
+    time: f32,
+    movers: Vec<Mover>,
+    touchplates: Vec<Touchplate>,
+}
+
+impl<'a> Simulation<'a> {
+    pub fn new(map: &'a Map) -> Self {
+        let objects = &map.dynamic_objects.dynamic_objects;
+        let movers: Vec<_> = objects.iter().enumerate()
+            .filter_map(|(object, dynamic)| {
+                let motion = Motion::from_object(dynamic)?;
+                let mesh = map.mesh_named(&dynamic.name);
+                let open = matches!(motion, Motion::Spin { .. });
+                Some(Mover { object, mesh, motion, open, progress: 0.0 })
+            })
+            .collect();
+
+        let touchplates = objects.iter().enumerate()
+            .filter_map(|(object, dynamic)| {
+                let DynamicObjectKind::OneTimeTouchplate {
+                    coordinates, attachments, ..
+                } = &dynamic.kind else {
+                    return None;
+                };
+                let doors = attachments.iter()
+                    .filter_map(|attachment| movers.iter().position(|mover| {
+                        objects[mover.object].section_name
+                            .eq_ignore_ascii_case(attachment)
+                    }))
+                    .collect();
+                let bounds = trigger_bounds(&dynamic.tm, coordinates);
+                Some(Touchplate { object, bounds, doors, fired: false })
+            })
+            .collect();
+
+        Self { map, time: 0.0, movers, touchplates }
+    }
+
+    /// Seconds advanced since the start
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+
+    pub fn movers(&self) -> &[Mover] {
+        &self.movers
+    }
+
+    pub fn touchplates(&self) -> &[Touchplate] {
+        &self.touchplates
+    }
+
+    /// The mover for `DynamicObjects::dynamic_objects[object]`, if it moves
+    pub fn mover_for(&self, object: usize) -> Option<usize> {
+        self.movers.iter().position(|mover| mover.object == object)
+    }
+
+    /// Open or close a door. It moves over the following `advance` calls.
+    pub fn set_open(&mut self, mover: usize, open: bool) {
+        let mover = &mut self.movers[mover];
+        if !matches!(mover.motion, Motion::Spin { .. }) {
+            mover.open = open;
+        }
+    }
+
+    /// Set off every touchplate that hasn't fired and contains `position`,
+    /// opening its doors. Returns the touchplates that fired.
+    pub fn touch(&mut self, position: [f32; 3]) -> Vec<usize> {
+        let mut fired = Vec::new();
+        for (i, plate) in self.touchplates.iter_mut().enumerate() {
+            if plate.fired || !plate.bounds.contains(position) {
+                continue;
+            }
+            plate.fired = true;
+            for &door in &plate.doors {
+                self.movers[door].open = true;
+            }
+            fired.push(i);
+        }
+        fired
+    }
+
+    /// Move time on by `dt` seconds
+    pub fn advance(&mut self, dt: f32) {
+        self.time += dt;
+        for mover in &mut self.movers {
+            mover.advance(dt);
+        }
+    }
+
+    /// Where a mover is now
+    pub fn transform(&self, mover: usize) -> TransformationMatrix {
+        let mover = &self.movers[mover];
+        let base = &self.map.dynamic_objects.dynamic_objects[mover.object].tm;
+        mover.motion.transform(base, mover.progress)
+    }
+
+    /// The map-space vertices of a mover's mesh where it is now. Empty when
+    /// the mesh wasn't found.
+    pub fn vertices(&self, mover: usize) -> Vec<[f32; 3]> {
+        let Some(mesh) = self.movers[mover].mesh else {
+            return Vec::new();
+        };
+        let tm = self.transform(mover);
+        self.map.geometries.objects[mesh].vertices.iter()
+            .map(|v| tm.transform_point([v.x, v.y, v.z]))
+            .collect()
+    }
+
+    /// The space a mover takes up now, which is what it blocks or pushes
+    /// aside. The meshes of dynamic objects have no collision lists in the
+    /// shipped maps, so this bounds the visible mesh.
+    pub fn collision(&self, mover: usize) -> Option<Aabb> {
+        Aabb::from_points(self.vertices(mover))
+    }
+}
+
+/// A touchplate's `coordinates` are two opposite corners relative to its
+/// transform
+fn trigger_bounds(tm: &TransformationMatrix, corners: &Vec6f) -> Aabb {
+    let Vec6f { x1, y1, z1, x2, y2, z2 } = *corners;
+    let points = [x1, x2].into_iter().flat_map(|x| {
+        [y1, y2].into_iter().flat_map(move |y| {
+            [z1, z2].into_iter().map(move |z| [x, y, z])
+        })
+    });
+    Aabb::from_points(points.map(|p| tm.transform_point(p)))
+        .expect("a box has corners")
+}
+
+fn columns(tm: &TransformationMatrix) -> [[f32; 3]; 4] {
+    [&tm.x_axis, &tm.y_axis, &tm.z_axis, &tm.position].map(|v| [v.x, v.y, v.z])
+}
+
+fn vec3f([x, y, z]: [f32; 3]) -> Vec3f {
+    Vec3f { x, y, z }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::FRAC_PI_2;
+    use std::path::Path;
+
+    use super::*;
+
+    fn rm19() -> Map {
+        crate::map::read(Path::new("data/map/rm19/rm19.map")).unwrap()
+    }
+
+    fn mover_named(sim: &Simulation<'_>, name: &str) -> usize {
+        let objects = &sim.map.dynamic_objects.dynamic_objects;
+        sim.movers().iter()
+            .position(|m| objects[m.object].section_name == name)
+            .unwrap()
+    }
+
+    #[test]
+    fn swing_turns_about_the_hinge() {
+        let base = TransformationMatrix {
+            x_axis: vec3f([1.0, 0.0, 0.0]),
+            y_axis: vec3f([0.0, 1.0, 0.0]),
+            z_axis: vec3f([0.0, 0.0, 1.0]),
+            position: vec3f([10.0, 0.0, 0.0]),
+        };
+        let motion = Motion::Swing { angle: FRAC_PI_2, speed: FRAC_PI_2 };
+        assert_eq!(motion.duration(), 1.0);
+        let open = motion.transform(&base, 1.0);
+        let point = open.transform_point([0.0, 5.0, -2.0]);
+        let expected = [8.0, 5.0, 0.0];
+        for (a, b) in point.iter().zip(expected) {
+            assert!((a - b).abs() < 1e-5, "{point:?}");
+        }
+    }
+
+    #[test]
+    fn slides_follow_the_object_x_axis() {
+        let map = rm19();
+        let mut door = map.dynamic_objects.dynamic_objects.iter()
+            .find(|object| object.section_name == "04_adoorslide02")
+            .unwrap()
+            .clone();
+        let offset = |door: &DynamicObject| match Motion::from_object(door) {
+            Some(Motion::Slide { offset, .. }) => offset,
+            motion => panic!("{motion:?}"),
+        };
+        let close = |a: [f32; 3], b: [f32; 3]| {
+            a.iter().zip(b).all(|(a, b)| (a - b).abs() < 1e-3)
+        };
+
+        // Turned 180° with a sign of -1, so it opens towards map -X
+        assert_eq!(door.tm.x_axis.x, -1.0);
+        assert!(close(offset(&door), [-280.0, 0.0, 0.0]));
+
+        // The same door in a wall along Z slides along Z
+        door.tm.x_axis = vec3f([0.0, 0.0, -1.0]);
+        door.tm.z_axis = vec3f([1.0, 0.0, 0.0]);
+        assert!(close(offset(&door), [0.0, 0.0, 280.0]));
+    }
+
+    #[test]
+    fn touchplates_open_sliding_doors_apart() {
+        let map = rm19();
+        let mut sim = Simulation::new(&map);
+        assert!(sim.movers().iter().all(|m| m.mesh.is_some()));
+        assert!(sim.touchplates().iter().all(|p| p.doors.len() == 2));
+
+        let left = mover_named(&sim, "04_adoorslide02");
+        let right = mover_named(&sim, "04_adoorslide01");
+        let closed = [sim.collision(left), sim.collision(right)]
+            .map(Option::unwrap);
+        assert!(closed[0].intersects(&closed[1]));
+
+        let outside = sim.touch([0.0, 0.0, 0.0]);
+        assert!(outside.is_empty());
+        let doorway = [28862.0, 32900.0, 34986.0];
+        let fired = sim.touch(doorway);
+        assert_eq!(fired.len(), 1);
+        assert_eq!(sim.touchplates()[fired[0]].doors, [right, left]);
+        assert!(sim.touch(doorway).is_empty());
+
+        let duration = sim.movers()[left].motion.duration();
+        sim.advance(duration / 2.0);
+        assert!((sim.movers()[left].progress() - 0.5).abs() < 1e-5);
+        sim.advance(duration);
+        let open = [sim.collision(left), sim.collision(right)]
+            .map(Option::unwrap);
+        assert_eq!(open[0].max[0], closed[0].max[0] - 280.0);
+        assert_eq!(open[1].min[0], closed[1].min[0] + 280.0);
+        assert!(!open[0].intersects(&open[1]));
+        assert_eq!(open[0].min[1], closed[0].min[1]);
+    }
+
+    #[test]
+    fn repeatable_doors_close_again_and_fans_keep_turning() {
+        let map = rm19();
+        let mut sim = Simulation::new(&map);
+        let door = mover_named(&sim, "425_doorintgray01");
+        let closed = sim.collision(door).unwrap();
+
+        sim.set_open(door, true);
+        sim.advance(10.0);
+        assert_eq!(sim.movers()[door].progress(), 1.0);
+        assert_ne!(sim.collision(door), Some(closed));
+        sim.set_open(door, false);
+        sim.advance(10.0);
+        assert_eq!(sim.movers()[door].progress(), 0.0);
+        assert_eq!(sim.collision(door), Some(closed));
+
+        let fan = mover_named(&sim, "300_animfan");
+        sim.set_open(fan, false);
+        assert!(sim.movers()[fan].is_open());
+        let before = sim.movers()[fan].progress();
+        sim.advance(1.0);
+        let turned = sim.movers()[fan].progress() - before;
+        assert!((turned.rem_euclid(TAU) - FRAC_PI_2).abs() < 1e-4);
+        assert_eq!(sim.time(), 21.0);
+    }
+}
@@ -0,0 +1,381 @@
+use std::collections::HashSet;
+use std::fmt;
+
+use serde::Serialize;
+
+use super::{FaceNormal, Map, NormalCoord, ObjectData};
+
+/// How far a normal's length may drift from 1.0 before it's reported. Every
+/// shipped map stays within a few ULPs of this.
+const NORMAL_TOLERANCE: f32 = 1e-3;
+
+/// Every problem found by `Map::validate`. Indices are positions in the
+/// in-memory lists, e.g. `object` is an index into `Geometries::objects` and
+/// `data` is an index into that object's `object_datas`.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct ValidationReport {
+    pub issues: Vec<ValidationIssue>,
+}
+
+impl ValidationReport {
+    /// True when no issues were found
+    pub fn is_ok(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+impl fmt::Display for ValidationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_ok() {
+            return write!(f, "no issues");
+        }
+        for issue in &self.issues {
+            writeln!(f, "{issue}")?;
+        }
+        write!(f, "{} issues", self.issues.len())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub enum ValidationIssue {
+    /// A `Faces::face_indices` entry points past `Object::vertices`
+    FaceIndexOutOfRange {
+        object: usize,
+        data: usize,
+        face: usize,
+        index: u16,
+        vertex_count: usize,
+    },
+
+    /// A `Faces::texture_indices` entry points past the `TextureVertices` of
+    /// the same `ObjectData`
+    TextureIndexOutOfRange {
+        object: usize,
+        data: usize,
+        face: usize,
+        index: u16,
+        texture_vertex_count: usize,
+    },
+
+    /// `ObjectData::mn` isn't a material index nor `ObjectData::NO_MATERIAL`
+    MissingMaterial {
+        object: usize,
+        data: usize,
+        material: u32,
+        material_count: usize,
+    },
+
+    /// A `Tag::coord1` entry points past `Object::vertices`
+    TagVertexOutOfRange {
+        object: usize,
+        tag: usize,
+        index: u16,
+        vertex_count: usize,
+    },
+
+    /// `Tag::face_index_1` points past the faces of all the object's
+    /// `ObjectData`
+    TagFaceOutOfRange {
+        object: usize,
+        tag: usize,
+        face: u16,
+        face_count: usize,
+    },
+
+    /// `Portal::room` or `Portal::opposite_room` names a room that doesn't
+    /// exist
+    PortalRoomMissing {
+        portal: usize,
+        room: u32,
+    },
+
+    /// A `PlanningLevel::room_names` entry names a room that doesn't exist
+    PlanningLevelRoomMissing {
+        level: usize,
+        room: String,
+    },
+
+    /// A face normal in `Faces::normals` isn't unit length
+    FaceNormalNotUnit {
+        object: usize,
+        data: usize,
+        face: usize,
+        length: f32,
+    },
+
+    /// A vertex normal in `TextureVertices::normals` isn't unit length
+    VertexNormalNotUnit {
+        object: usize,
+        data: usize,
+        vertex: usize,
+        length: f32,
+    },
+
+    /// A face normal in `Collisions::faces` isn't unit length
+    CollisionNormalNotUnit {
+        object: usize,
+        face: usize,
+        length: f32,
+    },
+}
+
+impl fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::FaceIndexOutOfRange {
+                object, data, face, index, vertex_count
+            } => write!(f, "object {object} data {data} face {face}: \
+                vertex index {index} >= vertex count {vertex_count}"),
+            Self::TextureIndexOutOfRange {
+                object, data, face, index, texture_vertex_count
+            } => write!(f, "object {object} data {data} face {face}: \
+                texture index {index} >= texture vertex count \
+                {texture_vertex_count}"),
+            Self::MissingMaterial {
+                object, data, material, material_count
+            } => write!(f, "object {object} data {data}: material {material} \
+                >= material count {material_count}"),
+            Self::TagVertexOutOfRange { object, tag, index, vertex_count } => {
+                write!(f, "object {object} tag {tag}: vertex index {index} >= \
+                    vertex count {vertex_count}")
+            }
+            Self::TagFaceOutOfRange { object, tag, face, face_count } => {
+                write!(f, "object {object} tag {tag}: face index {face} >= \
+                    face count {face_count}")
+            }
+            Self::PortalRoomMissing { portal, room } => {
+                write!(f, "portal {portal}: room {room} does not exist")
+            }
+            Self::PlanningLevelRoomMissing { level, room } => {
+                write!(f, "planning level {level}: room '{room}' does not exist")
+            }
+            Self::FaceNormalNotUnit { object, data, face, length } => {
+                write!(f, "object {object} data {data} face {face}: normal \
+                    length {length}")
+            }
+            Self::VertexNormalNotUnit { object, data, vertex, length } => {
+                write!(f, "object {object} data {data} vertex {vertex}: normal \
+                    length {length}")
+            }
+            Self::CollisionNormalNotUnit { object, face, length } => {
+                write!(f, "object {object} collision face {face}: normal \
+                    length {length}")
+            }
+        }
+    }
+}
+
+impl Map {
+    /// Check the cross references and invariants that the reader can't check
+    /// while parsing: indices into vertex, texture vertex, material and room
+    /// lists, and that normals are unit length.
+    pub fn validate(&self) -> ValidationReport {
+        let mut issues = Vec::new();
+        self.validate_geometry(&mut issues);
+        self.validate_rooms(&mut issues);
+        ValidationReport { issues }
+    }
+
+    fn validate_geometry(&self, issues: &mut Vec<ValidationIssue>) {
+        let material_count = self.materials.materials.len();
+
+        for (object, obj) in self.geometries.objects.iter().enumerate() {
+            let vertex_count = obj.vertices.len();
+
+            for (data, object_data) in obj.object_datas.iter().enumerate() {
+                let material = object_data.mn;
+                if material != ObjectData::NO_MATERIAL
+                    && material as usize >= material_count
+                {
+                    issues.push(ValidationIssue::MissingMaterial {
+                        object, data, material, material_count,
+                    });
+                }
+
+                let faces = &object_data.faces;
+                for (face, &(p1, p2, p3)) in faces.face_indices.iter()
+                    .enumerate()
+                {
+                    for index in [p1, p2, p3] {
+                        if index as usize >= vertex_count {
+                            issues.push(ValidationIssue::FaceIndexOutOfRange {
+                                object, data, face, index, vertex_count,
+                            });
+                        }
+                    }
+                }
+
+                let texture_vertex_count = object_data.texture_vertices
+                    .uv_coords.len();
+                for (face, &(p1, p2, p3)) in faces.texture_indices.iter()
+                    .enumerate()
+                {
+                    for index in [p1, p2, p3] {
+                        if index as usize >= texture_vertex_count {
+                            issues.push(
+                                ValidationIssue::TextureIndexOutOfRange {
+                                    object, data, face, index,
+                                    texture_vertex_count,
+                                }
+                            );
+                        }
+                    }
+                }
+
+                for (face, normal) in faces.normals.iter().enumerate() {
+                    if let Some(length) = face_normal_error(normal) {
+                        issues.push(ValidationIssue::FaceNormalNotUnit {
+                            object, data, face, length,
+                        });
+                    }
+                }
+
+                let normals = &object_data.texture_vertices.normals;
+                for (vertex, normal) in normals.iter().enumerate() {
+                    if let Some(length) = normal_coord_error(normal) {
+                        issues.push(ValidationIssue::VertexNormalNotUnit {
+                            object, data, vertex, length,
+                        });
+                    }
+                }
+            }
+
+            for (face, normal) in obj.collisions.faces.iter().enumerate() {
+                if let Some(length) = face_normal_error(normal) {
+                    issues.push(ValidationIssue::CollisionNormalNotUnit {
+                        object, face, length,
+                    });
+                }
+            }
+
+            // Only `coord1` and `face_index_1` are checked. The second pair
+            // doesn't index anything known; `face_index_2` is often 0xFFFF.
+            let face_count = obj.object_datas.iter()
+                .map(|data| data.faces.normals.len())
+                .sum();
+            for (tag, t) in obj.tags.iter().enumerate() {
+                let (p1, p2, p3) = t.coord1;
+                for index in [p1, p2, p3] {
+                    if index as usize >= vertex_count {
+                        issues.push(ValidationIssue::TagVertexOutOfRange {
+                            object, tag, index, vertex_count,
+                        });
+                    }
+                }
+                if t.face_index_1 as usize >= face_count {
+                    issues.push(ValidationIssue::TagFaceOutOfRange {
+                        object, tag, face: t.face_index_1, face_count,
+                    });
+                }
+            }
+        }
+    }
+
+    fn validate_rooms(&self, issues: &mut Vec<ValidationIssue>) {
+        // Rooms are identified by their section name. Portals refer to them
+        // by number and planning levels by name.
+        let rooms = self.rooms.rooms.iter()
+            .map(|room| room.section_name.as_str())
+            .collect::<HashSet<_>>();
+
+        for (portal, p) in self.portals.portals.iter().enumerate() {
+            for room in [p.room, p.opposite_room] {
+                if !rooms.contains(room.to_string().as_str()) {
+                    issues.push(ValidationIssue::PortalRoomMissing {
+                        portal, room,
+                    });
+                }
+            }
+        }
+
+        for (level, l) in self.planning_levels.levels.iter().enumerate() {
+            for room in &l.room_names {
+                if !rooms.contains(room.as_str()) {
+                    issues.push(ValidationIssue::PlanningLevelRoomMissing {
+                        level, room: room.clone(),
+                    });
+                }
+            }
+        }
+    }
+}
+
+/// The normal's length when it isn't within tolerance of 1.0
+fn face_normal_error(normal: &FaceNormal) -> Option<f32> {
+    unit_length_error(normal.x, normal.y, normal.z)
+}
+
+/// The normal's length when it isn't within tolerance of 1.0
+fn normal_coord_error(normal: &NormalCoord) -> Option<f32> {
+    unit_length_error(normal.x, normal.y, normal.z)
+}
+
+fn unit_length_error(x: f32, y: f32, z: f32) -> Option<f32> {
+    let length = (x * x + y * y + z * z).sqrt();
+    if !length.is_finite() || (length - 1.0).abs() > NORMAL_TOLERANCE {
+        Some(length)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+    use crate::map;
+
+    #[test]
+    fn shipped_maps_are_valid() {
+        for path in [
+            "data/map/m00/citystreet_large.map",
+            "data/map/rm19/rm19.map",
+        ] {
+            let map = map::read(Path::new(path)).unwrap();
+            let report = map.validate();
+            assert!(report.is_ok(), "{path}: {report}");
+        }
+    }
+
+    #[test]
+    fn broken_references_are_reported() {
+        let path = Path::new("data/map/m00/citystreet_large.map");
+        let mut map = map::read(path).unwrap();
+
+        let vertex_count = map.geometries.objects[0].vertices.len();
+        let data = &mut map.geometries.objects[0].object_datas[0];
+        data.faces.face_indices[0].1 = vertex_count as u16;
+        data.mn = 9999;
+        map.portals.portals[0].opposite_room = 9999;
+        map.planning_levels.levels[0].room_names.push("nowhere".into());
+
+        let issues = map.validate().issues;
+        assert_eq!(issues.len(), 4, "{issues:#?}");
+        assert!(issues.contains(&ValidationIssue::FaceIndexOutOfRange {
+            object: 0, data: 0, face: 0, index: vertex_count as u16,
+            vertex_count,
+        }));
+        assert!(issues.contains(&ValidationIssue::MissingMaterial {
+            object: 0, data: 0, material: 9999, material_count: 56,
+        }));
+        assert!(issues.contains(&ValidationIssue::PortalRoomMissing {
+            portal: 0, room: 9999,
+        }));
+        assert!(issues.contains(&ValidationIssue::PlanningLevelRoomMissing {
+            level: 0, room: "nowhere".into(),
+        }));
+    }
+
+    #[test]
+    fn nan_normals_are_reported() {
+        let path = Path::new("data/map/m00/citystreet_large.map");
+        let mut map = map::read(path).unwrap();
+        map.geometries.objects[0].object_datas[0].faces.normals[0].x = f32::NAN;
+
+        let issues = map.validate().issues;
+        assert!(matches!(issues[..], [ValidationIssue::FaceNormalNotUnit {
+            object: 0, data: 0, face: 0, length,
+        }] if length.is_nan()), "{issues:#?}");
+    }
+}
@@ -1,89 +1,283 @@
 use std::fs::File;
-use std::io::{BufReader, Cursor, Read};
+use std::io::{BufReader, BufWriter, Cursor, Read, Write};
 use std::path::{Path, PathBuf};
 
 use anyhow::Context;
-use byteorder::{LE, ReadBytesExt};
+use byteorder::{LE, ReadBytesExt, WriteBytesExt};
+
+use crate::limits::{Limits, check_remaining};
 
 pub fn read(filename: &Path) -> anyhow::Result<Rsb> {
     let file = File::open(filename).context("could not open RSB file")?;
     let mut reader = BufReader::new(file);
     let mut buf = Vec::new();
     reader.read_to_end(&mut buf).context("failed to read RSB file")?;
-    let mut buf = Cursor::new(buf);
-
-    let mut rsb = Rsb::default();
+    let mut rsb = read_bytes(&buf)?;
     rsb.filename = filename.to_path_buf();
+    Ok(rsb)
+}
 
-    rsb.version = buf.read_u32::<LE>()?;
-    // Only handle Rainbow Six and Rogue Spear
-    if rsb.version >= 2 {
-        anyhow::bail!("RSB version {} not supported", rsb.version);
-    }
-
-    rsb.width = buf.read_u32::<LE>()?;
-    rsb.height = buf.read_u32::<LE>()?;
-    rsb.palette = if rsb.version == 0 {
-        let palette = buf.read_u32::<LE>()?;
-        if palette == 0 {
-            rsb.bitmask = BitMask::try_new(&mut buf)?;
-        } else if palette == 1 {
-            // Read the 256 palette colors
-            let mut tmp = vec![0u8; 256 * std::mem::size_of::<u32>()];
-            let mut colors = Vec::with_capacity(256);
-            buf.read_exact(&mut tmp)?;
-            for w in tmp.windows(4) {
-                let b = w[0];
-                let g = w[1];
-                let r = w[2];
-                let a = w[3];
-                colors.push(PaletteColor::new(b, g, r, a));
-            }
-            rsb.palette_colors = Some(colors);
+/// Parse an RSB already in memory with the default `Limits`.
+/// `Rsb::filename` is left empty.
+pub fn read_bytes(bytes: &[u8]) -> anyhow::Result<Rsb> {
+    read_bytes_with_limits(bytes, &Limits::default())
+}
+
+/// `read_bytes` with the dimensions checked against `limits`
+pub fn read_bytes_with_limits(
+    bytes: &[u8],
+    limits: &Limits,
+) -> anyhow::Result<Rsb> {
+    let mut buf = Cursor::new(bytes);
+    let remaining = |buf: &Cursor<&[u8]>| {
+        bytes.len().saturating_sub(buf.position() as usize)
+    };
+
+    let header = Header::read(&mut buf, limits)?;
+    let has_palette = header.has_palette();
+    let mut rsb = Rsb {
+        version: header.version,
+        width: header.width,
+        height: header.height,
+        palette: header.palette,
+        palette_colors: header.palette_colors,
+        bitmask: header.bitmask,
+        ..Default::default()
+    };
+
+    let size = rsb.width as usize * rsb.height as usize;
+    let texel_size = if has_palette { 1 } else { 2 };
+    check_remaining(size, texel_size, remaining(&buf), "pixels")?;
+    rsb.pixels = if has_palette {
+        // Read the palette color indices
+        let mut indices = vec![0; size];
+        buf.read_exact(&mut indices)?;
+        Pixels::Indexed(indices)
+    } else {
+        // Read either ARGB or BGRA pixel data
+        let mut values = vec![0; size];
+        buf.read_u16_into::<LE>(&mut values)?;
+        if rsb.bitmask.is_argb() {
+            // TODO: 32-bit pixels are still read 16 bits at a time
+            Pixels::Argb32(values.into_iter().map(u32::from).collect())
         } else {
-            let path = rsb.filename.display();
-            anyhow::bail!("{path}: palette {palette} is unhandled");
+            // TODO(simplify?) Rogue Spear RSBs are only 16-bit BGRA
+            Pixels::Bgra16(values)
         }
-        Some(palette)
-    } else {
-        rsb.bitmask = BitMask::try_new(&mut buf)?;
-        None
     };
 
-    let size = (rsb.width * rsb.height) as usize;
-    rsb.pixels = Vec::with_capacity(size);
-    for _ in 0..size {
-        let pixel = if rsb.version == 0 && rsb.palette.is_some_and(|x| x == 1) {
-            // Read the palette color index
-            let mut tmp = [0u8; 1];
-            buf.read_exact(&mut tmp)?;
-            Pixel::PaletteColorIndex(tmp[0])
-        } else {
-            // Read either ARGB or BGRA pixel data
-            // TODO: convert to one pixel format?
-            let value = buf.read_u16::<LE>()?.into();
-            if rsb.bitmask.is_argb() {
-                Pixel::Argb(value)
+    if has_palette {
+        rsb.bitmask = BitMask::try_new(&mut buf)?;
+
+        check_remaining(size, 2, remaining(&buf), "masked pixels")?;
+        let mut values = vec![0; size];
+        buf.read_u16_into::<LE>(&mut values)?;
+        rsb.masked_pixels = Some(values.into_iter().map(MaskedPixel).collect());
+    }
+
+    rsb.color_model = ColorModel::for_bitmask(&rsb.bitmask);
+    Ok(rsb)
+}
+
+/// Everything in an RSB before the pixel data
+#[derive(Clone, Debug, Default)]
+pub struct Header {
+    /// See `Rsb::version`
+    pub version: u32,
+    pub width: u32,
+    pub height: u32,
+    /// See `Rsb::palette`
+    pub palette: Option<u32>,
+    pub palette_colors: Option<Vec<PaletteColor>>,
+    /// The layout of `Rsb::pixels`. Palette RSBs have no bitmask here; theirs
+    /// comes after the palette indices.
+    pub bitmask: BitMask,
+}
+
+impl Header {
+    /// Read the header, rejecting dimensions beyond `limits`
+    pub fn read(buf: &mut impl Read, limits: &Limits) -> anyhow::Result<Self> {
+        let mut header = Self {
+            version: buf.read_u32::<LE>()?,
+            ..Default::default()
+        };
+        // Only handle Rainbow Six and Rogue Spear
+        if header.version >= 2 {
+            anyhow::bail!("RSB version {} not supported", header.version);
+        }
+
+        header.width = buf.read_u32::<LE>()?;
+        header.height = buf.read_u32::<LE>()?;
+        limits.check_texture(header.width, header.height)?;
+        header.palette = if header.version == 0 {
+            let palette = buf.read_u32::<LE>()?;
+            if palette == 0 {
+                header.bitmask = BitMask::try_new(buf)?;
+            } else if palette == 1 {
+                // Read the 256 palette colors
+                let mut tmp = vec![0u8; 256 * std::mem::size_of::<u32>()];
+                let mut colors = Vec::with_capacity(256);
+                buf.read_exact(&mut tmp)?;
+                for w in tmp.chunks_exact(4) {
+                    let b = w[0];
+                    let g = w[1];
+                    let r = w[2];
+                    let a = w[3];
+                    colors.push(PaletteColor::new(b, g, r, a));
+                }
+                header.palette_colors = Some(colors);
             } else {
-                // TODO(simplify?) Rogue Spear RSBs are only 16-bit BGRA
-                Pixel::Bgra(value)
+                anyhow::bail!("palette {palette} is unhandled");
             }
+            Some(palette)
+        } else {
+            header.bitmask = BitMask::try_new(buf)?;
+            None
         };
-        rsb.pixels.push(pixel);
+        Ok(header)
     }
 
-    if rsb.version == 0 && rsb.palette.is_some_and(|x| x == 1) {
-        rsb.bitmask = BitMask::try_new(&mut buf)?;
+    /// Whether the pixels are 8-bit palette indices followed by a second
+    /// 16-bit image, see `Rsb::masked_pixels`
+    fn has_palette(&self) -> bool {
+        self.version == 0 && self.palette == Some(1)
+    }
+}
+
+/// Decodes an RSB a row at a time from any reader, into the same 8-bit RGBA
+/// as `Rsb::to_rgba8`. Memory use is one row of raw pixels regardless of the
+/// texture size, so it suits large textures and batch conversion.
+///
+/// Palette RSBs decode their full-colour `masked_pixels`; the palette
+/// indices before them are read through and discarded.
+pub struct RowDecoder<R> {
+    reader: R,
+    header: Header,
+    /// Decodes the rows' layout
+    unpacker: Unpacker,
+    /// The next row to decode
+    row: u32,
+    raw: Vec<u8>,
+}
+
+impl<R: Read> RowDecoder<R> {
+    /// Read the header, leaving the reader at the first row
+    pub fn new(reader: R) -> anyhow::Result<Self> {
+        Self::with_limits(reader, &Limits::default())
+    }
+
+    /// `new` with the dimensions checked against `limits`
+    pub fn with_limits(mut reader: R, limits: &Limits) -> anyhow::Result<Self> {
+        let header = Header::read(&mut reader, limits).context("RSB header")?;
+        Ok(Self {
+            reader,
+            unpacker: Unpacker::for_pixels(&header.bitmask),
+            raw: vec![0; header.width as usize * 2],
+            header,
+            row: 0,
+        })
+    }
 
-        let mut masked_pixels = Vec::with_capacity(size);
-        for _ in 0..size {
-            let value = buf.read_u16::<LE>()?.into();
-            masked_pixels.push(MaskedPixel(value));
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    /// Bytes of RGBA in each decoded row
+    pub fn row_len(&self) -> usize {
+        self.header.width as usize * 4
+    }
+
+    /// Decode the next row into the start of `out`, returning its index, or
+    /// `None` once every row has been decoded
+    pub fn read_row(&mut self, out: &mut [u8]) -> anyhow::Result<Option<u32>> {
+        if self.row == self.header.height {
+            return Ok(None);
+        }
+        anyhow::ensure!(out.len() >= self.row_len(),
+            "row buffer holds {} bytes but a row is {}", out.len(),
+            self.row_len());
+
+        if self.row == 0 && self.header.has_palette() {
+            let size = self.header.width as u64 * self.header.height as u64;
+            let skipped = std::io::copy(
+                &mut (&mut self.reader).take(size), &mut std::io::sink())?;
+            anyhow::ensure!(skipped == size,
+                "expected {size} palette indices but found {skipped}");
+            let bitmask = BitMask::try_new(&mut self.reader)
+                .context("masked pixel bitmask")?;
+            self.unpacker = Unpacker::for_masked(&bitmask);
+        }
+
+        self.reader.read_exact(&mut self.raw)
+            .with_context(|| format!("row {}", self.row))?;
+        let values = self.raw.chunks_exact(2)
+            .map(|x| u16::from_le_bytes([x[0], x[1]]));
+        for (value, out) in values.zip(out.chunks_exact_mut(4)) {
+            out.copy_from_slice(&self.unpacker.rgba8(value.into()));
         }
-        rsb.masked_pixels = Some(masked_pixels);
+
+        self.row += 1;
+        Ok(Some(self.row - 1))
     }
+}
 
-    Ok(rsb)
+/// Write `rsb` in the same layout `read` expects. Only versions 0 and 1 are
+/// supported, like `read`.
+pub fn write(filename: &Path, rsb: &Rsb) -> anyhow::Result<()> {
+    anyhow::ensure!(rsb.version < 2, "RSB version {} not supported", rsb.version);
+    anyhow::ensure!(rsb.pixels.len() == rsb.size(),
+        "expected {} pixels but have {}", rsb.size(), rsb.pixels.len());
+
+    let file = File::create(filename).context("could not create RSB file")?;
+    let mut buf = BufWriter::new(file);
+
+    buf.write_u32::<LE>(rsb.version)?;
+    buf.write_u32::<LE>(rsb.width)?;
+    buf.write_u32::<LE>(rsb.height)?;
+    let palette = if rsb.version == 0 {
+        let palette = rsb.palette.context("version 0 RSB requires a palette")?;
+        buf.write_u32::<LE>(palette)?;
+        palette
+    } else {
+        0
+    };
+
+    if palette == 1 {
+        let colors = rsb.palette_colors.as_ref()
+            .context("palette RSB is missing palette colors")?;
+        anyhow::ensure!(colors.len() == 256,
+            "expected 256 palette colors but have {}", colors.len());
+        for color in colors {
+            buf.write_all(&[color.b, color.g, color.r, color.a])?;
+        }
+    } else {
+        rsb.bitmask.write(&mut buf)?;
+    }
+
+    match &rsb.pixels {
+        Pixels::Indexed(indices) => buf.write_all(indices)?,
+        Pixels::Bgra16(values) => {
+            for &value in values {
+                buf.write_u16::<LE>(value)?;
+            }
+        }
+        Pixels::Argb32(values) => {
+            for &value in values {
+                buf.write_u16::<LE>(value as u16)?;
+            }
+        }
+    }
+
+    if palette == 1 {
+        let masked = rsb.masked_pixels.as_ref()
+            .context("palette RSB is missing masked pixels")?;
+        rsb.bitmask.write(&mut buf)?;
+        for pixel in masked {
+            buf.write_u16::<LE>(pixel.0)?;
+        }
+    }
+
+    buf.flush().context("failed to write RSB file")
 }
 
 #[derive(Clone, Debug, Default)]
@@ -116,11 +310,16 @@ pub struct Rsb {
     pub bitmask: BitMask,
 
     /// Pixel data of size `width * height`
-    pub pixels: Vec<Pixel>,
+    pub pixels: Pixels,
 
     /// `width * height` of image data when `version == 0` and `palette == 1`.
     /// The `bitmask` must be used to extract the RGBA data.
     pub masked_pixels: Option<Vec<MaskedPixel>>,
+
+    /// How the decoded channel values are meant to be interpreted. This
+    /// isn't stored in the file; `read` picks `ColorModel::for_bitmask` and
+    /// callers that know better, e.g. about a colour key, may change it.
+    pub color_model: ColorModel,
 }
 
 impl Rsb {
@@ -128,9 +327,339 @@ impl Rsb {
     pub fn size(&self) -> usize {
         (self.width * self.height) as _
     }
+
+    /// Decode to 8-bit RGBA, row by row. Channels are scaled up from their
+    /// `bitmask` depth and missing alpha is opaque. Palette RSBs decode their
+    /// full-colour `masked_pixels` rather than the 8-bit copy.
+    pub fn to_rgba8(&self) -> Vec<u8> {
+        match &self.masked_pixels {
+            Some(masked) => self.masked_rgba8(masked),
+            None => self.pixels_rgba8(),
+        }
+    }
+
+    /// Decode one `Channel` as 8-bit RGBA, or `None` if this RSB doesn't
+    /// have it. Single channels are greyscale and, like `Channel::Color`,
+    /// come from `pixels`; the masked image is only shown by
+    /// `Channel::Masked`.
+    pub fn channel_rgba8(&self, channel: Channel) -> Option<Vec<u8>> {
+        let grey = |i: usize| {
+            self.pixels_rgba8().chunks_exact(4)
+                .flat_map(|p| [p[i], p[i], p[i], 255])
+                .collect()
+        };
+        Some(match channel {
+            Channel::Color => self.pixels_rgba8(),
+            Channel::Red => grey(0),
+            Channel::Green => grey(1),
+            Channel::Blue => grey(2),
+            Channel::Alpha => grey(3),
+            Channel::PaletteIndex => {
+                self.palette_colors.as_ref()?;
+                self.pixels.as_indexed()?.iter()
+                    .flat_map(|&i| [i, i, i, 255])
+                    .collect()
+            }
+            Channel::Masked => self.masked_rgba8(self.masked_pixels.as_ref()?),
+        })
+    }
+
+    /// `pixels` as RGBA. Palette indices are looked up in `palette_colors`.
+    /// Palette alpha is ignored since it isn't known what it means.
+    fn pixels_rgba8(&self) -> Vec<u8> {
+        let unpacker = Unpacker::for_pixels(&self.bitmask);
+        match &self.pixels {
+            Pixels::Indexed(indices) => {
+                let palette = self.palette_colors.as_deref().unwrap_or(&[]);
+                indices.iter()
+                    .flat_map(|&i| palette.get(i as usize)
+                        .map_or([0, 0, 0, 255], |c| [c.r, c.g, c.b, 255]))
+                    .collect()
+            }
+            Pixels::Bgra16(values) => unpacker.decode(values),
+            Pixels::Argb32(values) => unpacker.decode(values),
+        }
+    }
+
+    fn masked_rgba8(&self, masked: &[MaskedPixel]) -> Vec<u8> {
+        let unpacker = Unpacker::for_masked(&self.bitmask);
+        let mut rgba = Vec::with_capacity(masked.len() * 4);
+        for pixel in masked {
+            rgba.extend_from_slice(&unpacker.rgba8(pixel.0.into()));
+        }
+        rgba
+    }
+
+    /// Encode 8-bit RGBA pixels as a version 1 RSB with the channel layout
+    /// of `bitmask`, e.g. 5/6/5/0 for opaque or 4/4/4/4 for translucent
+    /// textures.
+    pub fn from_rgba8(
+        width: u32,
+        height: u32,
+        rgba: &[u8],
+        bitmask: BitMask,
+    ) -> anyhow::Result<Self> {
+        let bytes = (width as usize).checked_mul(height as usize)
+            .and_then(|size| size.checked_mul(4))
+            .with_context(|| format!("{width}x{height} image is too large"))?;
+        anyhow::ensure!(rgba.len() == bytes,
+            "expected {bytes} RGBA bytes but have {}", rgba.len());
+        anyhow::ensure!(bitmask.bits() == 16,
+            "only 16-bit RSB pixels are supported, not {}", bitmask.bits());
+
+        let pixels = rgba.chunks_exact(4)
+            .map(|p| {
+                // BGRA order from the lowest bits up
+                let b = quantize(p[2], bitmask.b);
+                let g = quantize(p[1], bitmask.g) << bitmask.b;
+                let r = quantize(p[0], bitmask.r) << (bitmask.b + bitmask.g);
+                let a = quantize(p[3], bitmask.a)
+                    << (bitmask.b + bitmask.g + bitmask.r);
+                (b | g | r | a) as u16
+            })
+            .collect();
+
+        Ok(Self {
+            filename: PathBuf::new(),
+            version: 1,
+            width,
+            height,
+            palette: None,
+            palette_colors: None,
+            color_model: ColorModel::for_bitmask(&bitmask),
+            bitmask,
+            pixels: Pixels::Bgra16(pixels),
+            masked_pixels: None,
+        })
+    }
+
+    /// `to_rgba8` with the `color_model` alpha applied: colour keyed texels
+    /// become transparent, cutout alpha snaps to 0 or 255, premultiplied
+    /// colour is divided back out and opaque RSBs ignore their alpha bits.
+    /// Colour values keep their transfer function.
+    pub fn to_rgba8_straight(&self) -> Vec<u8> {
+        let mut rgba = self.to_rgba8();
+        for p in rgba.chunks_exact_mut(4) {
+            match self.color_model.alpha {
+                AlphaMode::Straight => {}
+                AlphaMode::Premultiplied => {
+                    let a = p[3] as u32;
+                    for c in &mut p[..3] {
+                        *c = match a {
+                            0 => 0,
+                            a => (*c as u32 * 255 / a).min(255) as u8,
+                        };
+                    }
+                }
+                AlphaMode::Cutout => p[3] = if p[3] >= 128 { 255 } else { 0 },
+                AlphaMode::Opaque => p[3] = 255,
+                AlphaMode::ColorKey(key) => {
+                    p[3] = if p[..3] == key { 0 } else { 255 };
+                }
+            }
+        }
+        rgba
+    }
+
+    /// Straight alpha RGBA in `0.0..=1.0` with linear-light colour, the form
+    /// lighting and filtering should work in. See `to_rgba8_straight`.
+    pub fn to_linear_f32(&self) -> Vec<[f32; 4]> {
+        let transfer = self.color_model.transfer;
+        self.to_rgba8_straight()
+            .chunks_exact(4)
+            .map(|p| {
+                let c = |x: u8| transfer.to_linear(x as f32 / 255.0);
+                [c(p[0]), c(p[1]), c(p[2]), p[3] as f32 / 255.0]
+            })
+            .collect()
+    }
+
+    /// `to_linear_f32` with colour multiplied by alpha, ready for blending
+    pub fn to_premultiplied_f32(&self) -> Vec<[f32; 4]> {
+        self.to_linear_f32()
+            .into_iter()
+            .map(|[r, g, b, a]| [r * a, g * a, b * a, a])
+            .collect()
+    }
+}
+
+/// How an RSB's decoded channel values are to be interpreted. The format
+/// only stores raw bit fields so this is a convention, not file data.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ColorModel {
+    pub transfer: Transfer,
+    pub alpha: AlphaMode,
+}
+
+impl ColorModel {
+    /// The model a texture with this channel layout almost certainly has:
+    /// sRGB colour like every texture the games' artists painted, no alpha
+    /// without alpha bits, cutout alpha for 1-bit (5551) and straight alpha
+    /// otherwise.
+    pub fn for_bitmask(bitmask: &BitMask) -> Self {
+        let alpha = match bitmask.a {
+            0 => AlphaMode::Opaque,
+            1 => AlphaMode::Cutout,
+            _ => AlphaMode::Straight,
+        };
+        Self { transfer: Transfer::Srgb, alpha }
+    }
+}
+
+/// The encoding of colour channel values
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Transfer {
+    /// Gamma encoded, as displayed by the monitors the textures were made on
+    #[default]
+    Srgb,
+    /// Proportional to light, e.g. for data textures
+    Linear,
+}
+
+impl Transfer {
+    /// Decode a `0.0..=1.0` channel value to linear light
+    pub fn to_linear(self, x: f32) -> f32 {
+        match self {
+            Self::Linear => x,
+            Self::Srgb if x <= 0.04045 => x / 12.92,
+            Self::Srgb => ((x + 0.055) / 1.055).powf(2.4),
+        }
+    }
+
+    /// Encode a `0.0..=1.0` linear light value
+    pub fn from_linear(self, x: f32) -> f32 {
+        match self {
+            Self::Linear => x,
+            Self::Srgb if x <= 0.0031308 => x * 12.92,
+            Self::Srgb => 1.055 * x.powf(1.0 / 2.4) - 0.055,
+        }
+    }
+}
+
+/// What the alpha channel means
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AlphaMode {
+    /// Colour is independent of alpha
+    #[default]
+    Straight,
+    /// Colour has already been multiplied by alpha
+    Premultiplied,
+    /// Alpha is a mask: texels under half alpha are fully transparent and
+    /// the rest fully opaque. How 1-bit alpha is treated.
+    Cutout,
+    /// Any alpha bits are ignored
+    Opaque,
+    /// There's no alpha channel; texels of exactly this 8-bit RGB colour are
+    /// transparent and the rest opaque
+    ColorKey([u8; 3]),
+}
+
+impl AlphaMode {
+    /// The magenta colour key
+    pub const MAGENTA_KEY: Self = Self::ColorKey([255, 0, 255]);
+    /// The black colour key
+    pub const BLACK_KEY: Self = Self::ColorKey([0, 0, 0]);
+}
+
+/// A displayable part of an RSB, see `Rsb::channel_rgba8`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Channel {
+    /// `pixels` in full colour, with palette RSBs looked up in their palette
+    Color,
+    Red,
+    Green,
+    Blue,
+    Alpha,
+    /// The raw 8-bit palette indices. Only for palette RSBs.
+    PaletteIndex,
+    /// The secondary `masked_pixels` image. Only for palette RSBs.
+    Masked,
+}
+
+/// Decodes packed 16 or 32-bit pixels to 8-bit RGBA. The channel shifts and
+/// `expand` results are worked out once per image so that each texel is a
+/// few shifts and table lookups.
+struct Unpacker {
+    /// Shift, mask and 8-bit value table of each of R, G, B and A
+    channels: [(u32, u32, Vec<u8>); 4],
+}
+
+impl Unpacker {
+    /// The layout of `Rsb::pixels`, BGRA or ARGB depending on `bitmask`
+    fn for_pixels(bitmask: &BitMask) -> Self {
+        let BitMask { r, g, b, a } = *bitmask;
+        if bitmask.is_argb() {
+            Self::new(bitmask, [a, a + r, a + r + g, 0])
+        } else {
+            Self::new(bitmask, [b + g, b, 0, b + g + r])
+        }
+    }
+
+    /// The layout of `Rsb::masked_pixels`, red in the lowest bits
+    fn for_masked(bitmask: &BitMask) -> Self {
+        let BitMask { r, g, b, .. } = *bitmask;
+        Self::new(bitmask, [0, r, r + g, r + g + b])
+    }
+
+    fn new(bitmask: &BitMask, shifts: [u32; 4]) -> Self {
+        let bits = [bitmask.r, bitmask.g, bitmask.b, bitmask.a];
+        let channels = std::array::from_fn(|i| {
+            let default = if i == 3 { 255 } else { 0 };
+            let bits = bits[i];
+            if bits == 0 {
+                // Every value maps to the default
+                return (0, 0, vec![default]);
+            }
+            // Channels wider than a byte aren't worth a table
+            let mask = u32::MAX >> (32 - bits);
+            let table = (0..=mask.min(0xff))
+                .map(|value| expand(Some(value), bits, default))
+                .collect();
+            (shifts[i], mask, table)
+        });
+        Self { channels }
+    }
+
+    #[inline]
+    fn rgba8(&self, value: u32) -> [u8; 4] {
+        self.channels.each_ref().map(|(shift, mask, table)| {
+            let channel = (value >> shift) & mask;
+            match table.get(channel as usize) {
+                Some(&rgba) => rgba,
+                None => expand(Some(channel), mask.count_ones(), 0),
+            }
+        })
+    }
+
+    fn decode<T: Copy + Into<u32>>(&self, values: &[T]) -> Vec<u8> {
+        let mut rgba = Vec::with_capacity(values.len() * 4);
+        for &value in values {
+            rgba.extend_from_slice(&self.rgba8(value.into()));
+        }
+        rgba
+    }
+}
+
+/// Scale a `bits` deep channel value up to 8 bits, or `default` when the
+/// channel is absent.
+fn expand(value: Option<u32>, bits: u32, default: u8) -> u8 {
+    match value {
+        Some(value) if bits > 0 => {
+            let max = u64::MAX >> (64 - bits);
+            ((value as u64 * 255 + max / 2) / max) as u8
+        }
+        _ => default,
+    }
+}
+
+/// Scale an 8-bit channel value down to `bits` deep
+fn quantize(value: u8, bits: u32) -> u32 {
+    let max = (1u32 << bits) - 1;
+    (value as u32 * max + 127) / 255
 }
 
 impl std::fmt::Display for Rsb {
+    #[allow(clippy::write_with_newline)]
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{} {{\n", self.filename.display())?;
         write!(f, "  version: {}\n", self.version)?;
@@ -144,6 +673,8 @@ impl std::fmt::Display for Rsb {
         write!(f, "  RGBA bits: {}/{}/{}/{}\n",
             self.bitmask.r, self.bitmask.g, self.bitmask.b, self.bitmask.a)?;
         write!(f, "  pixels: {}\n", self.pixels.len())?;
+        write!(f, "  color model: {:?} {:?}\n", self.color_model.transfer,
+            self.color_model.alpha)?;
         if let Some(masked) = &self.masked_pixels {
             write!(f, "  masked pixel count: {}\n", masked.len())?;
         }
@@ -153,7 +684,7 @@ impl std::fmt::Display for Rsb {
 
 /// The color depth bitmask. Use this to figure out the bit sizes of the RGBA
 /// channels in pixel data.
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
 pub struct BitMask {
     // TODO yank pub
     pub r: u32,
@@ -163,13 +694,28 @@ pub struct BitMask {
 }
 
 impl BitMask {
-    fn try_new(buf: &mut Cursor<Vec<u8>>) -> anyhow::Result<Self> {
-        Ok(Self {
+    fn try_new(buf: &mut impl Read) -> anyhow::Result<Self> {
+        let bitmask = Self {
             r: buf.read_u32::<LE>()?,
             g: buf.read_u32::<LE>()?,
             b: buf.read_u32::<LE>()?,
             a: buf.read_u32::<LE>()?,
-        })
+        };
+        // Channels are unpacked from at most a 32-bit value
+        let bits = [bitmask.g, bitmask.b, bitmask.a].into_iter()
+            .try_fold(bitmask.r, u32::checked_add);
+        anyhow::ensure!(bits.is_some_and(|x| x <= 32),
+            "RGBA bits {}/{}/{}/{} are wider than 32 bits", bitmask.r,
+            bitmask.g, bitmask.b, bitmask.a);
+        Ok(bitmask)
+    }
+
+    fn write(&self, buf: &mut impl Write) -> anyhow::Result<()> {
+        buf.write_u32::<LE>(self.r)?;
+        buf.write_u32::<LE>(self.g)?;
+        buf.write_u32::<LE>(self.b)?;
+        buf.write_u32::<LE>(self.a)?;
+        Ok(())
     }
 
     /// ARGB order is used for pixel data when `bitmask` channel depths sum to
@@ -199,7 +745,96 @@ impl PaletteColor {
     }
 }
 
-#[derive(Clone, Debug)]
+/// `Rsb::pixels` as one packed buffer in the file's own pixel format, rather
+/// than a tagged `Pixel` per texel. Use the typed slices for bulk work and
+/// `get` or `iter` where a `Pixel` is more convenient.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Pixels {
+    /// 8-bit palette indices. When `version` is 0 and `palette` is 1.
+    Indexed(Vec<u8>),
+
+    /// Blue, green, red and alpha from the lowest bits up. Use `bitmask`
+    /// fields to know how many bits each channel is.
+    Bgra16(Vec<u16>),
+
+    /// Alpha, red, green and blue from the lowest bits up. When `bitmask`
+    /// channels sum to 32 bits.
+    Argb32(Vec<u32>),
+}
+
+impl Default for Pixels {
+    fn default() -> Self {
+        Self::Bgra16(Vec::new())
+    }
+}
+
+impl Pixels {
+    pub fn len(&self) -> usize {
+        match self {
+            Self::Indexed(x) => x.len(),
+            Self::Bgra16(x) => x.len(),
+            Self::Argb32(x) => x.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Bytes of pixel data held
+    pub fn byte_len(&self) -> usize {
+        match self {
+            Self::Indexed(x) => std::mem::size_of_val(x.as_slice()),
+            Self::Bgra16(x) => std::mem::size_of_val(x.as_slice()),
+            Self::Argb32(x) => std::mem::size_of_val(x.as_slice()),
+        }
+    }
+
+    pub fn get(&self, index: usize) -> Option<Pixel> {
+        match self {
+            Self::Indexed(x) => {
+                x.get(index).map(|&i| Pixel::PaletteColorIndex(i))
+            }
+            Self::Bgra16(x) => x.get(index).map(|&v| Pixel::Bgra(v.into())),
+            Self::Argb32(x) => x.get(index).map(|&v| Pixel::Argb(v)),
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Pixel> + '_ {
+        let (indexed, bgra, argb) = match self {
+            Self::Indexed(x) => (x.as_slice(), &[][..], &[][..]),
+            Self::Bgra16(x) => (&[][..], x.as_slice(), &[][..]),
+            Self::Argb32(x) => (&[][..], &[][..], x.as_slice()),
+        };
+        indexed.iter().map(|&i| Pixel::PaletteColorIndex(i))
+            .chain(bgra.iter().map(|&v| Pixel::Bgra(v.into())))
+            .chain(argb.iter().map(|&v| Pixel::Argb(v)))
+    }
+
+    pub fn as_indexed(&self) -> Option<&[u8]> {
+        match self {
+            Self::Indexed(x) => Some(x),
+            _ => None,
+        }
+    }
+
+    pub fn as_bgra16(&self) -> Option<&[u16]> {
+        match self {
+            Self::Bgra16(x) => Some(x),
+            _ => None,
+        }
+    }
+
+    pub fn as_argb32(&self) -> Option<&[u32]> {
+        match self {
+            Self::Argb32(x) => Some(x),
+            _ => None,
+        }
+    }
+}
+
+/// One texel of `Pixels`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Pixel {
     /// When `version` is 0 and `palette` is 1
     PaletteColorIndex(u8),
@@ -266,7 +901,7 @@ impl Pixel {
     // TODO: make this generic and replace `MaskedPixel::masked`
     fn masked(value: u32, channel: u32, shift: u32) -> Option<u32> {
         if channel > 0 {
-            let mask = ((1u32 << channel) - 1) << shift;
+            let mask = (u32::MAX >> (32 - channel)) << shift;
             Some((value & mask) >> shift)
         } else {
             None
@@ -280,7 +915,7 @@ impl Pixel {
 /// Example:
 /// `BitMask { r: 5, g: 6, b: 5, a: 0 }` means the `MaskedPixel` data contains
 /// red (5 bits), green (6 bits), blue (5 bits) and no alpha bits.
-#[derive(Clone, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct MaskedPixel(u16);
 
 impl MaskedPixel {
@@ -311,6 +946,7 @@ impl MaskedPixel {
 }
 
 #[cfg(test)]
+#[allow(clippy::useless_conversion)]
 mod tests {
     use super::*;
     use std::io::Cursor;
@@ -427,4 +1063,214 @@ mod tests {
         assert_eq!(pixels[3].r(&bitmask), Some(4));
         assert_eq!(pixels[3].a(&bitmask), None);
     }
+
+    #[test]
+    fn rgba8_round_trips_through_4444_and_565() {
+        // Every 4-bit value scales to 8 bits and back exactly
+        let rgba = (0..16u8).flat_map(|x| [x * 17, 255 - x * 17, x * 17, x * 17])
+            .collect::<Vec<_>>();
+        let bitmask = BitMask { r: 4, g: 4, b: 4, a: 4 };
+        let rsb = Rsb::from_rgba8(4, 4, &rgba, bitmask).unwrap();
+        assert_eq!(rsb.to_rgba8(), rgba);
+
+        // 5/6/5 has no alpha so it decodes as opaque
+        let bitmask = BitMask { r: 5, g: 6, b: 5, a: 0 };
+        let rsb = Rsb::from_rgba8(1, 1, &[255, 0, 255, 0], bitmask).unwrap();
+        assert_eq!(rsb.pixels.get(0), Some(Pixel::Bgra(0xf81f)));
+        assert_eq!(rsb.to_rgba8(), [255, 0, 255, 255]);
+
+        // Sizes that overflow are errors rather than wrapping
+        let bitmask = BitMask { r: 5, g: 6, b: 5, a: 0 };
+        let error = Rsb::from_rgba8(u32::MAX, u32::MAX, &[], bitmask)
+            .unwrap_err();
+        assert!(error.to_string().ends_with("image is too large"), "{error}");
+    }
+
+    #[test]
+    fn channels_decode_as_greyscale() {
+        let bitmask = BitMask { r: 4, g: 4, b: 4, a: 4 };
+        let rsb = Rsb::from_rgba8(1, 1, &[255, 0, 136, 17], bitmask).unwrap();
+        let channel = |channel| rsb.channel_rgba8(channel).unwrap();
+        assert_eq!(channel(Channel::Color), [255, 0, 136, 17]);
+        assert_eq!(channel(Channel::Red), [255, 255, 255, 255]);
+        assert_eq!(channel(Channel::Green), [0, 0, 0, 255]);
+        assert_eq!(channel(Channel::Blue), [136, 136, 136, 255]);
+        assert_eq!(channel(Channel::Alpha), [17, 17, 17, 255]);
+        assert_eq!(rsb.channel_rgba8(Channel::PaletteIndex), None);
+        assert_eq!(rsb.channel_rgba8(Channel::Masked), None);
+    }
+
+    #[test]
+    fn palette_channels_come_from_pixels() {
+        // A 1x1 palette RSB whose entry 0 is red and whose masked pixel is
+        // blue
+        let mut bytes = Vec::new();
+        for x in [0, 1, 1, 1] {
+            bytes.write_u32::<LE>(x).unwrap();
+        }
+        bytes.extend([0, 0, 255, 255]);
+        bytes.extend([0; 255 * 4]);
+        bytes.push(0);
+        for x in [5, 6, 5, 0] {
+            bytes.write_u32::<LE>(x).unwrap();
+        }
+        bytes.write_u16::<LE>(0xf800).unwrap();
+        let rsb = read_bytes(&bytes).unwrap();
+        let channel = |channel| rsb.channel_rgba8(channel).unwrap();
+        assert_eq!(channel(Channel::Color), [255, 0, 0, 255]);
+        assert_eq!(channel(Channel::Red), [255, 255, 255, 255]);
+        assert_eq!(channel(Channel::Blue), [0, 0, 0, 255]);
+        assert_eq!(channel(Channel::Masked), [0, 0, 255, 255]);
+    }
+
+    #[test]
+    fn color_model_resolves_alpha() {
+        let bitmask = BitMask { r: 5, g: 6, b: 5, a: 0 };
+        let mut rsb = Rsb::from_rgba8(2, 1, &[255, 0, 255, 255, 0, 0, 0, 255],
+            bitmask).unwrap();
+        assert_eq!(rsb.color_model.alpha, AlphaMode::Opaque);
+        rsb.color_model.alpha = AlphaMode::MAGENTA_KEY;
+        assert_eq!(rsb.to_rgba8_straight(), [255, 0, 255, 0, 0, 0, 0, 255]);
+        rsb.color_model.alpha = AlphaMode::BLACK_KEY;
+        assert_eq!(rsb.to_rgba8_straight(), [255, 0, 255, 255, 0, 0, 0, 0]);
+
+        let bitmask = BitMask { r: 5, g: 5, b: 5, a: 1 };
+        let rsb = Rsb::from_rgba8(1, 1, &[255, 255, 255, 0], bitmask).unwrap();
+        assert_eq!(rsb.color_model.alpha, AlphaMode::Cutout);
+        assert_eq!(rsb.to_rgba8_straight(), [255, 255, 255, 0]);
+
+        let bitmask = BitMask { r: 4, g: 4, b: 4, a: 4 };
+        let mut rsb = Rsb::from_rgba8(1, 1, &[119, 0, 0, 136], bitmask)
+            .unwrap();
+        rsb.color_model.alpha = AlphaMode::Premultiplied;
+        assert_eq!(rsb.to_rgba8_straight(), [223, 0, 0, 136]);
+        let [r, _, _, a] = rsb.to_premultiplied_f32()[0];
+        assert!((a - 136.0 / 255.0).abs() < 1e-6);
+        assert!((r - Transfer::Srgb.to_linear(223.0 / 255.0) * a).abs() < 1e-6);
+    }
+
+    #[test]
+    fn srgb_round_trips() {
+        for i in 0..=255 {
+            let x = i as f32 / 255.0;
+            let y = Transfer::Srgb.from_linear(Transfer::Srgb.to_linear(x));
+            assert!((x - y).abs() < 1e-5, "{x} -> {y}");
+        }
+    }
+
+    fn decode_rows(bytes: &[u8]) -> Vec<u8> {
+        let mut decoder = RowDecoder::new(bytes).unwrap();
+        let mut row = vec![0; decoder.row_len()];
+        let mut rgba = Vec::new();
+        let mut expected = 0;
+        while let Some(index) = decoder.read_row(&mut row).unwrap() {
+            assert_eq!(index, expected);
+            expected += 1;
+            rgba.extend_from_slice(&row);
+        }
+        assert_eq!(expected, decoder.header().height);
+        rgba
+    }
+
+    #[test]
+    fn row_decoder_matches_to_rgba8() {
+        for path in ["data/texture/faces/Chavez_hrt_face.RSB",
+            "data/texture/faces/Chavez_hrt_face_blink.rsb"]
+        {
+            let bytes = std::fs::read(path).unwrap();
+            let rsb = read(Path::new(path)).unwrap();
+            assert_eq!(decode_rows(&bytes), rsb.to_rgba8(), "{path}");
+        }
+
+        // A 2x1 palette RSB: palette, indices, then the masked image
+        let mut bytes = Vec::new();
+        for x in [0, 2, 1, 1] {
+            bytes.write_u32::<LE>(x).unwrap();
+        }
+        bytes.extend([0x10; 256 * 4]);
+        bytes.extend([7, 9]);
+        for x in [5, 6, 5, 0] {
+            bytes.write_u32::<LE>(x).unwrap();
+        }
+        for x in [0xf800, 0x001f] {
+            bytes.write_u16::<LE>(x).unwrap();
+        }
+        // `MaskedPixel` has red in the low bits
+        assert_eq!(decode_rows(&bytes), [0, 0, 255, 255, 255, 0, 0, 255]);
+
+        let mut truncated = RowDecoder::new(&bytes[..bytes.len() - 1]).unwrap();
+        assert!(truncated.read_row(&mut [0; 8]).is_err());
+    }
+
+    #[test]
+    fn pixels_are_stored_packed() {
+        let rsb = read(Path::new("data/texture/faces/Chavez_hrt_face.RSB"))
+            .unwrap();
+        let values = rsb.pixels.as_bgra16().unwrap();
+        assert_eq!(rsb.pixels.byte_len(), 64 * 64 * 2);
+        assert!(rsb.pixels.as_indexed().is_none());
+        assert!(rsb.pixels.iter()
+            .zip(values)
+            .all(|(pixel, &value)| pixel == Pixel::Bgra(value.into())));
+        assert_eq!(rsb.pixels.get(values.len()), None);
+
+        let indexed = Pixels::Indexed(vec![7, 9]);
+        assert_eq!(indexed.iter().collect::<Vec<_>>(),
+            [Pixel::PaletteColorIndex(7), Pixel::PaletteColorIndex(9)]);
+    }
+
+    #[test]
+    fn hostile_bytes_are_errors() {
+        let bytes = std::fs::read("data/texture/faces/Chavez_hrt_face.RSB")
+            .unwrap();
+        assert!(read_bytes(&bytes).is_ok());
+        for len in 0..bytes.len() {
+            assert!(read_bytes(&bytes[..len]).is_err(), "{len} bytes");
+        }
+
+        let mut wide = bytes.clone();
+        // Red bits of a version 1 header
+        wide[12..16].copy_from_slice(&40u32.to_le_bytes());
+        let error = read_bytes(&wide).unwrap_err();
+        assert!(format!("{error:#}").contains("wider than 32 bits"));
+    }
+
+    #[test]
+    fn huge_dimensions_are_rejected_before_allocating() {
+        // A 20-byte version 1 header claiming 65535x65535 4444 texels
+        let mut bytes = Vec::new();
+        for x in [1, 65535, 65535, 4, 4, 4, 4] {
+            bytes.extend(u32::to_le_bytes(x));
+        }
+        let error = read_bytes(&bytes).unwrap_err();
+        assert_eq!(error.to_string(),
+            "65535x65535 texture exceeds the limit of 8192x8192");
+        let error = read_bytes_with_limits(&bytes, &Limits::none())
+            .unwrap_err();
+        assert_eq!(error.to_string(), "4294836225 pixels need at least \
+            8589672450 bytes but only 0 remain");
+        assert!(RowDecoder::new(bytes.as_slice()).is_err());
+    }
+
+    #[test]
+    fn palette_entries_are_four_bytes_apart() {
+        // Version 0, 1x1, palette 1, then 256 BGRA entries where entry i
+        // is (i, i + 1, i + 2, 255)
+        let mut bytes = Vec::new();
+        for x in [0, 1, 1, 1] {
+            bytes.extend(u32::to_le_bytes(x));
+        }
+        for i in 0..=255u8 {
+            bytes.extend([i, i.wrapping_add(1), i.wrapping_add(2), 255]);
+        }
+        let header = Header::read(&mut bytes.as_slice(), &Limits::default())
+            .unwrap();
+        let colors = header.palette_colors.unwrap();
+        assert_eq!(colors.len(), 256);
+        for (i, color) in colors.iter().enumerate() {
+            let i = i as u8;
+            assert_eq!([color.b, color.g, color.r, color.a],
+                [i, i.wrapping_add(1), i.wrapping_add(2), 255]);
+        }
+    }
 }
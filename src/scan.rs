@@ -0,0 +1,335 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use anyhow::Context;
+use rayon::prelude::*;
+use serde::Serialize;
+
+use crate::{map, rsb};
+
+/// The file formats a scan parses, by extension
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub enum Format {
+    Rsb,
+    Map,
+}
+
+impl Format {
+    /// The format of `path` from its case-insensitive extension
+    pub fn of(path: &Path) -> Option<Self> {
+        let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+        match ext.as_str() {
+            "rsb" => Some(Self::Rsb),
+            "map" => Some(Self::Map),
+            _ => None,
+        }
+    }
+}
+
+/// Every RSB and MAP file under `root`, sorted, and the entries that couldn't
+/// be read on the way. Game installs mix `.RSB` and `.rsb` so extensions are
+/// matched case-insensitively.
+pub fn find(root: &Path) -> anyhow::Result<Found> {
+    // Escaped so directories like "Rogue Spear [GOG]" aren't read as patterns
+    let escaped = root.to_str()
+        .with_context(|| format!("invalid path {}", root.display()))?;
+    let pattern = Path::new(&glob::Pattern::escape(escaped))
+        .join("**").join("*");
+    let pattern = pattern.to_str()
+        .with_context(|| format!("invalid path {}", root.display()))?;
+    let mut found = Found::default();
+    for entry in glob::glob(pattern)? {
+        match entry {
+            Ok(path) if path.is_file() && Format::of(&path).is_some() => {
+                found.paths.push(path);
+            }
+            Ok(_) => {}
+            Err(e) => found.unreadable.push(Unreadable {
+                path: e.path().to_path_buf(),
+                error: e.error().to_string(),
+            }),
+        }
+    }
+    found.paths.sort();
+    Ok(found)
+}
+
+/// What `find` turned up under a root
+#[derive(Clone, Debug, Default)]
+pub struct Found {
+    pub paths: Vec<PathBuf>,
+    pub unreadable: Vec<Unreadable>,
+}
+
+impl Found {
+    /// `scan` the paths, with the unreadable entries in the report
+    pub fn scan(&self) -> ScanReport {
+        let mut report = scan(&self.paths);
+        report.unreadable = self.unreadable.clone();
+        report
+    }
+}
+
+/// A directory entry that couldn't be read, e.g. for lack of permission
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Unreadable {
+    pub path: PathBuf,
+    pub error: String,
+}
+
+/// Parse every RSB and MAP in `paths` in parallel. Paths of any other format
+/// are skipped.
+pub fn scan(paths: &[PathBuf]) -> ScanReport {
+    let now = Instant::now();
+    let scanned = paths.par_iter()
+        .filter_map(|path| Some(scan_file(path, Format::of(path)?)))
+        .collect::<Vec<_>>();
+
+    let mut report = ScanReport::default();
+    let mut failures = BTreeMap::<_, Vec<PathBuf>>::new();
+    for file in scanned {
+        let totals = match file.format {
+            Format::Rsb => &mut report.rsb,
+            Format::Map => &mut report.map,
+        };
+        totals.bytes += file.bytes;
+        totals.parse_time += file.parse_time;
+
+        match file.outcome {
+            Ok(Parsed::Rsb { version, palette, bitmask }) => {
+                totals.parsed += 1;
+                let palette = palette.map_or("nil".to_string(), |x| {
+                    x.to_string()
+                });
+                let version = format!("version={version} palette={palette}");
+                *report.rsb_versions.entry(version).or_default() += 1;
+                let (r, g, b, a) = bitmask;
+                let layout = format!("{r}/{g}/{b}/{a}");
+                *report.rsb_layouts.entry(layout).or_default() += 1;
+            }
+            Ok(Parsed::Map) => totals.parsed += 1,
+            Err(e) => {
+                totals.failed += 1;
+                let (section, kind) = classify(&e);
+                failures.entry((file.format, kind, section))
+                    .or_default()
+                    .push(file.path);
+            }
+        }
+    }
+
+    report.failures = failures.into_iter()
+        .map(|((format, kind, section), mut paths)| {
+            paths.sort();
+            FailureGroup { format, kind, section, paths }
+        })
+        .collect();
+    report.elapsed = now.elapsed();
+    report
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct ScanReport {
+    pub rsb: FormatTotals,
+    pub map: FormatTotals,
+    /// Number of RSBs per version and palette
+    pub rsb_versions: BTreeMap<String, usize>,
+    /// Number of RSBs per `BitMask` RGBA channel layout
+    pub rsb_layouts: BTreeMap<String, usize>,
+    /// Failed files grouped by format, error kind and section
+    pub failures: Vec<FailureGroup>,
+    /// Entries `find` couldn't read, so never scanned. See `Found::scan`.
+    pub unreadable: Vec<Unreadable>,
+    /// Wall-clock time for the whole scan
+    pub elapsed: Duration,
+}
+
+impl ScanReport {
+    /// Failed files and unreadable entries
+    pub fn failed(&self) -> usize {
+        self.rsb.failed + self.map.failed + self.unreadable.len()
+    }
+}
+
+impl fmt::Display for ScanReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (name, totals) in [("RSB", &self.rsb), ("MAP", &self.map)] {
+            writeln!(f, "{name}: {} parsed, {} failed, {} bytes in {:?}",
+                totals.parsed, totals.failed, totals.bytes, totals.parse_time)?;
+        }
+        for (version, count) in &self.rsb_versions {
+            writeln!(f, "{version:<24}: {count:>5} files")?;
+        }
+        for (layout, count) in &self.rsb_layouts {
+            writeln!(f, "RGBA {layout:<19}: {count:>5} files")?;
+        }
+        for group in &self.failures {
+            writeln!(f, "{:?} failures: {} files", group.format,
+                group.paths.len())?;
+            if !group.section.is_empty() {
+                writeln!(f, "  section: {}", group.section)?;
+            }
+            writeln!(f, "  error: {}", group.kind)?;
+            for path in &group.paths {
+                writeln!(f, "    {}", path.display())?;
+            }
+        }
+        if !self.unreadable.is_empty() {
+            writeln!(f, "Unreadable: {} entries", self.unreadable.len())?;
+            for entry in &self.unreadable {
+                writeln!(f, "    {}: {}", entry.path.display(), entry.error)?;
+            }
+        }
+        write!(f, "Scanned in {:?}", self.elapsed)
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct FormatTotals {
+    pub parsed: usize,
+    pub failed: usize,
+    /// Size of all files of this format, parsed or not
+    pub bytes: u64,
+    /// Time spent parsing summed across threads, so it can exceed `elapsed`
+    pub parse_time: Duration,
+}
+
+/// Files that failed with the same error in the same section
+#[derive(Clone, Debug, Serialize)]
+pub struct FailureGroup {
+    pub format: Format,
+    /// The innermost error message, e.g. "failed to fill whole buffer"
+    pub kind: String,
+    /// The outer error contexts joined with " > " and with numbers replaced
+    /// by `N`, e.g. "Geometry List > EIndices N > EIndex text". Empty when
+    /// the error has no context.
+    pub section: String,
+    pub paths: Vec<PathBuf>,
+}
+
+struct ScannedFile {
+    path: PathBuf,
+    format: Format,
+    bytes: u64,
+    parse_time: Duration,
+    outcome: anyhow::Result<Parsed>,
+}
+
+/// The few details of a parsed file the report keeps, so whole textures and
+/// maps aren't held in memory until the scan finishes.
+enum Parsed {
+    Rsb {
+        version: u32,
+        palette: Option<u32>,
+        bitmask: (u32, u32, u32, u32),
+    },
+    Map,
+}
+
+fn scan_file(path: &Path, format: Format) -> ScannedFile {
+    let bytes = std::fs::metadata(path).map_or(0, |x| x.len());
+    let now = Instant::now();
+    let outcome = match format {
+        Format::Rsb => rsb::read(path).map(|rsb| {
+            let b = &rsb.bitmask;
+            Parsed::Rsb {
+                version: rsb.version,
+                palette: rsb.palette,
+                bitmask: (b.r, b.g, b.b, b.a),
+            }
+        }),
+        Format::Map => map::read(path).map(|_| Parsed::Map),
+    };
+    ScannedFile {
+        path: path.to_path_buf(),
+        format,
+        bytes,
+        parse_time: now.elapsed(),
+        outcome,
+    }
+}
+
+/// Split an error into its section path and kind. See `FailureGroup`.
+fn classify(error: &anyhow::Error) -> (String, String) {
+    let mut chain = error.chain().map(|e| e.to_string()).collect::<Vec<_>>();
+    let kind = chain.pop().unwrap_or_default();
+    let section = chain.iter()
+        .map(|context| without_numbers(context))
+        .collect::<Vec<_>>()
+        .join(" > ");
+    (section, without_numbers(&kind))
+}
+
+/// Replace each run of digits with `N` so that e.g. "room 3 of 98" and
+/// "room 7 of 12" group together.
+fn without_numbers(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut in_number = false;
+    for c in s.chars() {
+        if c.is_ascii_digit() {
+            if !in_number {
+                out.push('N');
+            }
+            in_number = true;
+        } else {
+            out.push(c);
+            in_number = false;
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shipped_data_scans_without_failures() {
+        let found = find(Path::new("data")).unwrap();
+        assert!(found.unreadable.is_empty());
+        let report = found.scan();
+        assert_eq!(report.rsb.parsed, 2);
+        assert_eq!(report.map.parsed, 2);
+        assert_eq!(report.failed(), 0, "{report}");
+        assert_eq!(report.rsb_layouts.get("4/4/4/4"), Some(&2));
+    }
+
+    #[test]
+    fn roots_with_glob_characters_are_found() {
+        let root = std::env::temp_dir()
+            .join(format!("rogue-scan [GOG] {}", std::process::id()));
+        std::fs::create_dir_all(&root).unwrap();
+        let map = root.join("rm19.MAP");
+        std::fs::copy("data/map/rm19/rm19.map", &map).unwrap();
+        let found = find(&root);
+        std::fs::remove_dir_all(&root).unwrap();
+        assert_eq!(found.unwrap().paths, [map]);
+    }
+
+    #[test]
+    fn unreadable_entries_are_reported_as_failures() {
+        let found = Found {
+            paths: Vec::new(),
+            unreadable: vec![Unreadable {
+                path: PathBuf::from("data/locked"),
+                error: "Permission denied (os error 13)".into(),
+            }],
+        };
+        let report = found.scan();
+        assert_eq!(report.failed(), 1);
+        assert!(report.to_string()
+            .contains("data/locked: Permission denied (os error 13)"));
+    }
+
+    #[test]
+    fn failures_are_classified_by_section_and_kind() {
+        let error = anyhow::anyhow!("unknown texture mode address value: 7")
+            .context("material section header 12")
+            .context("Materials List");
+        let (section, kind) = classify(&error);
+        assert_eq!(section, "Materials List > material section header N");
+        assert_eq!(kind, "unknown texture mode address value: N");
+    }
+}